@@ -0,0 +1,65 @@
+use clap::Args;
+use db::database::{Database, Issue, Report};
+use db::error::Result;
+use std::env;
+use std::path::Path;
+
+/// List of arguments supported by the command.
+#[derive(Args)]
+pub struct Params {
+    #[clap(short, long, help = "Database directory")]
+    directory: Option<String>,
+    #[clap(short, long, help = "Repair found problems")]
+    repair: bool,
+    #[clap(short, long, help = "JSON output format")]
+    pub json: bool,
+}
+
+/// Output of the command
+pub type Output = Report;
+
+/// Print command's text output
+pub fn print_text_output(output: &Output) {
+    for issue in &output.issues {
+        match issue {
+            Issue::OrphanFile { path } => println!("Orphan file: {}", path.display()),
+            Issue::MissingFile { collection } => {
+                println!("Missing file of collection: {}", collection);
+            }
+            Issue::Unparsable { path, cause } => {
+                println!("Unparsable file: {} ({})", path.display(), cause);
+            }
+            Issue::ChecksumMismatch { collection } => {
+                println!("Checksum mismatch of collection: {}", collection);
+            }
+            Issue::TempFile { path } => println!("Temporary file: {}", path.display()),
+            Issue::TornRecord { path } => println!("Torn record at the end of: {}", path.display()),
+        }
+    }
+    for (original, quarantined) in &output.quarantined {
+        println!(
+            "Quarantined: {} -> {}",
+            original.display(),
+            quarantined.display()
+        );
+    }
+
+    if output.is_healthy() {
+        println!("No problems found");
+    } else if output.repaired {
+        println!("Repaired {} problem(s)", output.issues.len());
+    } else {
+        println!("Found {} problem(s)", output.issues.len());
+    }
+}
+
+/// Main entry of the command
+pub fn execute(params: &Params) -> Result<Output> {
+    // Check a database inside a specified directory or the current directory
+    // in case no argument was provided
+    let path = match &params.directory {
+        Some(directory) => Path::new(directory).to_path_buf(),
+        None => env::current_dir()?,
+    };
+    Database::check(path, params.repair)
+}
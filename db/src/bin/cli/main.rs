@@ -10,6 +10,7 @@
 //! - `Output` which describes what is returned as the result of running a command
 //! - `execute` which is used to invoke a command and return an output to the caller
 //! - `print_text_output` which prints out result(data) of a command in text mode
+//!
 //! The function for packing an output in json format is common across all commands, therefore
 //! it is not needed to implement the function for each command respectively. The only restriction
 //! is that `Output` must implement `Serialize` trait.
@@ -22,6 +23,7 @@ use db::error::Result;
 use serde::Serialize;
 use std::process;
 
+mod check;
 mod create;
 
 #[derive(Parser)]
@@ -34,6 +36,8 @@ struct Cli {
 enum Commands {
     #[clap(about = "Create an empty database")]
     Create(create::Params),
+    #[clap(about = "Check integrity of a database and optionally repair it")]
+    Check(check::Params),
 }
 
 #[derive(Serialize)]
@@ -117,5 +121,11 @@ fn main() {
             params.json,
             create::print_text_output,
         ),
+        Commands::Check(params) => do_execute(
+            check::execute,
+            params,
+            params.json,
+            check::print_text_output,
+        ),
     };
 }
@@ -0,0 +1,248 @@
+//! Encryption of database files.
+//!
+//! Files of an encrypted database are sealed with ChaCha20-Poly1305, which detects any tampering
+//! with their content. The key is derived from a passphrase with Argon2id. Parameters of the
+//! derivation are kept in an unencrypted header together with a verifier, which tells a wrong
+//! passphrase apart from a damaged file.
+
+use crate::error::{CustomKind, Error, Result};
+use argon2::{Algorithm, Argon2, Params, Version};
+use chacha20poly1305::aead::rand_core::RngCore;
+use chacha20poly1305::aead::{Aead, AeadCore, KeyInit, OsRng, Payload};
+use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce};
+use serde::{Deserialize, Serialize};
+use std::fmt::{Debug, Formatter};
+
+/// Unencrypted header of an encrypted database.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Header {
+    // Hex encoded salt of the key derivation
+    salt: String,
+    // Argon2id memory cost in KiB
+    memory: u32,
+    // Argon2id number of iterations
+    iterations: u32,
+    // Argon2id degree of parallelism
+    parallelism: u32,
+    // Hex encoded sealed verifier
+    verifier: String,
+}
+
+/// A cipher sealing and opening content of database files.
+pub struct Cipher {
+    aead: ChaCha20Poly1305,
+}
+
+impl Debug for Cipher {
+    // The key is never printed
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Cipher").finish_non_exhaustive()
+    }
+}
+
+impl Cipher {
+    // Length of a salt of the key derivation
+    const SALT_LEN: usize = 16;
+    // Length of a random nonce preceding sealed content
+    const NONCE_LEN: usize = 12;
+    // Plaintext of the verifier kept in the header
+    const VERIFIER: &'static [u8] = b"mint";
+
+    /// Derive a new key from a passphrase and return the cipher together with a header which
+    /// allows the key to be derived again.
+    ///
+    /// # Errors
+    /// The function returns [`CustomKind::InvalidArgument`] error if the key cannot be derived.
+    pub fn create(passphrase: &str) -> Result<(Self, Header)> {
+        let mut salt = [0; Self::SALT_LEN];
+        OsRng.fill_bytes(&mut salt);
+        let params = Params::default();
+
+        let cipher = Self::derive(passphrase, &salt, &params)?;
+        let header = Header {
+            salt: hex::encode(salt),
+            memory: params.m_cost(),
+            iterations: params.t_cost(),
+            parallelism: params.p_cost(),
+            verifier: hex::encode(cipher.seal(Self::VERIFIER, &[])?),
+        };
+        Ok((cipher, header))
+    }
+
+    /// Derive a key from a passphrase according to a header of an encrypted database.
+    ///
+    /// # Errors
+    /// The function returns [`CustomKind::WrongKey`] error if the passphrase does not match the
+    /// one the header has been created with. [`CustomKind::InvalidArgument`] error is returned
+    /// if the header is malformed.
+    pub fn open(passphrase: &str, header: &Header) -> Result<Self> {
+        let invalid_header = |cause: &str| {
+            Error::custom_err(
+                CustomKind::InvalidArgument,
+                &format!("Encryption header is malformed: {}", cause),
+            )
+        };
+        let salt = hex::decode(&header.salt).map_err(|err| invalid_header(&err.to_string()))?;
+        let verifier =
+            hex::decode(&header.verifier).map_err(|err| invalid_header(&err.to_string()))?;
+        let params = Params::new(header.memory, header.iterations, header.parallelism, None)
+            .map_err(|err| invalid_header(&err.to_string()))?;
+
+        let cipher = Self::derive(passphrase, &salt, &params)?;
+        match cipher.unseal(&verifier, &[]) {
+            Ok(plaintext) if plaintext == Self::VERIFIER => Ok(cipher),
+            _ => Err(Error::custom_err(
+                CustomKind::WrongKey,
+                "Passphrase does not match the database",
+            )),
+        }
+    }
+
+    // Return a cipher whose key is derived from a passphrase
+    fn derive(passphrase: &str, salt: &[u8], params: &Params) -> Result<Self> {
+        let mut key = Key::default();
+        Argon2::new(Algorithm::Argon2id, Version::V0x13, params.clone())
+            .hash_password_into(passphrase.as_bytes(), salt, &mut key)
+            .map_err(|err| {
+                Error::custom_err(
+                    CustomKind::InvalidArgument,
+                    &format!("Cannot derive an encryption key: {}", err),
+                )
+            })?;
+        Ok(Self {
+            aead: ChaCha20Poly1305::new(&key),
+        })
+    }
+
+    /// Encrypt content. The result is preceded by a random nonce it has been encrypted with.
+    ///
+    /// Additional data `aad` is authenticated but not encrypted. The same data has to be passed
+    /// to [`Cipher::unseal`], which binds the content to e.g. a file it is stored in.
+    ///
+    /// # Errors
+    /// The function returns [`CustomKind::Encoding`] error if the content is too large.
+    pub fn seal(&self, plaintext: &[u8], aad: &[u8]) -> Result<Vec<u8>> {
+        let nonce = ChaCha20Poly1305::generate_nonce(&mut OsRng);
+        let payload = Payload {
+            msg: plaintext,
+            aad,
+        };
+        let ciphertext = self
+            .aead
+            .encrypt(&nonce, payload)
+            .map_err(|_| Error::custom_err(CustomKind::Encoding, "Cannot encrypt content"))?;
+        Ok([nonce.as_slice(), &ciphertext].concat())
+    }
+
+    /// Decrypt content sealed by [`Cipher::seal`].
+    ///
+    /// # Errors
+    /// The function returns [`CustomKind::Encoding`] error if the content has been altered,
+    /// sealed with another key or with other additional data.
+    pub fn unseal(&self, content: &[u8], aad: &[u8]) -> Result<Vec<u8>> {
+        let undecryptable = || {
+            Error::custom_err(
+                CustomKind::Encoding,
+                "Cannot decrypt content, it is either damaged or encrypted with another key",
+            )
+        };
+        if content.len() < Self::NONCE_LEN {
+            return Err(undecryptable());
+        }
+
+        let (nonce, ciphertext) = content.split_at(Self::NONCE_LEN);
+        let payload = Payload {
+            msg: ciphertext,
+            aad,
+        };
+        self.aead
+            .decrypt(Nonce::from_slice(nonce), payload)
+            .map_err(|_| undecryptable())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::*;
+
+    /* ------------------ */
+    /* ---- Fixtures ---- */
+    /* ------------------ */
+
+    type CipherFixture = (Cipher, Header);
+
+    #[fixture]
+    fn cipher() -> CipherFixture {
+        Cipher::create("correct horse").unwrap()
+    }
+
+    /* -------------------------- */
+    /* ---- Test definitions ---- */
+    /* -------------------------- */
+
+    #[rstest]
+    fn sealed_content_is_unsealed(cipher: CipherFixture) {
+        let (cipher, _) = cipher;
+        let sealed = cipher.seal(b"Study notes", b"notes.json").unwrap();
+        assert!(!sealed
+            .windows(b"Study notes".len())
+            .any(|window| window == b"Study notes"));
+        assert_eq!(
+            b"Study notes".to_vec(),
+            cipher.unseal(&sealed, b"notes.json").unwrap()
+        );
+
+        // Every sealing uses a fresh nonce
+        assert_ne!(sealed, cipher.seal(b"Study notes", b"notes.json").unwrap());
+    }
+
+    #[rstest]
+    fn key_is_derived_again_from_header(cipher: CipherFixture) {
+        let (cipher, header) = cipher;
+        let sealed = cipher.seal(b"Study notes", b"notes.json").unwrap();
+
+        let reopened = Cipher::open("correct horse", &header).unwrap();
+        assert_eq!(
+            b"Study notes".to_vec(),
+            reopened.unseal(&sealed, b"notes.json").unwrap()
+        );
+    }
+
+    #[rstest]
+    fn wrong_passphrase_is_detected(cipher: CipherFixture) {
+        let (_, header) = cipher;
+        let err = Cipher::open("wrong horse", &header).unwrap_err();
+        assert_eq!(CustomKind::WrongKey, *err.get_custom_kind().unwrap());
+    }
+
+    #[rstest]
+    #[case::flipped_bit(|content: &mut Vec<u8>| content[20] ^= 1)]
+    #[case::truncated(|content: &mut Vec<u8>| content.truncate(8))]
+    #[case::empty(|content: &mut Vec<u8>| content.clear())]
+    fn altered_content_is_not_unsealed(#[case] alter: fn(&mut Vec<u8>), cipher: CipherFixture) {
+        let (cipher, _) = cipher;
+        let mut sealed = cipher.seal(b"Study notes", b"notes.json").unwrap();
+        alter(&mut sealed);
+
+        let err = cipher.unseal(&sealed, b"notes.json").unwrap_err();
+        assert_eq!(CustomKind::Encoding, *err.get_custom_kind().unwrap());
+    }
+
+    #[rstest]
+    fn content_is_not_unsealed_with_other_additional_data(cipher: CipherFixture) {
+        let (cipher, _) = cipher;
+        let sealed = cipher.seal(b"Study notes", b"notes.json").unwrap();
+
+        let err = cipher.unseal(&sealed, b"words.json").unwrap_err();
+        assert_eq!(CustomKind::Encoding, *err.get_custom_kind().unwrap());
+    }
+
+    #[rstest]
+    fn malformed_header_produces_error(cipher: CipherFixture) {
+        let (_, mut header) = cipher;
+        header.salt = "not hex".to_string();
+        let err = Cipher::open("correct horse", &header).unwrap_err();
+        assert_eq!(CustomKind::InvalidArgument, *err.get_custom_kind().unwrap());
+    }
+}
@@ -7,23 +7,134 @@
 //! Database layer handles all I/O operations (loading and storing collections back to the
 //! filesystem) as well as provides methods to manipulate existing collections and create new ones.
 //!
+//! Document mutations are not written into collection files directly. They are appended to
+//! a write-ahead log instead (see [`crate::wal`]) and collection files are brought up to date
+//! during checkpoints, performed periodically and whenever the database is opened.
+//!
 //! On top of the database works a `Query Manager` which allows existing collections to be queried
 //! to pass data to appropriate endpoints.
 
-use crate::error::Result;
-#[double]
-use crate::io::Io;
-use crate::metadata::Database as DbMeta;
-use mockall_double::double;
+use crate::error::{CustomKind, Error, Result};
+use crate::index::Index;
+use crate::io::{is_name_valid, Compression, Format, Io, OpenMode};
+use crate::jutil::total_compare;
+use crate::metadata::{Collection as CollMeta, Database as DbMeta, Index as IndexMeta};
+use crate::query::{Match, Query};
+use crate::schema::Schema;
+use crate::storage::{MemoryStorage, Storage};
+use crate::wal::{Mutation, Record};
+use chrono::Local;
+use serde::Serialize;
+use serde_json::{Map, Value as JValue};
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
 use std::ffi::OsStr;
+use std::path::{Path, PathBuf};
+use uuid::Uuid;
+
+// Documents of a single collection indexed by their keys
+type Documents = Map<String, JValue>;
 
 /// A structure representing a database.
 #[non_exhaustive]
 pub struct Database {
-    #[allow(unused)] //TODO remove when the field is actually used
     io: Io,
-    #[allow(unused)] //TODO remove when the field is actually used
     metadata: DbMeta,
+    // Collections loaded into memory so far
+    documents: HashMap<String, Documents>,
+    // Indexes of loaded collections, by indexed pointers
+    indexes: HashMap<String, BTreeMap<String, Index>>,
+    // Schemas of loaded collections which have one
+    schemas: HashMap<String, Schema>,
+    // Collections altered since the last checkpoint
+    dirty: BTreeSet<String>,
+    // Number of records in the write-ahead log
+    wal_len: usize,
+    mode: OpenMode,
+}
+
+/// A set of document operations applied all at once.
+///
+/// A transaction is started with [`Database::transaction`] and may span multiple collections.
+/// Operations are validated immediately, like their counterparts of [`Collection`], but none of them
+/// takes effect until [`Transaction::commit`] is called. Operations see results of preceding
+/// operations of the same transaction.
+pub struct Transaction<'a> {
+    database: &'a mut Database,
+    mutations: Vec<Mutation>,
+    // Documents altered by the transaction so far, None denotes a deleted document
+    staged: HashMap<(String, String), Option<JValue>>,
+}
+
+/// A handle to a single collection of a database.
+///
+/// The handle is returned by [`Database::collection`] and allows documents stored inside the
+/// collection to be manipulated. Every document is a JSON object identified by an unique key.
+/// Each mutation is recorded in the write-ahead log before the function performing it returns.
+/// Stored documents have to satisfy the collection's schema, if any (see [`Database::set_schema`]).
+pub struct Collection<'a> {
+    database: &'a mut Database,
+    name: String,
+}
+
+/// Outcome of [`Database::check`].
+#[derive(Serialize, Debug, Clone, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct Report {
+    /// Problems found, in the order they have been found
+    pub issues: Vec<Issue>,
+    /// Whether the problems have been repaired
+    pub repaired: bool,
+    /// Files moved aside by the repair, as pairs of original and quarantined paths
+    pub quarantined: Vec<(PathBuf, PathBuf)>,
+}
+
+/// A problem found by [`Database::check`].
+///
+/// Paths are relative to database's base directory.
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Issue {
+    /// A collection or schema file which does not belong to any registered collection
+    OrphanFile {
+        /// Path to the file
+        path: PathBuf,
+    },
+    /// A registered collection whose file does not exist
+    MissingFile {
+        /// Name of the collection
+        collection: String,
+    },
+    /// A file which cannot be decoded
+    Unparsable {
+        /// Path to the file
+        path: PathBuf,
+        /// Description of the decoding failure
+        cause: String,
+    },
+    /// A readable collection file which does not match checksums kept in metadata
+    ChecksumMismatch {
+        /// Name of the collection
+        collection: String,
+    },
+    /// A temporary file left behind by an interrupted write
+    TempFile {
+        /// Path to the file
+        path: PathBuf,
+    },
+    /// A write-ahead log whose last record has been torn by an interrupted write
+    TornRecord {
+        /// Path to the log
+        path: PathBuf,
+    },
+}
+
+impl Report {
+    /// Check whether no problem has been found.
+    #[must_use]
+    pub fn is_healthy(&self) -> bool {
+        self.issues.is_empty()
+    }
 }
 
 impl Database {
@@ -37,11 +148,75 @@ impl Database {
     /// could not be initialized due to internal error.
     pub fn create<P>(name: &str, path: P) -> Result<Self>
     where
-        P: AsRef<OsStr> + 'static,
+        P: AsRef<OsStr>,
     {
         let metadata = DbMeta::new(name);
         let io = Io::create(path, &metadata)?;
-        Ok(Self { io, metadata })
+        Ok(Self::new(io, metadata, OpenMode::ReadWrite))
+    }
+
+    /// Create an empty database kept in memory.
+    ///
+    /// Nothing is written to the disk, hence the database is gone once dropped. Such a database is
+    /// useful for tests, ephemeral sessions and benchmarks. Use [`Database::create_with`] together
+    /// with a [`MemoryStorage`] clone retained by the caller if the database should be opened again
+    /// later on.
+    ///
+    /// # Errors
+    /// The function produces an error in case the name is invalid.
+    pub fn create_in_memory(name: &str) -> Result<Self> {
+        Self::create_with(name, Box::new(MemoryStorage::new()))
+    }
+
+    /// Create an empty database inside a given storage.
+    ///
+    /// The function is a counterpart of [`Database::create`] which allows any storage backend
+    /// to be used (see [`crate::storage`]).
+    ///
+    /// # Errors
+    /// The function may produce an error in case the storage has failed or database could not be
+    /// initialized due to internal error.
+    pub fn create_with(name: &str, storage: Box<dyn Storage>) -> Result<Self> {
+        let metadata = DbMeta::new(name);
+        let io = Io::create_with(storage, &metadata)?;
+        Ok(Self::new(io, metadata, OpenMode::ReadWrite))
+    }
+
+    /// Create an empty encrypted database.
+    ///
+    /// The function is a counterpart of [`Database::create`] which encrypts all database files,
+    /// including collections, schemas and the write-ahead log, with a key derived from
+    /// `passphrase`. Only a small header holding key derivation parameters is kept unencrypted.
+    /// The database may be opened afterwards with [`Database::open_encrypted`].
+    ///
+    /// # Errors
+    /// The function may produce an error in case I/O system call has failed or database
+    /// could not be initialized due to internal error.
+    pub fn create_encrypted<P>(name: &str, path: P, passphrase: &str) -> Result<Self>
+    where
+        P: AsRef<OsStr>,
+    {
+        let metadata = DbMeta::new(name);
+        let io = Io::create_encrypted(path, &metadata, passphrase)?;
+        Ok(Self::new(io, metadata, OpenMode::ReadWrite))
+    }
+
+    /// Create an empty encrypted database inside a given storage.
+    ///
+    /// The function is a counterpart of [`Database::create_encrypted`] which allows any storage
+    /// backend to be used (see [`crate::storage`]).
+    ///
+    /// # Errors
+    /// The function may produce an error in case the storage has failed or database could not be
+    /// initialized due to internal error.
+    pub fn create_encrypted_with(
+        name: &str,
+        storage: Box<dyn Storage>,
+        passphrase: &str,
+    ) -> Result<Self> {
+        let metadata = DbMeta::new(name);
+        let io = Io::create_encrypted_with(storage, &metadata, passphrase)?;
+        Ok(Self::new(io, metadata, OpenMode::ReadWrite))
     }
 
     /// Open an existing database.
@@ -49,81 +224,3229 @@ impl Database {
     /// The function may be called to load an existing database from the filesystem.
     /// [`Database::create`] has to be called prior to this function.
     ///
+    /// Mutations recorded in the write-ahead log are replayed and checkpointed into collection
+    /// files before the database is returned. In read-only mode mutations are replayed in memory
+    /// only.
+    ///
+    /// The database is locked for as long as the returned instance exists. Many instances may open
+    /// the same database in [`OpenMode::ReadOnly`] mode, whereas [`OpenMode::ReadWrite`] mode
+    /// requires exclusive access. Any operation altering a database opened in read-only mode fails
+    /// with [`CustomKind::ReadOnly`] error.
+    ///
     /// # Errors
     /// The function may produce a number of errors (both library and external ones) depending
-    /// on various conditions.
-    pub fn open<P>(path: P) -> Result<Self>
+    /// on various conditions. [`CustomKind::Locked`] error is returned if the database is already
+    /// opened by another instance in a conflicting mode.
+    pub fn open<P>(path: P, mode: OpenMode) -> Result<Self>
     where
-        P: AsRef<OsStr> + 'static,
+        P: AsRef<OsStr>,
     {
-        let (io, metadata) = Io::open(path)?;
-        Ok(Self { io, metadata })
+        let (io, metadata) = Io::open(path, mode)?;
+        Self::load(io, metadata, mode)
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use chrono::Utc;
-    use more_asserts::*;
-    use rstest::*;
+    /// Open an existing database kept by a given storage.
+    ///
+    /// The function is a counterpart of [`Database::open`] which allows any storage backend
+    /// to be used (see [`crate::storage`]). [`Database::create_with`] has to be called prior to
+    /// this function.
+    ///
+    /// # Errors
+    /// The function may produce a number of errors (both library and external ones) depending
+    /// on various conditions. [`CustomKind::Locked`] error is returned if the database is already
+    /// opened by another instance in a conflicting mode.
+    pub fn open_with(storage: Box<dyn Storage>, mode: OpenMode) -> Result<Self> {
+        let (io, metadata) = Io::open_with(storage, mode)?;
+        Self::load(io, metadata, mode)
+    }
 
-    /* ----------------- */
-    /* ---- Helpers ---- */
-    /* ----------------- */
+    /// Open an existing encrypted database.
+    ///
+    /// The function is a counterpart of [`Database::open`] for databases created by
+    /// [`Database::create_encrypted`]. Encrypted databases cannot be opened by
+    /// [`Database::open`].
+    ///
+    /// # Errors
+    /// The function returns [`CustomKind::WrongKey`] error if the passphrase does not match the
+    /// one the database has been created with and [`CustomKind::InvalidArgument`] error if the
+    /// database is not encrypted. Otherwise it fails in the same cases as [`Database::open`].
+    pub fn open_encrypted<P>(path: P, mode: OpenMode, passphrase: &str) -> Result<Self>
+    where
+        P: AsRef<OsStr>,
+    {
+        let (io, metadata) = Io::open_encrypted(path, mode, passphrase)?;
+        Self::load(io, metadata, mode)
+    }
 
-    const DATABASE_FAKE_PATH: &'static str = "/path/to/database";
-    const DATABASE_FAKE_NAME: &'static str = "TestDatabase";
+    /// Open an existing encrypted database kept by a given storage.
+    ///
+    /// The function is a counterpart of [`Database::open_encrypted`] which allows any storage
+    /// backend to be used (see [`crate::storage`]).
+    ///
+    /// # Errors
+    /// The function returns [`CustomKind::WrongKey`] error if the passphrase does not match the
+    /// one the database has been created with and [`CustomKind::InvalidArgument`] error if the
+    /// database is not encrypted. Otherwise it fails in the same cases as [`Database::open_with`].
+    pub fn open_encrypted_with(
+        storage: Box<dyn Storage>,
+        mode: OpenMode,
+        passphrase: &str,
+    ) -> Result<Self> {
+        let (io, metadata) = Io::open_encrypted_with(storage, mode, passphrase)?;
+        Self::load(io, metadata, mode)
+    }
 
-    /* ------------------ */
-    /* ---- Fixtures ---- */
-    /* ------------------ */
+    /// Check whether the database is encrypted.
+    #[must_use]
+    pub fn is_encrypted(&self) -> bool {
+        self.io.is_encrypted()
+    }
 
-    #[fixture]
-    fn fake_metadata() -> DbMeta {
-        DbMeta::new(DATABASE_FAKE_NAME)
+    /// Check integrity of an existing database and optionally repair it.
+    ///
+    /// The function walks the database directory and reports orphan collection files, collection
+    /// files which are missing, unparsable files (metadata, collections, schemas and the
+    /// write-ahead log), collection files not matching their checksums, temporary files left behind
+    /// by interrupted writes and a torn record at the end of the write-ahead log. Unparsable
+    /// metadata is reported as well, the rest of the check then proceeds as if no collection was
+    /// registered.
+    ///
+    /// Nothing is altered unless `repair` is set. In repair mode:
+    /// - broken files are moved into the `.quarantine` directory, under a subdirectory named after
+    ///   the time of the repair, and broken collections are replaced with empty ones,
+    /// - missing collection files are recreated empty,
+    /// - readable orphan collection files are registered as collections,
+    /// - checksums of readable collection files are updated,
+    /// - temporary files are removed,
+    /// - a torn record at the end of the write-ahead log is cut off,
+    ///
+    /// and finally metadata is rebuilt. The repair requires exclusive access to the database,
+    /// whereas the check alone may run alongside read-only instances. Lock files are never stale,
+    /// since locks are released by the operating system once the process holding them is gone.
+    ///
+    /// # Errors
+    /// The function returns [`CustomKind::Locked`] error if the database is opened by another
+    /// instance in a conflicting mode and [`CustomKind::WrongKey`] error if the database is
+    /// encrypted (see [`Database::check_encrypted`]). I/O errors are forwarded to the caller.
+    pub fn check<P>(path: P, repair: bool) -> Result<Report>
+    where
+        P: AsRef<OsStr>,
+    {
+        let io = Io::attach(path, Self::check_mode(repair), None)?;
+        Checker::new(io, repair).run()
     }
 
-    /* -------------------------- */
-    /* ---- Test definitions ---- */
-    /* -------------------------- */
+    /// Check integrity of an existing database kept by a given storage and optionally repair it.
+    ///
+    /// The function is a counterpart of [`Database::check`] which allows any storage backend
+    /// to be used (see [`crate::storage`]).
+    ///
+    /// # Errors
+    /// The function fails in the same cases as [`Database::check`].
+    pub fn check_with(storage: Box<dyn Storage>, repair: bool) -> Result<Report> {
+        let io = Io::attach_with(storage, Self::check_mode(repair), None)?;
+        Checker::new(io, repair).run()
+    }
 
-    #[rstest]
-    fn database_is_successfully_created() {
-        let name = DATABASE_FAKE_NAME;
-        let path = DATABASE_FAKE_PATH;
-
-        // Set up mock on Io::create which checks whether function arguments are valid
-        let ctx = Io::create_context();
-        ctx.expect()
-            .times(1)
-            .withf(move |path_arg: &&str, metadata_arg: &DbMeta| {
-                *path_arg == path && metadata_arg.name == name
+    /// Check integrity of an existing encrypted database and optionally repair it.
+    ///
+    /// The function is a counterpart of [`Database::check`] for databases created by
+    /// [`Database::create_encrypted`]. Files which cannot be decrypted are reported as
+    /// unparsable.
+    ///
+    /// # Errors
+    /// The function returns [`CustomKind::WrongKey`] error if the passphrase does not match the
+    /// one the database has been created with and [`CustomKind::InvalidArgument`] error if the
+    /// database is not encrypted. Otherwise it fails in the same cases as [`Database::check`].
+    pub fn check_encrypted<P>(path: P, repair: bool, passphrase: &str) -> Result<Report>
+    where
+        P: AsRef<OsStr>,
+    {
+        let io = Io::attach(path, Self::check_mode(repair), Some(passphrase))?;
+        Checker::new(io, repair).run()
+    }
+
+    /// Check integrity of an existing encrypted database kept by a given storage and optionally
+    /// repair it.
+    ///
+    /// The function is a counterpart of [`Database::check_encrypted`] which allows any storage
+    /// backend to be used (see [`crate::storage`]).
+    ///
+    /// # Errors
+    /// The function fails in the same cases as [`Database::check_encrypted`].
+    pub fn check_encrypted_with(
+        storage: Box<dyn Storage>,
+        repair: bool,
+        passphrase: &str,
+    ) -> Result<Report> {
+        let io = Io::attach_with(storage, Self::check_mode(repair), Some(passphrase))?;
+        Checker::new(io, repair).run()
+    }
+
+    // A repair alters the database, so it must not run alongside any other instance
+    fn check_mode(repair: bool) -> OpenMode {
+        if repair {
+            OpenMode::ReadWrite
+        } else {
+            OpenMode::ReadOnly
+        }
+    }
+
+    // Schemas are kept readable regardless of the format of collection files
+    const SCHEMA_FORMAT: Format = Format::PrettyJson;
+    // Write-ahead log file, relative to database's base directory
+    const WAL_FILE: &'static str = ".wal";
+    // Number of write-ahead log records which triggers a checkpoint
+    const CHECKPOINT_THRESHOLD: usize = 1000;
+    // Directory holding collection schemas, relative to database's base directory
+    const SCHEMA_DIR: &'static str = ".metadata/schemas";
+
+    // Return an opened database whose write-ahead log has been replayed
+    fn load(io: Io, metadata: DbMeta, mode: OpenMode) -> Result<Self> {
+        let mut database = Self::new(io, metadata, mode);
+        database.replay_wal()?;
+        Ok(database)
+    }
+
+    fn new(io: Io, metadata: DbMeta, mode: OpenMode) -> Self {
+        Self {
+            io,
+            metadata,
+            documents: HashMap::new(),
+            indexes: HashMap::new(),
+            schemas: HashMap::new(),
+            dirty: BTreeSet::new(),
+            wal_len: 0,
+            mode,
+        }
+    }
+
+    // Return an error if the database has been opened in read-only mode
+    fn ensure_writable(&self) -> Result<()> {
+        match self.mode {
+            OpenMode::ReadWrite => Ok(()),
+            OpenMode::ReadOnly => Err(Error::custom_err(
+                CustomKind::ReadOnly,
+                &format!(
+                    "Database '{}' is opened in read-only mode",
+                    self.metadata.name
+                ),
+            )),
+        }
+    }
+
+    // Return path to a collection file, relative to database's base directory. The path does not
+    // depend on the format, so a collection may be rewritten in another format atomically
+    fn collection_path(name: &str) -> PathBuf {
+        PathBuf::from(format!("{}.json", name))
+    }
+
+    // Return path to a collection's schema file, relative to database's base directory
+    fn schema_path(name: &str) -> PathBuf {
+        PathBuf::from(Self::SCHEMA_DIR).join(format!("{}.json", name))
+    }
+
+    // Return an error if a collection does not exist
+    fn ensure_collection_exists(&self, name: &str) -> Result<()> {
+        if self.metadata.collections.contains_key(name) {
+            Ok(())
+        } else {
+            Err(Error::custom_err(
+                CustomKind::NotFound,
+                &format!("Collection '{}' does not exist", name),
+            ))
+        }
+    }
+
+    // Return an error if a collection cannot be created with the given name
+    fn ensure_collection_name_available(&self, name: &str) -> Result<()> {
+        if !is_name_valid(name) {
+            return Err(Error::custom_err(
+                CustomKind::InvalidArgument,
+                &format!("Collection name '{}' contains forbidden characters", name),
+            ));
+        }
+        if self.metadata.collections.contains_key(name) {
+            return Err(Error::custom_err(
+                CustomKind::InvalidArgument,
+                &format!("Collection '{}' already exists", name),
+            ));
+        }
+
+        Ok(())
+    }
+
+    // Load a collection into memory unless it has been loaded already
+    fn load_collection(&mut self, name: &str) -> Result<()> {
+        if !self.documents.contains_key(name) {
+            let documents = self.io.deserialize_verified(
+                Self::collection_path(name),
+                &self.metadata.collections[name].checksums,
+            )?;
+            let indexes = self.metadata.collections[name]
+                .indexes
+                .iter()
+                .map(|(pointer, index)| {
+                    let index = Index::build(pointer, index.unique, &documents);
+                    (pointer.clone(), index)
+                })
+                .collect();
+            let schema_path = Self::schema_path(name);
+            if self.io.exists(&schema_path) {
+                let schema = Schema::new(self.io.deserialize(schema_path)?)?;
+                self.schemas.insert(name.to_string(), schema);
+            }
+            self.documents.insert(name.to_string(), documents);
+            self.indexes.insert(name.to_string(), indexes);
+        }
+        Ok(())
+    }
+
+    // Apply mutations recorded in the write-ahead log and checkpoint them
+    fn replay_wal(&mut self) -> Result<()> {
+        if !self.io.exists(PathBuf::from(Self::WAL_FILE)) {
+            return Ok(());
+        }
+
+        let records: Vec<Record> = self.io.deserialize_lines(PathBuf::from(Self::WAL_FILE))?;
+        self.wal_len = records.len();
+        for mutation in records.into_iter().flatten() {
+            // Mutations of collections which no longer exist are skipped
+            if !self
+                .metadata
+                .collections
+                .contains_key(mutation.collection())
+            {
+                continue;
+            }
+            self.load_collection(mutation.collection())?;
+            self.apply_mutation(mutation);
+        }
+
+        match self.mode {
+            OpenMode::ReadWrite => {
+                // A log holding nothing but a torn record left behind by a crash is not removed
+                // by the checkpoint. New records must never be appended after such a record
+                if self.wal_len == 0 {
+                    self.io.remove(PathBuf::from(Self::WAL_FILE))?;
+                }
+                self.checkpoint()
+            }
+            OpenMode::ReadOnly => Ok(()),
+        }
+    }
+
+    // Apply a mutation to a loaded collection, returning a document stored under the mutated key
+    // before. Indexes of the collection are updated accordingly
+    fn apply_mutation(&mut self, mutation: Mutation) -> Option<JValue> {
+        let name = mutation.collection().to_string();
+        let key = mutation.key().to_string();
+        let documents = self.documents.get_mut(&name).unwrap();
+        let previous = mutation.apply(documents);
+
+        for index in self.indexes.entry(name.clone()).or_default().values_mut() {
+            if let Some(previous) = &previous {
+                index.remove(&key, previous);
+            }
+            if let Some(document) = documents.get(&key) {
+                index.insert(&key, document);
+            }
+        }
+        self.dirty.insert(name);
+        previous
+    }
+
+    // Record mutations in the write-ahead log and apply them afterwards. Mutations are recorded
+    // as a single record, so either all of them are durable or none is. Documents stored under
+    // mutated keys before are returned in the order of mutations
+    fn write_batch(&mut self, mutations: Record) -> Result<Vec<Option<JValue>>> {
+        self.ensure_writable()?;
+        for mutation in &mutations {
+            self.ensure_valid(mutation)?;
+        }
+        self.ensure_unique(&mutations)?;
+        self.io.append(&mutations, PathBuf::from(Self::WAL_FILE))?;
+        self.wal_len += 1;
+
+        let previous = mutations
+            .into_iter()
+            .map(|mutation| self.apply_mutation(mutation))
+            .collect();
+
+        // Mutations are already durable, so a failed checkpoint is not reported. It is retried
+        // on the next write since the log keeps exceeding the threshold
+        if self.wal_len >= Self::CHECKPOINT_THRESHOLD {
+            let _ = self.checkpoint();
+        }
+        Ok(previous)
+    }
+
+    // Return an error if a document stored by a mutation does not satisfy the collection's schema
+    fn ensure_valid(&self, mutation: &Mutation) -> Result<()> {
+        match (mutation, self.schemas.get(mutation.collection())) {
+            (Mutation::Put { document, .. }, Some(schema)) => schema.validate(document),
+            _ => Ok(()),
+        }
+    }
+
+    // Return an error if mutations would make any unique index hold a value more than once.
+    // Only the state after all mutations counts, so e.g. values may be swapped between documents
+    fn ensure_unique(&self, mutations: &[Mutation]) -> Result<()> {
+        // Content of altered documents after all mutations, None denotes a deleted document
+        let mut altered = BTreeMap::new();
+        for mutation in mutations {
+            let document = match mutation {
+                Mutation::Put { document, .. } => Some(document),
+                Mutation::Delete { .. } => None,
+            };
+            altered.insert((mutation.collection(), mutation.key()), document);
+        }
+
+        for (&(collection, key), document) in &altered {
+            let (Some(document), Some(indexes)) = (document, self.indexes.get(collection)) else {
+                continue;
+            };
+            for index in indexes.values().filter(|index| index.is_unique()) {
+                let Some(value) = index.value_of(document) else {
+                    continue;
+                };
+                // Documents which are not altered keep their values
+                let held_by_stored = index
+                    .keys_of(value)
+                    .any(|other| !altered.contains_key(&(collection, other)));
+                let held_by_altered = altered.iter().any(|(&(other_coll, other), other_doc)| {
+                    other_coll == collection
+                        && other != key
+                        && other_doc
+                            .and_then(|other_doc| index.value_of(other_doc))
+                            .is_some_and(|other_value| {
+                                total_compare(value, other_value) == Ordering::Equal
+                            })
+                });
+                if held_by_stored || held_by_altered {
+                    return Err(unique_violation(collection, index.pointer(), value));
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Write collections altered since the last checkpoint into their files and clear
+    /// the write-ahead log.
+    ///
+    /// Checkpoints are performed automatically once the log grows large enough, thus calling
+    /// the function is not required. It may be useful though to keep collection files up to date,
+    /// e.g. before a backup is made.
+    ///
+    /// Checksums of written files are recorded in metadata, so a file altered outside of the
+    /// database is detected once the collection is loaded again.
+    ///
+    /// # Errors
+    /// The function forwards I/O and serde errors to the caller. The log is left untouched in such
+    /// a case, so no mutation is lost.
+    pub fn checkpoint(&mut self) -> Result<()> {
+        if self.dirty.is_empty() && self.wal_len == 0 {
+            return Ok(());
+        }
+        self.ensure_writable()?;
+
+        if !self.dirty.is_empty() {
+            self.write_dirty_collections()?;
+        }
+
+        if self.wal_len > 0 {
+            self.io.remove(PathBuf::from(Self::WAL_FILE))?;
+            self.wal_len = 0;
+        }
+        Ok(())
+    }
+
+    /// Return the format collection files are written in.
+    #[must_use]
+    pub fn format(&self) -> Format {
+        self.metadata.format
+    }
+
+    /// Change the format collection files are written in.
+    ///
+    /// All collections are rewritten in the new format right away. Collections of a new database
+    /// are stored as compact JSON, whereas binary formats (see [`Format`]) make large collections
+    /// several times smaller and faster to load. Collection files are read regardless of the format
+    /// they have been written in, so the database remains usable even if the function fails
+    /// halfway through.
+    ///
+    /// # Errors
+    /// The function returns [`CustomKind::ReadOnly`] error if the database has been opened
+    /// in read-only mode. I/O and encoding errors are forwarded to the caller.
+    pub fn set_format(&mut self, format: Format) -> Result<()> {
+        self.ensure_writable()?;
+        if self.metadata.format == format {
+            return Ok(());
+        }
+
+        self.load_all_collections()?;
+        self.metadata.format = format;
+        self.rewrite_collections()
+    }
+
+    /// Return the codec collection files are compressed with.
+    #[must_use]
+    pub fn compression(&self) -> Compression {
+        self.metadata.compression
+    }
+
+    /// Change the codec collection files are compressed with.
+    ///
+    /// All collections are rewritten with the new codec right away. Collections of a new database
+    /// are not compressed, whereas compression (see [`Compression`]) makes collections holding
+    /// repetitive text considerably smaller. Compressed collection files are recognized when read,
+    /// so the database remains usable even if the function fails halfway through.
+    ///
+    /// # Errors
+    /// The function returns [`CustomKind::ReadOnly`] error if the database has been opened
+    /// in read-only mode. I/O errors are forwarded to the caller.
+    pub fn set_compression(&mut self, compression: Compression) -> Result<()> {
+        self.ensure_writable()?;
+        if self.metadata.compression == compression {
+            return Ok(());
+        }
+
+        self.load_all_collections()?;
+        self.metadata.compression = compression;
+        self.rewrite_collections()
+    }
+
+    // Load every collection of the database into memory
+    fn load_all_collections(&mut self) -> Result<()> {
+        let names: Vec<String> = self.metadata.collections.keys().cloned().collect();
+        names.iter().try_for_each(|name| self.load_collection(name))
+    }
+
+    // Write all collections, which have to be loaded already, and store metadata afterwards
+    fn rewrite_collections(&mut self) -> Result<()> {
+        self.dirty.extend(self.metadata.collections.keys().cloned());
+        self.checkpoint()?;
+        self.sync_metadata()
+    }
+
+    // Write altered collections into their files and record checksums of the files in metadata
+    fn write_dirty_collections(&mut self) -> Result<()> {
+        // Collections are encoded first, so checksums are known before any file is replaced
+        let contents = self
+            .dirty
+            .iter()
+            .map(|name| {
+                let content = self.io.serialize_to_vec(
+                    &self.documents[name],
+                    Self::collection_path(name),
+                    self.metadata.format,
+                    self.metadata.compression,
+                )?;
+                Ok((name.clone(), content))
             })
-            .returning(|_path: &str, _metadata: &DbMeta| Ok(Io::new()));
+            .collect::<Result<Vec<_>>>()?;
 
-        let database = Database::create(name, path).unwrap();
+        // Both old and new files are accepted until all of them are written, so an interrupted
+        // checkpoint is not mistaken for a corruption
+        for (name, content) in &contents {
+            let collection = self.metadata.collections.get_mut(name).unwrap();
+            collection.checksums.push(Io::checksum(content));
+        }
+        self.sync_metadata()?;
+        for (name, content) in &contents {
+            self.io.write(Self::collection_path(name), content)?;
+            self.dirty.remove(name);
+        }
+        for (name, content) in &contents {
+            let collection = self.metadata.collections.get_mut(name).unwrap();
+            collection.checksums = vec![Io::checksum(content)];
+        }
+        self.sync_metadata()
+    }
 
-        // Check whether internal metadata is filled with correct values
-        let now = Utc::now();
-        assert_eq!(name, database.metadata.name);
-        assert_gt!(now, database.metadata.created);
-        assert_eq!(database.metadata.created, database.metadata.modified);
+    // Mark metadata as modified and store it in the filesystem
+    fn sync_metadata(&mut self) -> Result<()> {
+        self.metadata.modified = Local::now();
+        self.io.serialize_metadata(&self.metadata)
     }
 
-    #[rstest]
-    fn database_is_successfully_opened() {
-        let path = DATABASE_FAKE_PATH;
+    /// Create an empty collection.
+    ///
+    /// Each collection is stored in its own file inside the database directory and registered
+    /// in the database's metadata. Collection names follow the same restrictions as database
+    /// names, i.e. only alphanumeric characters and underscore are allowed.
+    ///
+    /// # Errors
+    /// The function returns a custom library error in case the name is invalid or a collection
+    /// with the same name already exists. I/O errors are forwarded to the caller.
+    pub fn create_collection(&mut self, name: &str) -> Result<()> {
+        self.ensure_writable()?;
+        self.ensure_collection_name_available(name)?;
+
+        let content = self.io.serialize_to_vec(
+            &Documents::new(),
+            Self::collection_path(name),
+            self.metadata.format,
+            self.metadata.compression,
+        )?;
+        self.io.write_new(Self::collection_path(name), &content)?;
+        let mut collection = CollMeta::new();
+        collection.checksums.push(Io::checksum(&content));
+        self.metadata
+            .collections
+            .insert(name.to_string(), collection);
+        self.documents.insert(name.to_string(), Documents::new());
+        self.indexes.insert(name.to_string(), BTreeMap::new());
+        self.sync_metadata()
+    }
+
+    /// Drop an existing collection.
+    ///
+    /// The collection file is removed from the filesystem together with all documents it holds.
+    ///
+    /// # Errors
+    /// The function returns a custom library error in case the collection does not exist.
+    /// I/O errors are forwarded to the caller.
+    pub fn drop_collection(&mut self, name: &str) -> Result<()> {
+        self.ensure_writable()?;
+        self.ensure_collection_exists(name)?;
+        self.checkpoint()?;
+
+        self.io.remove(Self::collection_path(name))?;
+        if self.io.exists(Self::schema_path(name)) {
+            self.io.remove(Self::schema_path(name))?;
+        }
+        self.metadata.collections.remove(name);
+        self.documents.remove(name);
+        self.indexes.remove(name);
+        self.schemas.remove(name);
+        self.sync_metadata()
+    }
+
+    /// Return names of all collections stored inside the database in alphabetical order.
+    #[must_use]
+    pub fn list_collections(&self) -> Vec<&str> {
+        self.metadata
+            .collections
+            .keys()
+            .map(String::as_str)
+            .collect()
+    }
+
+    /// Rename an existing collection.
+    ///
+    /// # Panics
+    /// Should never panic. If the function panics then it should be considered as a bug
+    /// inside the function's implementation.
+    ///
+    /// # Errors
+    /// The function returns a custom library error in case the collection does not exist or
+    /// the new name is either invalid or already taken. I/O errors are forwarded to the caller.
+    pub fn rename_collection(&mut self, name: &str, new_name: &str) -> Result<()> {
+        self.ensure_writable()?;
+        self.ensure_collection_exists(name)?;
+        self.ensure_collection_name_available(new_name)?;
+        self.checkpoint()?;
+
+        self.io
+            .rename(Self::collection_path(name), Self::collection_path(new_name))?;
+        // Encrypted content is sealed again under the new path, which changes its checksum
+        if self.io.is_encrypted() {
+            let content = self.io.read(Self::collection_path(new_name))?;
+            self.metadata.collections.get_mut(name).unwrap().checksums =
+                vec![Io::checksum(&content)];
+        }
+        if self.io.exists(Self::schema_path(name)) {
+            self.io
+                .rename(Self::schema_path(name), Self::schema_path(new_name))?;
+        }
+        let mut collection = self.metadata.collections.remove(name).unwrap();
+        collection.modified = Local::now();
+        self.metadata
+            .collections
+            .insert(new_name.to_string(), collection);
+        if let Some(documents) = self.documents.remove(name) {
+            self.documents.insert(new_name.to_string(), documents);
+        }
+        if let Some(indexes) = self.indexes.remove(name) {
+            self.indexes.insert(new_name.to_string(), indexes);
+        }
+        if let Some(schema) = self.schemas.remove(name) {
+            self.schemas.insert(new_name.to_string(), schema);
+        }
+        self.sync_metadata()
+    }
+
+    // Return an error if an index cannot be defined on the given pointer
+    fn ensure_index_available(&self, collection: &str, pointer: &str) -> Result<()> {
+        if !pointer.starts_with('/') {
+            return Err(Error::custom_err(
+                CustomKind::InvalidArgument,
+                &format!("Index pointer '{}' does not point into documents", pointer),
+            ));
+        }
+        if self.metadata.collections[collection]
+            .indexes
+            .contains_key(pointer)
+        {
+            return Err(Error::custom_err(
+                CustomKind::InvalidArgument,
+                &format!(
+                    "Index on '{}' already exists in collection '{}'",
+                    pointer, collection
+                ),
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// Create a secondary index on values denoted by a JSON pointer.
+    ///
+    /// The index maps values found at `pointer` (e.g. `/word` or `/tags/0`) to keys of documents
+    /// holding them. It is kept up to date on every mutation of the collection and used by
+    /// [`Collection::query`] to speed up equality and range lookups. Documents which do not
+    /// contain a value at `pointer` are not indexed.
+    ///
+    /// Index definitions are stored in the collection's metadata, whereas indexes themselves are
+    /// built in memory whenever the collection is loaded.
+    ///
+    /// # Panics
+    /// Should never panic. If the function panics then it should be considered as a bug
+    /// inside the function's implementation.
+    ///
+    /// # Errors
+    /// The function returns a custom library error in case the collection does not exist,
+    /// the pointer is empty or has invalid syntax, or the index already exists. I/O errors are
+    /// forwarded to the caller.
+    pub fn create_index(&mut self, collection: &str, pointer: &str) -> Result<()> {
+        self.add_index(collection, pointer, false)
+    }
+
+    /// Create a unique secondary index on values denoted by a JSON pointer.
+    ///
+    /// The index works like the one created by [`Database::create_index`], but additionally
+    /// no two documents of the collection may hold the same value at `pointer`. Any operation
+    /// which would store a duplicate value fails with [`CustomKind::UniqueViolation`] error.
+    /// Documents which do not contain a value at `pointer` are not constrained.
+    ///
+    /// # Errors
+    /// The function returns the same errors as [`Database::create_index`].
+    /// [`CustomKind::UniqueViolation`] error is returned if documents stored inside the collection
+    /// already hold duplicate values.
+    pub fn create_unique_index(&mut self, collection: &str, pointer: &str) -> Result<()> {
+        self.add_index(collection, pointer, true)
+    }
+
+    // Define an index and build it
+    fn add_index(&mut self, collection: &str, pointer: &str, unique: bool) -> Result<()> {
+        self.ensure_writable()?;
+        self.ensure_collection_exists(collection)?;
+        self.ensure_index_available(collection, pointer)?;
+        self.load_collection(collection)?;
+
+        let index = Index::build(pointer, unique, &self.documents[collection]);
+        if let Some(value) = index.duplicate().filter(|_| unique) {
+            return Err(unique_violation(collection, pointer, value));
+        }
+        let coll_meta = self.metadata.collections.get_mut(collection).unwrap();
+        coll_meta
+            .indexes
+            .insert(pointer.to_string(), IndexMeta::new(unique));
+        coll_meta.modified = Local::now();
+        self.indexes
+            .entry(collection.to_string())
+            .or_default()
+            .insert(pointer.to_string(), index);
+        self.sync_metadata()
+    }
+
+    /// Drop a secondary index.
+    ///
+    /// # Panics
+    /// Should never panic. If the function panics then it should be considered as a bug
+    /// inside the function's implementation.
+    ///
+    /// # Errors
+    /// The function returns a custom library error in case either the collection or the index
+    /// does not exist. I/O errors are forwarded to the caller.
+    pub fn drop_index(&mut self, collection: &str, pointer: &str) -> Result<()> {
+        self.ensure_writable()?;
+        self.ensure_collection_exists(collection)?;
+
+        let coll_meta = self.metadata.collections.get_mut(collection).unwrap();
+        if coll_meta.indexes.remove(pointer).is_none() {
+            return Err(Error::custom_err(
+                CustomKind::NotFound,
+                &format!(
+                    "Index on '{}' does not exist in collection '{}'",
+                    pointer, collection
+                ),
+            ));
+        }
+        coll_meta.modified = Local::now();
+        if let Some(indexes) = self.indexes.get_mut(collection) {
+            indexes.remove(pointer);
+        }
+        self.sync_metadata()
+    }
+
+    /// Return pointers of all secondary indexes defined in a collection in alphabetical order.
+    ///
+    /// # Errors
+    /// The function returns a custom library error in case the collection does not exist.
+    pub fn list_indexes(&self, collection: &str) -> Result<Vec<&str>> {
+        self.ensure_collection_exists(collection)?;
+        Ok(self.metadata.collections[collection]
+            .indexes
+            .keys()
+            .map(String::as_str)
+            .collect())
+    }
+
+    /// Attach a JSON Schema to a collection, replacing the previous one if any.
+    ///
+    /// Every document inserted or updated afterwards has to satisfy the schema, otherwise
+    /// the operation fails with [`CustomKind::Json`] error naming a JSON pointer to the offending
+    /// value and the rule it violates. Supported keywords are listed in [`crate::schema`].
+    /// The schema is stored inside the `.metadata` directory of the database.
+    ///
+    /// # Panics
+    /// Should never panic. If the function panics then it should be considered as a bug
+    /// inside the function's implementation.
+    ///
+    /// # Errors
+    /// The function returns a custom library error in case the collection does not exist or
+    /// the schema is invalid. [`CustomKind::Json`] error is returned if any document already stored
+    /// inside the collection does not satisfy the schema. I/O errors are forwarded to the caller.
+    pub fn set_schema(&mut self, collection: &str, schema: JValue) -> Result<()> {
+        self.ensure_writable()?;
+        self.ensure_collection_exists(collection)?;
+        self.load_collection(collection)?;
+
+        let schema = Schema::new(schema)?;
+        schema.validate_all(&self.documents[collection])?;
+        let schema_path = Self::schema_path(collection);
+        if self.io.exists(&schema_path) {
+            self.io.serialize(
+                schema.source(),
+                schema_path,
+                Self::SCHEMA_FORMAT,
+                Compression::None,
+            )?;
+        } else {
+            self.io.serialize_new(
+                schema.source(),
+                schema_path,
+                Self::SCHEMA_FORMAT,
+                Compression::None,
+            )?;
+        }
+        self.schemas.insert(collection.to_string(), schema);
+        self.metadata
+            .collections
+            .get_mut(collection)
+            .unwrap()
+            .modified = Local::now();
+        self.sync_metadata()
+    }
+
+    /// Detach a JSON Schema from a collection.
+    ///
+    /// # Panics
+    /// Should never panic. If the function panics then it should be considered as a bug
+    /// inside the function's implementation.
+    ///
+    /// # Errors
+    /// The function returns a custom library error in case the collection does not exist or
+    /// it has no schema. I/O errors are forwarded to the caller.
+    pub fn remove_schema(&mut self, collection: &str) -> Result<()> {
+        self.ensure_writable()?;
+        self.ensure_collection_exists(collection)?;
+
+        let schema_path = Self::schema_path(collection);
+        if !self.io.exists(&schema_path) {
+            return Err(Error::custom_err(
+                CustomKind::NotFound,
+                &format!("Collection '{}' has no schema", collection),
+            ));
+        }
+        self.io.remove(schema_path)?;
+        self.schemas.remove(collection);
+        self.metadata
+            .collections
+            .get_mut(collection)
+            .unwrap()
+            .modified = Local::now();
+        self.sync_metadata()
+    }
+
+    /// Return a handle to an existing collection.
+    ///
+    /// The collection is loaded from the filesystem on first access and kept in memory afterwards.
+    ///
+    /// # Errors
+    /// The function returns a custom library error in case the collection does not exist.
+    /// [`CustomKind::Corrupted`] error is returned if the collection file does not match its
+    /// checksum. I/O and serde errors are forwarded to the caller if the collection could not be
+    /// loaded.
+    pub fn collection(&mut self, name: &str) -> Result<Collection<'_>> {
+        self.ensure_collection_exists(name)?;
+        self.load_collection(name)?;
+
+        Ok(Collection {
+            database: self,
+            name: name.to_string(),
+        })
+    }
+
+    /// Start a transaction.
+    ///
+    /// See [`Transaction`] for details.
+    pub fn transaction(&mut self) -> Transaction<'_> {
+        Transaction {
+            database: self,
+            mutations: Vec::new(),
+            staged: HashMap::new(),
+        }
+    }
+}
+
+// State of a single run of `Database::check`
+struct Checker {
+    io: Io,
+    repair: bool,
+    report: Report,
+    // Directory receiving files quarantined by this run, relative to database's base directory
+    quarantine_dir: PathBuf,
+}
+
+impl Checker {
+    // Directory holding quarantined files, relative to database's base directory
+    const QUARANTINE_DIR: &'static str = ".quarantine";
+    // Suffix of temporary files left behind by interrupted writes
+    const TEMP_SUFFIX: &'static str = ".tmp";
+
+    fn new(io: Io, repair: bool) -> Self {
+        let stamp = Local::now().format("%Y%m%dT%H%M%S%.3f").to_string();
+        Self {
+            io,
+            repair,
+            report: Report {
+                repaired: repair,
+                ..Report::default()
+            },
+            quarantine_dir: Path::new(Self::QUARANTINE_DIR).join(stamp),
+        }
+    }
+
+    // Check the whole database, repair it if requested and return the report
+    fn run(mut self) -> Result<Report> {
+        // Files quarantined by previous runs are left alone
+        let files: Vec<PathBuf> = self
+            .io
+            .list()?
+            .into_iter()
+            .filter(|path| !path.starts_with(Self::QUARANTINE_DIR))
+            .collect();
+
+        let mut metadata = self.check_metadata()?;
+        self.check_temp_files(&files)?;
+        self.check_collections(&mut metadata)?;
+        self.check_orphans(&files, &mut metadata)?;
+        self.check_schemas(&files, &metadata)?;
+        self.check_wal()?;
+
+        if self.repair && !self.report.is_healthy() {
+            metadata.modified = Local::now();
+            self.io.serialize_metadata(&metadata)?;
+        }
+        Ok(self.report)
+    }
+
+    // Return database's metadata, or an empty one if the metadata file is broken
+    fn check_metadata(&mut self) -> Result<DbMeta> {
+        let path = Io::metadata_path();
+        match self.io.deserialize(&path) {
+            Ok(metadata) => Ok(metadata),
+            Err(err) => {
+                self.report.issues.push(Issue::Unparsable {
+                    path: path.clone(),
+                    cause: err.to_string(),
+                });
+                if self.repair {
+                    // The broken file is copied rather than moved, a database without metadata
+                    // file could not be checked again if the repair was interrupted
+                    let target = self.quarantine_dir.join(&path);
+                    self.io.write_new(&target, &self.io.read(&path)?)?;
+                    self.report.quarantined.push((path, target));
+                }
+                let name = self
+                    .io
+                    .root()
+                    .file_name()
+                    .map(|name| name.to_string_lossy().into_owned())
+                    .filter(|name| is_name_valid(name))
+                    .unwrap_or_else(|| "database".to_string());
+                Ok(DbMeta::new(&name))
+            }
+        }
+    }
+
+    fn check_temp_files(&mut self, files: &[PathBuf]) -> Result<()> {
+        for path in files.iter().filter(|path| Self::is_temp_file(path)) {
+            self.report
+                .issues
+                .push(Issue::TempFile { path: path.clone() });
+            if self.repair {
+                self.io.remove(path)?;
+            }
+        }
+        Ok(())
+    }
+
+    // Check files of registered collections
+    fn check_collections(&mut self, metadata: &mut DbMeta) -> Result<()> {
+        let (format, compression) = (metadata.format, metadata.compression);
+        for (name, collection) in &mut metadata.collections {
+            let path = Database::collection_path(name);
+            if !self.io.exists(&path) {
+                self.report.issues.push(Issue::MissingFile {
+                    collection: name.clone(),
+                });
+                if self.repair {
+                    collection.checksums = vec![self.write_empty(&path, format, compression)?];
+                }
+                continue;
+            }
+
+            let checksum = Io::checksum(&self.io.read(&path)?);
+            match self.io.deserialize::<Documents, _>(&path) {
+                Ok(_)
+                    if collection.checksums.is_empty()
+                        || collection.checksums.contains(&checksum) => {}
+                Ok(_) => {
+                    self.report.issues.push(Issue::ChecksumMismatch {
+                        collection: name.clone(),
+                    });
+                    if self.repair {
+                        collection.checksums = vec![checksum];
+                    }
+                }
+                Err(err) => {
+                    self.report.issues.push(Issue::Unparsable {
+                        path: path.clone(),
+                        cause: err.to_string(),
+                    });
+                    if self.repair {
+                        self.quarantine(&path)?;
+                        collection.checksums =
+                            vec![self.write_empty(&path, format, compression)?];
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    // Check collection files which are not registered in metadata
+    fn check_orphans(&mut self, files: &[PathBuf], metadata: &mut DbMeta) -> Result<()> {
+        for path in files {
+            let Some(name) = Self::collection_name(path) else {
+                continue;
+            };
+            if metadata.collections.contains_key(name) {
+                continue;
+            }
+
+            self.report
+                .issues
+                .push(Issue::OrphanFile { path: path.clone() });
+            match self.io.deserialize::<Documents, _>(path) {
+                Ok(_) if self.repair => {
+                    let mut collection = CollMeta::new();
+                    collection
+                        .checksums
+                        .push(Io::checksum(&self.io.read(path)?));
+                    metadata.collections.insert(name.to_string(), collection);
+                }
+                Ok(_) => {}
+                Err(err) => {
+                    self.report.issues.push(Issue::Unparsable {
+                        path: path.clone(),
+                        cause: err.to_string(),
+                    });
+                    if self.repair {
+                        self.quarantine(path)?;
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    // Check schema files, a collection whose schema is broken is left without one by the repair
+    fn check_schemas(&mut self, files: &[PathBuf], metadata: &DbMeta) -> Result<()> {
+        for path in files
+            .iter()
+            .filter(|path| path.parent() == Some(Path::new(Database::SCHEMA_DIR)))
+        {
+            let registered = path
+                .file_stem()
+                .and_then(OsStr::to_str)
+                .is_some_and(|name| metadata.collections.contains_key(name));
+            if !registered {
+                self.report
+                    .issues
+                    .push(Issue::OrphanFile { path: path.clone() });
+                if self.repair {
+                    self.quarantine(path)?;
+                }
+                continue;
+            }
+
+            if let Err(err) = self.io.deserialize(path).and_then(Schema::new) {
+                self.report.issues.push(Issue::Unparsable {
+                    path: path.clone(),
+                    cause: err.to_string(),
+                });
+                if self.repair {
+                    self.quarantine(path)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    // Check the write-ahead log, a broken log is quarantined as a whole by the repair
+    fn check_wal(&mut self) -> Result<()> {
+        let path = PathBuf::from(Database::WAL_FILE);
+        if !self.io.exists(&path) {
+            return Ok(());
+        }
+
+        if let Err(err) = self.io.deserialize_lines::<Record, _>(&path) {
+            self.report.issues.push(Issue::Unparsable {
+                path: path.clone(),
+                cause: err.to_string(),
+            });
+            if self.repair {
+                self.quarantine(&path)?;
+            }
+            return Ok(());
+        }
+
+        // Records are ignored by the replay unless terminated by a new line, yet a torn record
+        // would corrupt the one appended after it
+        let content = self.io.read(&path)?;
+        if !content.is_empty() && !content.ends_with(b"\n") {
+            self.report
+                .issues
+                .push(Issue::TornRecord { path: path.clone() });
+            if self.repair {
+                let complete = content
+                    .iter()
+                    .rposition(|byte| *byte == b'\n')
+                    .map_or(0, |position| position + 1);
+                self.io.write(&path, &content[..complete])?;
+            }
+        }
+        Ok(())
+    }
+
+    // Move a file into the quarantine directory, keeping its relative path. Content is copied
+    // verbatim, a damaged file of an encrypted database could not be encrypted again
+    fn quarantine(&mut self, path: &Path) -> Result<()> {
+        let target = self.quarantine_dir.join(path);
+        self.io.write_new(&target, &self.io.read(path)?)?;
+        self.io.remove(path)?;
+        self.report.quarantined.push((path.to_path_buf(), target));
+        Ok(())
+    }
+
+    // Write an empty collection file and return its checksum
+    fn write_empty(&self, path: &Path, format: Format, compression: Compression) -> Result<u32> {
+        let content = self
+            .io
+            .serialize_to_vec(&Documents::new(), path, format, compression)?;
+        self.io.write_new(path, &content)?;
+        Ok(Io::checksum(&content))
+    }
+
+    // Return name of a collection the file would belong to, if it looks like a collection file
+    fn collection_name(path: &Path) -> Option<&str> {
+        if path.parent() != Some(Path::new("")) || path.extension() != Some(OsStr::new("json")) {
+            return None;
+        }
+        path.file_stem()
+            .and_then(OsStr::to_str)
+            .filter(|name| !name.starts_with('.') && is_name_valid(name))
+    }
+
+    fn is_temp_file(path: &Path) -> bool {
+        path.file_name()
+            .and_then(OsStr::to_str)
+            .is_some_and(|name| name.starts_with('.') && name.ends_with(Self::TEMP_SUFFIX))
+    }
+}
+
+// Return an error describing a value which is not unique
+fn unique_violation(collection: &str, pointer: &str, value: &JValue) -> Error {
+    Error::custom_err(
+        CustomKind::UniqueViolation,
+        &format!(
+            "Value {} at '{}' is not unique in collection '{}'",
+            value, pointer, collection
+        ),
+    )
+}
+
+// Return an error if a document is not a JSON object
+fn ensure_object(document: &JValue) -> Result<()> {
+    if document.is_object() {
+        Ok(())
+    } else {
+        Err(Error::custom_err(
+            CustomKind::InvalidArgument,
+            "Document has to be a JSON object",
+        ))
+    }
+}
+
+// Operations shared by collection handles and transactions. Each operation validates its
+// arguments against a document currently stored under a key and returns a mutation to perform
+
+fn insert_mutation(
+    collection: &str,
+    key: &str,
+    existing: Option<&JValue>,
+    document: JValue,
+) -> Result<Mutation> {
+    ensure_object(&document)?;
+    if key.is_empty() || existing.is_some() {
+        return Err(Error::custom_err(
+            CustomKind::InvalidArgument,
+            &format!("Key '{}' is either empty or already taken", key),
+        ));
+    }
+
+    Ok(Mutation::Put {
+        collection: collection.to_string(),
+        key: key.to_string(),
+        document,
+    })
+}
+
+fn replace_mutation(
+    collection: &str,
+    key: &str,
+    existing: Option<&JValue>,
+    document: JValue,
+) -> Result<Mutation> {
+    ensure_object(&document)?;
+    ensure_document_exists(collection, key, existing)?;
+
+    Ok(Mutation::Put {
+        collection: collection.to_string(),
+        key: key.to_string(),
+        document,
+    })
+}
+
+fn update_mutation(
+    collection: &str,
+    key: &str,
+    existing: Option<&JValue>,
+    fields: JValue,
+) -> Result<Mutation> {
+    ensure_object(&fields)?;
+    let mut document = ensure_document_exists(collection, key, existing)?.clone();
+
+    let object = document.as_object_mut().unwrap();
+    if let JValue::Object(fields) = fields {
+        object.extend(fields);
+    }
+
+    Ok(Mutation::Put {
+        collection: collection.to_string(),
+        key: key.to_string(),
+        document,
+    })
+}
+
+fn delete_mutation(collection: &str, key: &str, existing: Option<&JValue>) -> Result<Mutation> {
+    ensure_document_exists(collection, key, existing)?;
+
+    Ok(Mutation::Delete {
+        collection: collection.to_string(),
+        key: key.to_string(),
+    })
+}
+
+// Return an existing document or an error if it does not exist
+fn ensure_document_exists<'a>(
+    collection: &str,
+    key: &str,
+    existing: Option<&'a JValue>,
+) -> Result<&'a JValue> {
+    existing.ok_or_else(|| {
+        Error::custom_err(
+            CustomKind::NotFound,
+            &format!("Document '{}' does not exist in '{}'", key, collection),
+        )
+    })
+}
+
+impl Collection<'_> {
+    fn documents(&self) -> &Documents {
+        &self.database.documents[&self.name]
+    }
+
+    // Perform a mutation. Documents are altered only if the mutation has been recorded in
+    // the write-ahead log successfully. A document stored under the mutated key before is returned
+    fn mutate(&mut self, mutation: Mutation) -> Result<Option<JValue>> {
+        Ok(self.database.write_batch(vec![mutation])?.pop().flatten())
+    }
+
+    /// Return name of the collection.
+    #[must_use]
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Return number of documents stored inside the collection.
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.documents().len()
+    }
+
+    /// Check whether the collection contains no documents.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.documents().is_empty()
+    }
+
+    /// Return an iterator over documents and their keys, ordered by keys.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &JValue)> {
+        self.documents()
+            .iter()
+            .map(|(key, document)| (key.as_str(), document))
+    }
+
+    /// Get a document by its key.
+    #[must_use]
+    pub fn get(&self, key: &str) -> Option<&JValue> {
+        self.documents().get(key)
+    }
+
+    /// Return the JSON Schema attached to the collection, if any.
+    ///
+    /// See [`Database::set_schema`] for details.
+    #[must_use]
+    pub fn schema(&self) -> Option<&JValue> {
+        self.database.schemas.get(&self.name).map(Schema::source)
+    }
+
+    // Return keys of documents which may match a query, as determined by indexes of the collection.
+    // None is returned if no index can serve any predicate of the query
+    fn candidates(&self, query: &Query) -> Option<BTreeSet<&str>> {
+        let indexes = self.database.indexes.get(&self.name)?;
+        query
+            .predicates()
+            .iter()
+            .filter_map(|predicate| indexes.get(predicate.pointer())?.lookup(predicate))
+            .reduce(|lhs, rhs| lhs.intersection(&rhs).copied().collect())
+    }
+
+    /// Execute a query against documents stored inside the collection.
+    ///
+    /// Equality and range predicates (see [`Query::eq`], [`Query::is_in`], [`Query::lt`] and
+    /// [`Query::gt`]) on indexed pointers are served by indexes of the collection (see
+    /// [`Database::create_index`]), so only documents found in the indexes are evaluated.
+    /// Otherwise every document of the collection is evaluated. Results are the same in both cases.
+    ///
+    /// # Errors
+    /// The function returns a custom library error in case the query is invalid.
+    pub fn query(&self, query: &Query) -> Result<Vec<Match>> {
+        self.candidates(query).map_or_else(
+            || query.execute(self.iter()),
+            |keys| {
+                query.execute(
+                    keys.into_iter()
+                        .filter_map(|key| Some((key, self.get(key)?))),
+                )
+            },
+        )
+    }
+
+    /// Insert a new document under an automatically generated key.
+    ///
+    /// Keys are generated as random UUIDs. The generated key is returned to the caller.
+    ///
+    /// # Errors
+    /// The function returns a custom library error in case the document is not a JSON object.
+    /// I/O and serde errors are forwarded to the caller.
+    pub fn insert(&mut self, document: JValue) -> Result<String> {
+        let key = Uuid::new_v4().to_string();
+        self.insert_with_key(&key, document)?;
+        Ok(key)
+    }
+
+    /// Insert a new document under a key chosen by the caller.
+    ///
+    /// # Errors
+    /// The function returns a custom library error in case the key is empty or already taken
+    /// as well as when the document is not a JSON object. I/O and serde errors are forwarded
+    /// to the caller.
+    pub fn insert_with_key(&mut self, key: &str, document: JValue) -> Result<()> {
+        let mutation = insert_mutation(&self.name, key, self.get(key), document)?;
+        self.mutate(mutation)?;
+        Ok(())
+    }
+
+    /// Replace an existing document, returning the previous one.
+    ///
+    /// # Panics
+    /// Should never panic. If the function panics then it should be considered as a bug
+    /// inside the function's implementation.
+    ///
+    /// # Errors
+    /// The function returns a custom library error in case the document does not exist or the new
+    /// one is not a JSON object. I/O and serde errors are forwarded to the caller.
+    pub fn replace(&mut self, key: &str, document: JValue) -> Result<JValue> {
+        let mutation = replace_mutation(&self.name, key, self.get(key), document)?;
+        Ok(self.mutate(mutation)?.unwrap())
+    }
+
+    /// Update selected fields of an existing document.
+    ///
+    /// Every top-level field of `fields` overwrites a corresponding field of the document or is
+    /// added if the document does not contain it yet. Remaining fields are left untouched.
+    ///
+    /// # Panics
+    /// Should never panic. If the function panics then it should be considered as a bug
+    /// inside the function's implementation.
+    ///
+    /// # Errors
+    /// The function returns a custom library error in case the document does not exist or
+    /// `fields` is not a JSON object. I/O and serde errors are forwarded to the caller.
+    pub fn update(&mut self, key: &str, fields: JValue) -> Result<()> {
+        let mutation = update_mutation(&self.name, key, self.get(key), fields)?;
+        self.mutate(mutation)?;
+        Ok(())
+    }
+
+    /// Delete an existing document, returning it to the caller.
+    ///
+    /// # Panics
+    /// Should never panic. If the function panics then it should be considered as a bug
+    /// inside the function's implementation.
+    ///
+    /// # Errors
+    /// The function returns a custom library error in case the document does not exist.
+    /// I/O and serde errors are forwarded to the caller.
+    pub fn delete(&mut self, key: &str) -> Result<JValue> {
+        let mutation = delete_mutation(&self.name, key, self.get(key))?;
+        Ok(self.mutate(mutation)?.unwrap())
+    }
+}
+
+impl Transaction<'_> {
+    // Return a document as seen by the transaction
+    fn current(&mut self, collection: &str, key: &str) -> Result<Option<&JValue>> {
+        self.database.ensure_collection_exists(collection)?;
+        self.database.load_collection(collection)?;
+
+        let staged_key = (collection.to_string(), key.to_string());
+        match self.staged.get(&staged_key) {
+            Some(staged) => Ok(staged.as_ref()),
+            None => Ok(self.database.documents[collection].get(key)),
+        }
+    }
+
+    // Stage a mutation, returning a document stored under the mutated key before as seen by
+    // the transaction
+    fn stage(&mut self, mutation: Mutation) -> Result<Option<JValue>> {
+        self.database.ensure_valid(&mutation)?;
+        let previous = self
+            .current(mutation.collection(), mutation.key())?
+            .cloned();
+        let document = match &mutation {
+            Mutation::Put { document, .. } => Some(document.clone()),
+            Mutation::Delete { .. } => None,
+        };
+
+        self.staged.insert(
+            (
+                mutation.collection().to_string(),
+                mutation.key().to_string(),
+            ),
+            document,
+        );
+        self.mutations.push(mutation);
+        Ok(previous)
+    }
+
+    /// Get a document by its key, including changes made by the transaction so far.
+    ///
+    /// # Errors
+    /// The function returns a custom library error in case the collection does not exist.
+    /// I/O and serde errors are forwarded to the caller if the collection could not be loaded.
+    pub fn get(&mut self, collection: &str, key: &str) -> Result<Option<&JValue>> {
+        self.current(collection, key)
+    }
+
+    /// Insert a new document under an automatically generated key.
+    ///
+    /// The operation behaves like [`Collection::insert`] but takes effect on commit only.
+    ///
+    /// # Errors
+    /// The function returns a custom library error in case the collection does not exist or
+    /// the document is not a JSON object.
+    pub fn insert(&mut self, collection: &str, document: JValue) -> Result<String> {
+        let key = Uuid::new_v4().to_string();
+        self.insert_with_key(collection, &key, document)?;
+        Ok(key)
+    }
+
+    /// Insert a new document under a key chosen by the caller.
+    ///
+    /// The operation behaves like [`Collection::insert_with_key`] but takes effect on commit only.
+    ///
+    /// # Errors
+    /// The function returns a custom library error in case the collection does not exist, the key
+    /// is empty or already taken as well as when the document is not a JSON object.
+    pub fn insert_with_key(&mut self, collection: &str, key: &str, document: JValue) -> Result<()> {
+        let existing = self.current(collection, key)?;
+        let mutation = insert_mutation(collection, key, existing, document)?;
+        self.stage(mutation)?;
+        Ok(())
+    }
+
+    /// Replace an existing document, returning the previous one.
+    ///
+    /// The operation behaves like [`Collection::replace`] but takes effect on commit only.
+    ///
+    /// # Panics
+    /// Should never panic. If the function panics then it should be considered as a bug
+    /// inside the function's implementation.
+    ///
+    /// # Errors
+    /// The function returns a custom library error in case either the collection or the document
+    /// does not exist or the new document is not a JSON object.
+    pub fn replace(&mut self, collection: &str, key: &str, document: JValue) -> Result<JValue> {
+        let existing = self.current(collection, key)?;
+        let mutation = replace_mutation(collection, key, existing, document)?;
+        Ok(self.stage(mutation)?.unwrap())
+    }
+
+    /// Update selected fields of an existing document.
+    ///
+    /// The operation behaves like [`Collection::update`] but takes effect on commit only.
+    ///
+    /// # Errors
+    /// The function returns a custom library error in case either the collection or the document
+    /// does not exist or `fields` is not a JSON object.
+    pub fn update(&mut self, collection: &str, key: &str, fields: JValue) -> Result<()> {
+        let existing = self.current(collection, key)?;
+        let mutation = update_mutation(collection, key, existing, fields)?;
+        self.stage(mutation)?;
+        Ok(())
+    }
+
+    /// Delete an existing document, returning it to the caller.
+    ///
+    /// The operation behaves like [`Collection::delete`] but takes effect on commit only.
+    ///
+    /// # Panics
+    /// Should never panic. If the function panics then it should be considered as a bug
+    /// inside the function's implementation.
+    ///
+    /// # Errors
+    /// The function returns a custom library error in case either the collection or the document
+    /// does not exist.
+    pub fn delete(&mut self, collection: &str, key: &str) -> Result<JValue> {
+        let existing = self.current(collection, key)?;
+        let mutation = delete_mutation(collection, key, existing)?;
+        Ok(self.stage(mutation)?.unwrap())
+    }
+
+    /// Apply all operations of the transaction.
+    ///
+    /// Operations are recorded in the write-ahead log as a single record, so either all of them
+    /// take effect, also after a crash, or none of them does.
+    ///
+    /// # Errors
+    /// I/O and serde errors are forwarded to the caller. No operation takes effect in such a case.
+    pub fn commit(self) -> Result<()> {
+        if !self.mutations.is_empty() {
+            self.database.write_batch(self.mutations)?;
+        }
+        Ok(())
+    }
+
+    /// Discard all operations of the transaction.
+    ///
+    /// Dropping a transaction without committing it has the same effect.
+    pub fn rollback(self) {}
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::query::Order;
+    use chrono::Utc;
+    use more_asserts::*;
+    use rstest::*;
+    use serde_json::json;
+    use std::path::Path;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::Arc;
+    use tempdir::TempDir;
+
+    /* ----------------- */
+    /* ---- Helpers ---- */
+    /* ----------------- */
+
+    const DATABASE_NAME: &str = "TestDatabase";
+
+    // In-memory storage which may be told to fail on every write
+    #[derive(Debug, Clone, Default)]
+    struct TestStorage {
+        inner: MemoryStorage,
+        failing: Arc<AtomicBool>,
+    }
+
+    impl TestStorage {
+        fn fail_writes(&self) {
+            self.failing.store(true, Ordering::SeqCst);
+        }
+
+        fn check(&self) -> Result<()> {
+            if self.failing.load(Ordering::SeqCst) {
+                Err(Error::custom_err(CustomKind::DbIo, "IO failure"))
+            } else {
+                Ok(())
+            }
+        }
+
+        // Return content of a JSON file
+        fn json(&self, path: &str) -> JValue {
+            serde_json::from_slice(&self.inner.read(Path::new(path)).unwrap()).unwrap()
+        }
+
+        // Return records of the write-ahead log
+        fn wal(&self) -> Vec<Record> {
+            let content = self.inner.read(Path::new(".wal")).unwrap_or_default();
+            String::from_utf8(content)
+                .unwrap()
+                .lines()
+                .map(|line| serde_json::from_str(line).unwrap())
+                .collect()
+        }
+    }
+
+    impl Storage for TestStorage {
+        fn root(&self) -> &Path {
+            self.inner.root()
+        }
+
+        fn exists(&self, path: &Path) -> bool {
+            self.inner.exists(path)
+        }
+
+        fn read(&self, path: &Path) -> Result<Vec<u8>> {
+            self.inner.read(path)
+        }
+
+        fn write(&self, path: &Path, data: &[u8]) -> Result<()> {
+            self.check()?;
+            self.inner.write(path, data)
+        }
+
+        fn write_new(&self, path: &Path, data: &[u8]) -> Result<()> {
+            self.check()?;
+            self.inner.write_new(path, data)
+        }
+
+        fn append(&self, path: &Path, data: &[u8]) -> Result<()> {
+            self.check()?;
+            self.inner.append(path, data)
+        }
+
+        fn remove(&self, path: &Path) -> Result<()> {
+            self.check()?;
+            self.inner.remove(path)
+        }
+
+        fn rename(&self, from: &Path, to: &Path) -> Result<()> {
+            self.check()?;
+            self.inner.rename(from, to)
+        }
+
+        fn list(&self) -> Result<Vec<PathBuf>> {
+            self.inner.list()
+        }
+    }
+
+    // Return a database kept in the storage with two collections: 'words' and 'history'
+    fn database_in(storage: &TestStorage) -> Database {
+        let mut database = Database::create_with(DATABASE_NAME, Box::new(storage.clone())).unwrap();
+        database.create_collection("words").unwrap();
+        database.create_collection("history").unwrap();
+        database
+    }
+
+    // Return a database kept in the storage whose 'words' collection holds two documents
+    fn database_with_words_in(storage: &TestStorage) -> Database {
+        let mut database = database_in(storage);
+        let mut words = database.collection("words").unwrap();
+        words
+            .insert_with_key("apple", json!({"word": "apple", "level": 1}))
+            .unwrap();
+        words
+            .insert_with_key("house", json!({"word": "house", "level": 2}))
+            .unwrap();
+        database.checkpoint().unwrap();
+        database
+    }
+
+    // Reopen a database kept in the storage
+    fn reopen(storage: &TestStorage, mode: OpenMode) -> Database {
+        Database::open_with(Box::new(storage.clone()), mode).unwrap()
+    }
+
+    /* ------------------ */
+    /* ---- Fixtures ---- */
+    /* ------------------ */
+
+    #[fixture]
+    fn storage() -> TestStorage {
+        TestStorage::default()
+    }
+
+    #[fixture]
+    fn database() -> Database {
+        database_in(&storage())
+    }
+
+    #[fixture]
+    fn database_with_words() -> Database {
+        database_with_words_in(&storage())
+    }
+
+    /* -------------------------- */
+    /* ---- Test definitions ---- */
+    /* -------------------------- */
+
+    #[rstest]
+    fn database_is_successfully_created() {
+        let database = Database::create_in_memory(DATABASE_NAME).unwrap();
+
+        // Check whether internal metadata is filled with correct values
+        let now = Utc::now();
+        assert_eq!(DATABASE_NAME, database.metadata.name);
+        assert_gt!(now, database.metadata.created);
+        assert_eq!(database.metadata.created, database.metadata.modified);
+        assert!(database.list_collections().is_empty());
+    }
+
+    #[rstest]
+    fn database_is_successfully_opened() {
+        let temp_dir = TempDir::new("").unwrap();
+        let mut database = Database::create(DATABASE_NAME, temp_dir.path()).unwrap();
+        database.create_collection("words").unwrap();
+        database
+            .collection("words")
+            .unwrap()
+            .insert_with_key("apple", json!({"word": "apple"}))
+            .unwrap();
+        drop(database);
+
+        let mut database =
+            Database::open(temp_dir.path().join(DATABASE_NAME), OpenMode::ReadWrite).unwrap();
+        assert_eq!(DATABASE_NAME, database.metadata.name);
+        let words = database.collection("words").unwrap();
+        assert_eq!(json!({"word": "apple"}), *words.get("apple").unwrap());
+
+        drop(database);
+        temp_dir.close().unwrap();
+    }
+
+    #[rstest]
+    fn invalid_database_name_produces_error() {
+        let err = Database::create_in_memory("my-database").err().unwrap();
+        assert_eq!(CustomKind::InvalidArgument, *err.get_custom_kind().unwrap());
+    }
+
+    #[rstest]
+    fn collection_is_created(storage: TestStorage) {
+        let mut database = database_in(&storage);
+
+        database.create_collection("cards").unwrap();
+        assert_eq!(
+            vec!["cards", "history", "words"],
+            database.list_collections()
+        );
+        assert_eq!(json!({}), storage.json("cards.json"));
+        assert!(storage.json(".metadata/metadata.json")["collections"]
+            .get("cards")
+            .is_some());
+    }
+
+    #[rstest]
+    #[case::empty_name("")]
+    #[case::forbidden_characters("my-cards")]
+    #[case::existing_collection("words")]
+    fn collection_is_not_created_when_name_is_not_available(
+        #[case] name: &str,
+        mut database: Database,
+    ) {
+        let err = database.create_collection(name).unwrap_err();
+        assert_eq!(CustomKind::InvalidArgument, *err.get_custom_kind().unwrap());
+        assert_eq!(vec!["history", "words"], database.list_collections());
+    }
+
+    #[rstest]
+    fn collection_is_dropped(storage: TestStorage) {
+        let mut database = database_in(&storage);
+
+        database.drop_collection("words").unwrap();
+        assert_eq!(vec!["history"], database.list_collections());
+        assert!(!storage.exists(Path::new("words.json")));
+        assert!(storage.json(".metadata/metadata.json")["collections"]
+            .get("words")
+            .is_none());
+    }
+
+    #[rstest]
+    fn dropping_non_existing_collection_produces_error(mut database: Database) {
+        let err = database.drop_collection("cards").unwrap_err();
+        assert_eq!(CustomKind::NotFound, *err.get_custom_kind().unwrap());
+    }
+
+    #[rstest]
+    fn collection_is_not_dropped_when_io_fails(storage: TestStorage) {
+        let mut database = database_in(&storage);
+        storage.fail_writes();
+
+        database.drop_collection("words").unwrap_err();
+        assert_eq!(vec!["history", "words"], database.list_collections());
+    }
+
+    #[rstest]
+    fn collections_are_listed_in_alphabetical_order(database: Database) {
+        assert_eq!(vec!["history", "words"], database.list_collections());
+    }
+
+    #[rstest]
+    fn collection_is_renamed(storage: TestStorage) {
+        let mut database = database_with_words_in(&storage);
+
+        database.rename_collection("words", "vocab").unwrap();
+        assert_eq!(vec!["history", "vocab"], database.list_collections());
+        assert!(!storage.exists(Path::new("words.json")));
+        assert_eq!(2, storage.json("vocab.json").as_object().unwrap().len());
+        assert!(storage.json(".metadata/metadata.json")["collections"]
+            .get("vocab")
+            .is_some());
+    }
+
+    #[rstest]
+    #[case::non_existing_collection("cards", "vocab", CustomKind::NotFound)]
+    #[case::invalid_name("words", "vocab!", CustomKind::InvalidArgument)]
+    #[case::existing_name("words", "history", CustomKind::InvalidArgument)]
+    fn collection_is_not_renamed_when_arguments_are_invalid(
+        #[case] name: &str,
+        #[case] new_name: &str,
+        #[case] kind: CustomKind,
+        mut database: Database,
+    ) {
+        let err = database.rename_collection(name, new_name).unwrap_err();
+        assert_eq!(kind, *err.get_custom_kind().unwrap());
+        assert_eq!(vec!["history", "words"], database.list_collections());
+    }
+
+    #[rstest]
+    fn accessing_non_existing_collection_produces_error(mut database: Database) {
+        let err = database.collection("cards").err().unwrap();
+        assert_eq!(CustomKind::NotFound, *err.get_custom_kind().unwrap());
+    }
+
+    #[rstest]
+    fn collection_is_loaded_on_first_access_only(storage: TestStorage) {
+        drop(database_with_words_in(&storage));
+        let mut database = reopen(&storage, OpenMode::ReadWrite);
+
+        assert!(!database.documents.contains_key("words"));
+        assert_eq!(2, database.collection("words").unwrap().len());
+        // Second access shall use documents loaded into memory
+        storage.inner.remove(Path::new("words.json")).unwrap();
+        assert_eq!(2, database.collection("words").unwrap().len());
+    }
+
+    #[rstest]
+    fn document_is_inserted_under_generated_key(mut database_with_words: Database) {
+        let mut words = database_with_words.collection("words").unwrap();
+
+        let key1 = words.insert(json!({"word": "tree"})).unwrap();
+        let key2 = words.insert(json!({"word": "tree"})).unwrap();
+        assert_ne!(key1, key2);
+        assert_eq!(json!({"word": "tree"}), *words.get(&key1).unwrap());
+        assert_eq!(4, words.len());
+    }
+
+    #[rstest]
+    fn document_is_inserted_under_chosen_key(storage: TestStorage) {
+        let mut database = database_with_words_in(&storage);
+        let mut words = database.collection("words").unwrap();
+
+        words
+            .insert_with_key("tree", json!({"word": "tree"}))
+            .unwrap();
+        assert_eq!(json!({"word": "tree"}), *words.get("tree").unwrap());
+        assert_eq!(
+            vec![vec![Mutation::Put {
+                collection: "words".to_string(),
+                key: "tree".to_string(),
+                document: json!({"word": "tree"}),
+            }]],
+            storage.wal()
+        );
+    }
+
+    #[rstest]
+    #[case::empty_key("", json!({"word": "tree"}))]
+    #[case::existing_key("apple", json!({"word": "tree"}))]
+    #[case::not_an_object("tree", json!(["tree"]))]
+    fn invalid_document_is_not_inserted(
+        #[case] key: &str,
+        #[case] document: JValue,
+        mut database_with_words: Database,
+    ) {
+        let mut words = database_with_words.collection("words").unwrap();
+
+        let err = words.insert_with_key(key, document).unwrap_err();
+        assert_eq!(CustomKind::InvalidArgument, *err.get_custom_kind().unwrap());
+        assert_eq!(2, words.len());
+    }
+
+    #[rstest]
+    fn document_is_not_inserted_when_io_fails(storage: TestStorage) {
+        let mut database = database_with_words_in(&storage);
+        storage.fail_writes();
+        let mut words = database.collection("words").unwrap();
+
+        words.insert_with_key("tree", json!({})).unwrap_err();
+        assert!(words.get("tree").is_none());
+    }
+
+    #[rstest]
+    fn document_is_replaced(mut database_with_words: Database) {
+        let mut words = database_with_words.collection("words").unwrap();
+
+        let previous = words.replace("apple", json!({"word": "Apple"})).unwrap();
+        assert_eq!(json!({"word": "apple", "level": 1}), previous);
+        assert_eq!(json!({"word": "Apple"}), *words.get("apple").unwrap());
+    }
+
+    #[rstest]
+    fn document_is_updated_partially(mut database_with_words: Database) {
+        let mut words = database_with_words.collection("words").unwrap();
+
+        words
+            .update("apple", json!({"level": 3, "tags": ["fruit"]}))
+            .unwrap();
+        assert_eq!(
+            json!({"word": "apple", "level": 3, "tags": ["fruit"]}),
+            *words.get("apple").unwrap()
+        );
+    }
+
+    #[rstest]
+    fn document_is_not_updated_when_io_fails(storage: TestStorage) {
+        let mut database = database_with_words_in(&storage);
+        storage.fail_writes();
+        let mut words = database.collection("words").unwrap();
+
+        words.update("apple", json!({"level": 3})).unwrap_err();
+        assert_eq!(
+            json!({"word": "apple", "level": 1}),
+            *words.get("apple").unwrap()
+        );
+    }
+
+    #[rstest]
+    fn document_is_deleted(mut database_with_words: Database) {
+        let mut words = database_with_words.collection("words").unwrap();
+
+        let deleted = words.delete("house").unwrap();
+        assert_eq!(json!({"word": "house", "level": 2}), deleted);
+        assert!(words.get("house").is_none());
+        assert_eq!(1, words.len());
+    }
+
+    #[rstest]
+    fn manipulating_non_existing_document_produces_error(mut database_with_words: Database) {
+        let mut words = database_with_words.collection("words").unwrap();
+
+        let errors = [
+            words.replace("tree", json!({})).unwrap_err(),
+            words.update("tree", json!({})).unwrap_err(),
+            words.delete("tree").unwrap_err(),
+        ];
+        for err in errors {
+            assert_eq!(CustomKind::NotFound, *err.get_custom_kind().unwrap());
+        }
+    }
+
+    #[rstest]
+    fn collection_is_queried(mut database_with_words: Database) {
+        let words = database_with_words.collection("words").unwrap();
+
+        let query = Query::new().gt("/level", json!(1)).project(&["/word"]);
+        let matches = words.query(&query).unwrap();
+        assert_eq!(
+            vec![("house".to_string(), json!({"word": "house"}))],
+            matches
+        );
+    }
+
+    #[rstest]
+    fn documents_are_iterated_in_key_order(mut database_with_words: Database) {
+        let words = database_with_words.collection("words").unwrap();
+
+        let keys: Vec<&str> = words.iter().map(|(key, _)| key).collect();
+        assert_eq!(vec!["apple", "house"], keys);
+    }
+
+    #[rstest]
+    fn write_ahead_log_is_replayed_when_opening_database(storage: TestStorage) {
+        let mut database = database_with_words_in(&storage);
+        database
+            .collection("words")
+            .unwrap()
+            .insert_with_key("tree", json!({"word": "tree"}))
+            .unwrap();
+        // Mutations of non-existing collections are skipped
+        let record = vec![
+            Mutation::Put {
+                collection: "cards".to_string(),
+                key: "card".to_string(),
+                document: json!({}),
+            },
+            Mutation::Delete {
+                collection: "words".to_string(),
+                key: "apple".to_string(),
+            },
+        ];
+        database.io.append(&record, Database::WAL_FILE).unwrap();
+        drop(database);
+
+        let mut database = reopen(&storage, OpenMode::ReadWrite);
+        assert_eq!(0, database.wal_len);
+        assert!(database.dirty.is_empty());
+        assert!(!storage.exists(Path::new(".wal")));
+        assert_eq!(
+            json!({"house": {"word": "house", "level": 2}, "tree": {"word": "tree"}}),
+            storage.json("words.json")
+        );
+        let words = database.collection("words").unwrap();
+        assert_eq!(json!({"word": "tree"}), *words.get("tree").unwrap());
+    }
+
+    #[rstest]
+    #[case::torn_record_only(b"[{\"op\":\"put\",\"coll".to_vec())]
+    #[case::torn_record_after_complete_one(
+        b"[{\"op\":\"delete\",\"collection\":\"words\",\"key\":\"apple\"}]\n[{\"op\"".to_vec()
+    )]
+    fn torn_write_ahead_log_tail_is_discarded_when_opening_database(
+        #[case] content: Vec<u8>,
+        storage: TestStorage,
+    ) {
+        drop(database_with_words_in(&storage));
+        storage.write(Path::new(".wal"), &content).unwrap();
+
+        let mut database = reopen(&storage, OpenMode::ReadWrite);
+        assert!(!storage.exists(Path::new(".wal")));
+        database
+            .collection("words")
+            .unwrap()
+            .insert_with_key("tree", json!({"word": "tree"}))
+            .unwrap();
+        drop(database);
+
+        let mut database = reopen(&storage, OpenMode::ReadWrite);
+        let words = database.collection("words").unwrap();
+        assert_eq!(json!({"word": "tree"}), *words.get("tree").unwrap());
+        assert!(words.get("house").is_some());
+    }
+
+    #[rstest]
+    fn checkpoint_writes_altered_collections_and_clears_write_ahead_log(storage: TestStorage) {
+        let mut database = database_with_words_in(&storage);
+        database
+            .collection("words")
+            .unwrap()
+            .delete("apple")
+            .unwrap();
+        assert_eq!(2, storage.json("words.json").as_object().unwrap().len());
+
+        database.checkpoint().unwrap();
+        assert_eq!(1, storage.json("words.json").as_object().unwrap().len());
+        assert!(!storage.exists(Path::new(".wal")));
+        // Nothing to do on subsequent checkpoint
+        database.checkpoint().unwrap();
+    }
+
+    #[rstest]
+    fn write_ahead_log_is_kept_when_checkpoint_fails(storage: TestStorage) {
+        let mut database = database_with_words_in(&storage);
+        database
+            .collection("words")
+            .unwrap()
+            .delete("apple")
+            .unwrap();
+        storage.fail_writes();
+
+        database.checkpoint().unwrap_err();
+        assert_eq!(1, database.wal_len);
+        assert!(database.dirty.contains("words"));
+        assert_eq!(1, storage.wal().len());
+    }
+
+    #[rstest]
+    fn checkpoint_is_performed_when_write_ahead_log_grows_large(storage: TestStorage) {
+        let mut database = database_with_words_in(&storage);
+        database.wal_len = Database::CHECKPOINT_THRESHOLD - 1;
+
+        database
+            .collection("words")
+            .unwrap()
+            .insert_with_key("tree", json!({}))
+            .unwrap();
+        assert_eq!(0, database.wal_len);
+        assert!(storage.json("words.json").get("tree").is_some());
+    }
+
+    #[rstest]
+    fn transaction_is_committed_as_single_record(storage: TestStorage) {
+        let mut database = database_with_words_in(&storage);
+
+        let mut transaction = database.transaction();
+        transaction
+            .update("words", "apple", json!({"level": 2}))
+            .unwrap();
+        let review = transaction
+            .insert("history", json!({"word": "apple", "result": "good"}))
+            .unwrap();
+        transaction.delete("words", "house").unwrap();
+        transaction.commit().unwrap();
+
+        let wal = storage.wal();
+        assert_eq!(1, wal.len());
+        assert_eq!(3, wal[0].len());
+        assert_eq!("words", wal[0][0].collection());
+        assert_eq!("history", wal[0][1].collection());
+        assert_eq!("house", wal[0][2].key());
+
+        let words = database.collection("words").unwrap();
+        assert_eq!(
+            json!({"word": "apple", "level": 2}),
+            *words.get("apple").unwrap()
+        );
+        assert!(words.get("house").is_none());
+        let history = database.collection("history").unwrap();
+        assert!(history.get(&review).is_some());
+    }
+
+    #[rstest]
+    fn transaction_sees_its_own_operations(mut database_with_words: Database) {
+        let mut transaction = database_with_words.transaction();
+
+        transaction
+            .insert_with_key("words", "tree", json!({"word": "tree"}))
+            .unwrap();
+        transaction
+            .update("words", "tree", json!({"level": 1}))
+            .unwrap();
+        assert_eq!(
+            json!({"word": "tree", "level": 1}),
+            *transaction.get("words", "tree").unwrap().unwrap()
+        );
+
+        transaction.delete("words", "apple").unwrap();
+        assert!(transaction.get("words", "apple").unwrap().is_none());
+        let err = transaction.delete("words", "apple").unwrap_err();
+        assert_eq!(CustomKind::NotFound, *err.get_custom_kind().unwrap());
+        transaction
+            .insert_with_key("words", "apple", json!({"word": "Apple"}))
+            .unwrap();
+    }
+
+    #[rstest]
+    fn rolled_back_transaction_has_no_effect(mut database_with_words: Database) {
+        let mut transaction = database_with_words.transaction();
+        transaction.delete("words", "apple").unwrap();
+        transaction.rollback();
+
+        let mut transaction = database_with_words.transaction();
+        transaction.delete("words", "house").unwrap();
+        drop(transaction);
+
+        assert_eq!(2, database_with_words.collection("words").unwrap().len());
+        assert_eq!(0, database_with_words.wal_len);
+    }
+
+    #[rstest]
+    fn failed_commit_has_no_effect(storage: TestStorage) {
+        let mut database = database_with_words_in(&storage);
+        storage.fail_writes();
+
+        let mut transaction = database.transaction();
+        transaction.delete("words", "apple").unwrap();
+        transaction
+            .replace("words", "house", json!({"word": "House"}))
+            .unwrap();
+        transaction.commit().unwrap_err();
+
+        let words = database.collection("words").unwrap();
+        assert_eq!(
+            json!({"word": "house", "level": 2}),
+            *words.get("house").unwrap()
+        );
+        assert!(words.get("apple").is_some());
+        assert!(database.dirty.is_empty());
+    }
+
+    #[rstest]
+    fn empty_transaction_is_committed_without_io(storage: TestStorage) {
+        let mut database = database_with_words_in(&storage);
+
+        database.transaction().commit().unwrap();
+        assert!(!storage.exists(Path::new(".wal")));
+    }
+
+    #[rstest]
+    #[case::non_existing_collection("cards", "apple", CustomKind::NotFound)]
+    #[case::non_existing_document("words", "tree", CustomKind::NotFound)]
+    fn invalid_transaction_operation_produces_error(
+        #[case] collection: &str,
+        #[case] key: &str,
+        #[case] kind: CustomKind,
+        mut database_with_words: Database,
+    ) {
+        let mut transaction = database_with_words.transaction();
+
+        let err = transaction
+            .update(collection, key, json!({"level": 1}))
+            .unwrap_err();
+        assert_eq!(kind, *err.get_custom_kind().unwrap());
+    }
+
+    #[rstest]
+    fn write_ahead_log_is_replayed_in_memory_when_opening_database_in_read_only_mode(
+        storage: TestStorage,
+    ) {
+        let mut database = database_with_words_in(&storage);
+        database
+            .collection("words")
+            .unwrap()
+            .delete("apple")
+            .unwrap();
+        drop(database);
+
+        let mut database = reopen(&storage, OpenMode::ReadOnly);
+        assert_eq!(1, database.collection("words").unwrap().len());
+        // No checkpoint is expected
+        assert_eq!(1, storage.wal().len());
+        let err = database.checkpoint().unwrap_err();
+        assert_eq!(CustomKind::ReadOnly, *err.get_custom_kind().unwrap());
+    }
+
+    #[rstest]
+    fn database_opened_in_read_only_mode_cannot_be_altered(storage: TestStorage) {
+        drop(database_with_words_in(&storage));
+        let mut database = reopen(&storage, OpenMode::ReadOnly);
+        // Any write would fail with a different error
+        storage.fail_writes();
+
+        let errors = [
+            database.create_collection("cards").unwrap_err(),
+            database.drop_collection("words").unwrap_err(),
+            database.rename_collection("words", "vocab").unwrap_err(),
+            database
+                .collection("words")
+                .unwrap()
+                .delete("apple")
+                .unwrap_err(),
+        ];
+        for err in errors {
+            assert_eq!(CustomKind::ReadOnly, *err.get_custom_kind().unwrap());
+        }
+
+        let mut transaction = database.transaction();
+        transaction.delete("words", "apple").unwrap();
+        let err = transaction.commit().unwrap_err();
+        assert_eq!(CustomKind::ReadOnly, *err.get_custom_kind().unwrap());
+
+        // Documents may be still read
+        let words = database.collection("words").unwrap();
+        assert_eq!(2, words.len());
+    }
+
+    #[rstest]
+    fn index_is_created(storage: TestStorage) {
+        let mut database = database_with_words_in(&storage);
+
+        database.create_index("words", "/level").unwrap();
+        database.create_index("words", "/tags/0").unwrap();
+        assert_eq!(
+            vec!["/level", "/tags/0"],
+            database.list_indexes("words").unwrap()
+        );
+        assert!(
+            storage.json(".metadata/metadata.json")["collections"]["words"]["indexes"]
+                .get("/level")
+                .is_some()
+        );
+    }
+
+    #[rstest]
+    #[case::non_existing_collection("cards", "/level", CustomKind::NotFound)]
+    #[case::empty_pointer("words", "", CustomKind::InvalidArgument)]
+    #[case::invalid_pointer("words", "level", CustomKind::InvalidArgument)]
+    #[case::existing_index("words", "/word", CustomKind::InvalidArgument)]
+    fn index_is_not_created_when_arguments_are_invalid(
+        #[case] collection: &str,
+        #[case] pointer: &str,
+        #[case] kind: CustomKind,
+        mut database_with_words: Database,
+    ) {
+        database_with_words.create_index("words", "/word").unwrap();
+
+        let err = database_with_words
+            .create_index(collection, pointer)
+            .unwrap_err();
+        assert_eq!(kind, *err.get_custom_kind().unwrap());
+        assert_eq!(
+            vec!["/word"],
+            database_with_words.list_indexes("words").unwrap()
+        );
+    }
+
+    #[rstest]
+    fn index_is_dropped(mut database_with_words: Database) {
+        database_with_words.create_index("words", "/level").unwrap();
+
+        database_with_words.drop_index("words", "/level").unwrap();
+        assert!(database_with_words
+            .list_indexes("words")
+            .unwrap()
+            .is_empty());
+        assert!(database_with_words.indexes["words"].is_empty());
+
+        let err = database_with_words
+            .drop_index("words", "/level")
+            .unwrap_err();
+        assert_eq!(CustomKind::NotFound, *err.get_custom_kind().unwrap());
+    }
+
+    #[rstest]
+    #[case::eq(Query::new().eq("/level", json!(2)))]
+    #[case::lt(Query::new().lt("/level", json!(3)))]
+    #[case::gt(Query::new().gt("/level", json!(1)).sort_by("/word", Order::Descending))]
+    #[case::is_in(Query::new().is_in("/level", vec![json!(1), json!(3)]))]
+    #[case::mixed(Query::new().gt("/level", json!(1)).lt("/word", json!("tree")).limit(1))]
+    #[case::not_indexed(Query::new().regex("/word", "^[a-h]"))]
+    fn indexed_query_returns_same_documents_as_full_scan(
+        #[case] query: Query,
+        mut database_with_words: Database,
+    ) {
+        let mut words = database_with_words.collection("words").unwrap();
+        words
+            .insert_with_key("tree", json!({"word": "tree", "level": 3}))
+            .unwrap();
+        words
+            .insert_with_key("sun", json!({"word": "sun", "level": "1"}))
+            .unwrap();
+        words
+            .insert_with_key("moon", json!({"word": "moon"}))
+            .unwrap();
+        let expected = words.query(&query).unwrap();
+
+        database_with_words.create_index("words", "/level").unwrap();
+        database_with_words.create_index("words", "/word").unwrap();
+        let words = database_with_words.collection("words").unwrap();
+        assert_eq!(expected, words.query(&query).unwrap());
+    }
+
+    #[rstest]
+    fn index_follows_mutations(mut database_with_words: Database) {
+        database_with_words.create_index("words", "/level").unwrap();
+        let mut words = database_with_words.collection("words").unwrap();
+
+        words.update("apple", json!({"level": 5})).unwrap();
+        words.delete("house").unwrap();
+        words
+            .insert_with_key("tree", json!({"word": "tree", "level": 2}))
+            .unwrap();
+
+        let keys = |query: Query| -> Vec<String> {
+            let matches = words.query(&query).unwrap();
+            matches.into_iter().map(|(key, _)| key).collect()
+        };
+        assert!(keys(Query::new().eq("/level", json!(1))).is_empty());
+        assert_eq!(vec!["apple"], keys(Query::new().gt("/level", json!(4))));
+        assert_eq!(vec!["tree"], keys(Query::new().lt("/level", json!(4))));
+    }
+
+    #[rstest]
+    fn indexes_are_rebuilt_when_collection_is_loaded(storage: TestStorage) {
+        let mut database = database_with_words_in(&storage);
+        database.create_index("words", "/level").unwrap();
+        drop(database);
+
+        let mut database = reopen(&storage, OpenMode::ReadOnly);
+        let words = database.collection("words").unwrap();
+        let query = Query::new().eq("/level", json!(2));
+        assert_eq!(Some(BTreeSet::from(["house"])), words.candidates(&query));
+        assert_eq!("house", words.query(&query).unwrap()[0].0);
+    }
+
+    #[rstest]
+    fn indexes_are_kept_when_collection_is_renamed(mut database_with_words: Database) {
+        database_with_words.create_index("words", "/level").unwrap();
+
+        database_with_words
+            .rename_collection("words", "vocab")
+            .unwrap();
+        assert_eq!(
+            vec!["/level"],
+            database_with_words.list_indexes("vocab").unwrap()
+        );
+        let vocab = database_with_words.collection("vocab").unwrap();
+        assert!(vocab
+            .candidates(&Query::new().eq("/level", json!(1)))
+            .is_some());
+    }
+
+    #[rstest]
+    #[case::insert(|words: &mut Collection| words.insert(json!({"word": "apple"})).map(|_| ()))]
+    #[case::replace(|words: &mut Collection| words.replace("house", json!({"word": "apple"})).map(|_| ()))]
+    #[case::update(|words: &mut Collection| words.update("house", json!({"word": "apple"})))]
+    fn duplicate_value_of_unique_index_is_rejected(
+        #[case] operation: fn(&mut Collection) -> Result<()>,
+        storage: TestStorage,
+    ) {
+        let mut database = database_with_words_in(&storage);
+        database.create_unique_index("words", "/word").unwrap();
+        let mut words = database.collection("words").unwrap();
+
+        let err = operation(&mut words).unwrap_err();
+        assert_eq!(CustomKind::UniqueViolation, *err.get_custom_kind().unwrap());
+        assert_eq!(
+            json!({"word": "house", "level": 2}),
+            *words.get("house").unwrap()
+        );
+        assert!(storage.wal().is_empty());
+    }
+
+    #[rstest]
+    fn unique_index_allows_documents_without_value_and_unchanged_values(
+        mut database_with_words: Database,
+    ) {
+        database_with_words
+            .create_unique_index("words", "/word")
+            .unwrap();
+        let mut words = database_with_words.collection("words").unwrap();
+
+        words.insert(json!({"level": 1})).unwrap();
+        words.insert(json!({"level": 1})).unwrap();
+        words.update("apple", json!({"level": 7})).unwrap();
+        words
+            .replace("house", json!({"word": "house", "level": 3}))
+            .unwrap();
+    }
+
+    #[rstest]
+    fn unique_index_is_not_created_when_values_are_duplicated(mut database_with_words: Database) {
+        database_with_words
+            .collection("words")
+            .unwrap()
+            .insert_with_key("tree", json!({"word": "tree", "level": 2}))
+            .unwrap();
+
+        let err = database_with_words
+            .create_unique_index("words", "/level")
+            .unwrap_err();
+        assert_eq!(CustomKind::UniqueViolation, *err.get_custom_kind().unwrap());
+        assert!(database_with_words
+            .list_indexes("words")
+            .unwrap()
+            .is_empty());
+    }
+
+    #[rstest]
+    fn values_of_unique_index_may_be_swapped_within_transaction(mut database_with_words: Database) {
+        database_with_words
+            .create_unique_index("words", "/word")
+            .unwrap();
+
+        let mut transaction = database_with_words.transaction();
+        transaction
+            .update("words", "apple", json!({"word": "house"}))
+            .unwrap();
+        transaction
+            .update("words", "house", json!({"word": "apple"}))
+            .unwrap();
+        transaction.commit().unwrap();
+
+        let words = database_with_words.collection("words").unwrap();
+        assert_eq!(json!("house"), words.get("apple").unwrap()["word"]);
+    }
+
+    #[rstest]
+    fn transaction_storing_duplicate_values_is_rejected(mut database_with_words: Database) {
+        database_with_words
+            .create_unique_index("words", "/word")
+            .unwrap();
+
+        let mut transaction = database_with_words.transaction();
+        transaction.delete("words", "apple").unwrap();
+        transaction
+            .insert("words", json!({"word": "tree"}))
+            .unwrap();
+        transaction
+            .insert("words", json!({"word": "tree"}))
+            .unwrap();
+        let err = transaction.commit().unwrap_err();
+        assert_eq!(CustomKind::UniqueViolation, *err.get_custom_kind().unwrap());
+
+        // A value of a deleted document may be reused
+        let mut transaction = database_with_words.transaction();
+        transaction.delete("words", "apple").unwrap();
+        transaction
+            .insert("words", json!({"word": "apple"}))
+            .unwrap();
+        transaction.commit().unwrap();
+    }
+
+    #[rstest]
+    fn unique_index_is_enforced_after_reopening_database(storage: TestStorage) {
+        let mut database = database_with_words_in(&storage);
+        database.create_unique_index("words", "/word").unwrap();
+        drop(database);
+
+        let mut database = reopen(&storage, OpenMode::ReadWrite);
+        let err = database
+            .collection("words")
+            .unwrap()
+            .insert(json!({"word": "house"}))
+            .unwrap_err();
+        assert_eq!(CustomKind::UniqueViolation, *err.get_custom_kind().unwrap());
+    }
+
+    #[rstest]
+    fn schema_is_set_and_stored_in_metadata_directory(storage: TestStorage) {
+        let mut database = database_with_words_in(&storage);
+        let schema = json!({"type": "object", "required": ["word"]});
+        database.set_schema("words", schema.clone()).unwrap();
+
+        assert_eq!(schema, storage.json(".metadata/schemas/words.json"));
+        assert_eq!(
+            Some(&schema),
+            database.collection("words").unwrap().schema()
+        );
+        assert!(database.collection("history").unwrap().schema().is_none());
+
+        // The schema is replaced
+        let schema = json!({"type": "object"});
+        database.set_schema("words", schema.clone()).unwrap();
+        assert_eq!(schema, storage.json(".metadata/schemas/words.json"));
+    }
+
+    #[rstest]
+    #[case::invalid_schema("words", json!({"type": "word"}), CustomKind::InvalidArgument)]
+    #[case::non_existing_collection("colors", json!({}), CustomKind::NotFound)]
+    #[case::existing_document_violates_schema(
+        "words",
+        json!({"properties": {"level": {"minimum": 2}}}),
+        CustomKind::Json
+    )]
+    fn schema_is_not_set_when_arguments_are_invalid(
+        #[case] collection: &str,
+        #[case] schema: JValue,
+        #[case] kind: CustomKind,
+        mut database_with_words: Database,
+    ) {
+        let err = database_with_words
+            .set_schema(collection, schema)
+            .unwrap_err();
+        assert_eq!(kind, *err.get_custom_kind().unwrap());
+        assert!(database_with_words
+            .collection("words")
+            .unwrap()
+            .schema()
+            .is_none());
+    }
+
+    #[rstest]
+    fn documents_violating_schema_are_rejected(mut database_with_words: Database) {
+        database_with_words
+            .set_schema(
+                "words",
+                json!({
+                    "type": "object",
+                    "required": ["word", "level"],
+                    "properties": {"level": {"type": "integer", "minimum": 1}}
+                }),
+            )
+            .unwrap();
+
+        let mut words = database_with_words.collection("words").unwrap();
+        let err = words.insert(json!({"word": "tree"})).unwrap_err();
+        assert_eq!(CustomKind::Json, *err.get_custom_kind().unwrap());
+        assert_eq!(
+            "Library error: Value at '/level' violates schema rule 'required': property is missing",
+            err.to_string()
+        );
+        let err = words.update("apple", json!({"level": 0})).unwrap_err();
+        assert!(err
+            .to_string()
+            .contains("Value at '/level' violates schema rule 'minimum'"));
+        assert!(words
+            .replace("house", json!({"word": "house", "level": "2"}))
+            .is_err());
+
+        words
+            .insert_with_key("tree", json!({"word": "tree", "level": 3}))
+            .unwrap();
+        assert_eq!(3, words.len());
+        assert_eq!(json!(1), words.get("apple").unwrap()["level"]);
+    }
+
+    #[rstest]
+    fn transaction_operation_violating_schema_is_rejected(storage: TestStorage) {
+        let mut database = database_with_words_in(&storage);
+        database
+            .set_schema("words", json!({"required": ["word"]}))
+            .unwrap();
+
+        let mut transaction = database.transaction();
+        transaction
+            .insert("words", json!({"word": "tree"}))
+            .unwrap();
+        let err = transaction
+            .insert("words", json!({"level": 3}))
+            .unwrap_err();
+        assert_eq!(CustomKind::Json, *err.get_custom_kind().unwrap());
+        transaction.commit().unwrap();
+
+        assert_eq!(3, database.collection("words").unwrap().len());
+    }
+
+    #[rstest]
+    fn schema_is_enforced_after_reopening_database(storage: TestStorage) {
+        let mut database = database_with_words_in(&storage);
+        database
+            .set_schema("words", json!({"required": ["word"]}))
+            .unwrap();
+        drop(database);
+
+        let mut database = reopen(&storage, OpenMode::ReadWrite);
+        let mut words = database.collection("words").unwrap();
+        assert!(words.schema().is_some());
+        assert!(words.insert(json!({"level": 3})).is_err());
+    }
+
+    #[rstest]
+    fn schema_is_removed(storage: TestStorage) {
+        let mut database = database_with_words_in(&storage);
+        database
+            .set_schema("words", json!({"required": ["word"]}))
+            .unwrap();
+        database.remove_schema("words").unwrap();
+
+        assert!(!storage.exists(Path::new(".metadata/schemas/words.json")));
+        let mut words = database.collection("words").unwrap();
+        assert!(words.schema().is_none());
+        words.insert(json!({"level": 3})).unwrap();
+
+        let err = database.remove_schema("words").unwrap_err();
+        assert_eq!(CustomKind::NotFound, *err.get_custom_kind().unwrap());
+    }
+
+    #[rstest]
+    fn schema_follows_collection_when_renamed_or_dropped(storage: TestStorage) {
+        let mut database = database_with_words_in(&storage);
+        database
+            .set_schema("words", json!({"required": ["word"]}))
+            .unwrap();
+
+        database.rename_collection("words", "vocab").unwrap();
+        assert!(!storage.exists(Path::new(".metadata/schemas/words.json")));
+        assert!(storage.exists(Path::new(".metadata/schemas/vocab.json")));
+        assert!(database.collection("vocab").unwrap().schema().is_some());
+
+        database.drop_collection("vocab").unwrap();
+        assert!(!storage.exists(Path::new(".metadata/schemas/vocab.json")));
+        database.create_collection("vocab").unwrap();
+        assert!(database.collection("vocab").unwrap().schema().is_none());
+    }
+
+    #[rstest]
+    #[case::cbor(Format::Cbor)]
+    #[case::message_pack(Format::MessagePack)]
+    #[case::pretty_json(Format::PrettyJson)]
+    fn collections_are_rewritten_in_selected_format(#[case] format: Format, storage: TestStorage) {
+        let mut database = database_with_words_in(&storage);
+        let json_len = storage.read(Path::new("words.json")).unwrap().len();
+        database.set_format(format).unwrap();
+        assert_eq!(format, database.format());
+        assert_eq!(
+            json!(format),
+            storage.json(".metadata/metadata.json")["format"]
+        );
+        let content = storage.read(Path::new("words.json")).unwrap();
+        assert_eq!(format == Format::PrettyJson, content.len() > json_len);
+        drop(database);
+
+        let mut database = reopen(&storage, OpenMode::ReadWrite);
+        assert_eq!(format, database.format());
+        let mut words = database.collection("words").unwrap();
+        assert_eq!(json!(2), words.get("house").unwrap()["level"]);
+        words
+            .insert_with_key("tree", json!({"word": "tree"}))
+            .unwrap();
+        database.create_collection("colors").unwrap();
+        database.checkpoint().unwrap();
+        drop(database);
+
+        let mut database = reopen(&storage, OpenMode::ReadOnly);
+        assert_eq!(3, database.collection("words").unwrap().len());
+        assert!(database.collection("colors").unwrap().is_empty());
+    }
+
+    #[rstest]
+    fn schema_is_stored_as_json_regardless_of_format(storage: TestStorage) {
+        let mut database = database_with_words_in(&storage);
+        database.set_format(Format::Cbor).unwrap();
+        let schema = json!({"required": ["word"]});
+        database.set_schema("words", schema.clone()).unwrap();
+        assert_eq!(schema, storage.json(".metadata/schemas/words.json"));
+    }
+
+    #[rstest]
+    fn format_is_not_changed_in_read_only_mode(storage: TestStorage) {
+        drop(database_with_words_in(&storage));
+
+        let mut database = reopen(&storage, OpenMode::ReadOnly);
+        let err = database.set_format(Format::MessagePack).unwrap_err();
+        assert_eq!(CustomKind::ReadOnly, *err.get_custom_kind().unwrap());
+        assert_eq!(Format::Json, database.format());
+    }
+
+    #[rstest]
+    #[case::gzip(Compression::Gzip, Format::Json)]
+    #[case::zstd(Compression::Zstd, Format::MessagePack)]
+    fn collections_are_rewritten_with_selected_compression(
+        #[case] compression: Compression,
+        #[case] format: Format,
+        storage: TestStorage,
+    ) {
+        let mut database = database_with_words_in(&storage);
+        database.set_format(format).unwrap();
+        database.set_compression(compression).unwrap();
+        assert_eq!(compression, database.compression());
+        assert_eq!(
+            json!(compression),
+            storage.json(".metadata/metadata.json")["compression"]
+        );
+        drop(database);
+
+        let mut database = reopen(&storage, OpenMode::ReadWrite);
+        assert_eq!(compression, database.compression());
+        let mut words = database.collection("words").unwrap();
+        assert_eq!(json!(2), words.get("house").unwrap()["level"]);
+        words
+            .insert_with_key("tree", json!({"word": "tree"}))
+            .unwrap();
+        database.checkpoint().unwrap();
+
+        // Collections are decompressed once compression is turned off
+        database.set_compression(Compression::None).unwrap();
+        database.set_format(Format::Json).unwrap();
+        assert_eq!(3, storage.json("words.json").as_object().unwrap().len());
+    }
+
+    #[rstest]
+    fn encrypted_database_keeps_no_plaintext(storage: TestStorage) {
+        let mut database =
+            Database::create_encrypted_with(DATABASE_NAME, Box::new(storage.clone()), "secret")
+                .unwrap();
+        assert!(database.is_encrypted());
+        database.create_collection("notes").unwrap();
+        database
+            .set_schema("notes", json!({"required": ["note"]}))
+            .unwrap();
+        database
+            .collection("notes")
+            .unwrap()
+            .insert_with_key("first", json!({"note": "Irregular verbs"}))
+            .unwrap();
+
+        // Mutations are still kept in the write-ahead log at this point
+        let paths = [".wal", "notes.json", ".metadata/metadata.json"];
+        for path in paths.iter().chain(&[".metadata/schemas/notes.json"]) {
+            let content = storage.read(Path::new(path)).unwrap();
+            assert!(serde_json::from_slice::<JValue>(&content).is_err());
+            for plaintext in [&b"Irregular verbs"[..], b"notes", b"note"] {
+                assert!(!content
+                    .windows(plaintext.len())
+                    .any(|window| window == plaintext));
+            }
+        }
+        drop(database);
+
+        let mut database =
+            Database::open_encrypted_with(Box::new(storage.clone()), OpenMode::ReadWrite, "secret")
+                .unwrap();
+        let mut notes = database.collection("notes").unwrap();
+        assert_eq!(
+            json!("Irregular verbs"),
+            notes.get("first").unwrap()["note"]
+        );
+        assert!(notes.insert(json!({"word": "go"})).is_err());
+
+        // Files of a renamed collection are encrypted again under their new paths
+        database.rename_collection("notes", "memos").unwrap();
+        drop(database);
+        let mut database =
+            Database::open_encrypted_with(Box::new(storage), OpenMode::ReadWrite, "secret")
+                .unwrap();
+        let mut memos = database.collection("memos").unwrap();
+        assert_eq!(
+            json!("Irregular verbs"),
+            memos.get("first").unwrap()["note"]
+        );
+        assert!(memos.insert(json!({"word": "go"})).is_err());
+    }
+
+    #[rstest]
+    fn encrypted_database_is_not_opened_without_passphrase(storage: TestStorage) {
+        drop(
+            Database::create_encrypted_with(DATABASE_NAME, Box::new(storage.clone()), "secret")
+                .unwrap(),
+        );
+
+        let err = Database::open_with(Box::new(storage.clone()), OpenMode::ReadOnly)
+            .err()
+            .unwrap();
+        assert_eq!(CustomKind::WrongKey, *err.get_custom_kind().unwrap());
+        let err =
+            Database::open_encrypted_with(Box::new(storage.clone()), OpenMode::ReadOnly, "guess")
+                .err()
+                .unwrap();
+        assert_eq!(CustomKind::WrongKey, *err.get_custom_kind().unwrap());
+        assert_eq!(
+            "Library error: Passphrase does not match the database",
+            err.to_string()
+        );
+
+        // Failed attempts do not keep the database locked
+        Database::open_encrypted_with(Box::new(storage), OpenMode::ReadWrite, "secret").unwrap();
+    }
+
+    #[rstest]
+    fn plain_database_is_not_opened_with_passphrase(storage: TestStorage) {
+        drop(database_in(&storage));
+
+        let err =
+            Database::open_encrypted_with(Box::new(storage.clone()), OpenMode::ReadOnly, "secret")
+                .err()
+                .unwrap();
+        assert_eq!(CustomKind::InvalidArgument, *err.get_custom_kind().unwrap());
+        assert!(!reopen(&storage, OpenMode::ReadOnly).is_encrypted());
+    }
+
+    #[rstest]
+    fn encrypted_database_is_created_in_directory() {
+        let temp_dir = TempDir::new("").unwrap();
+        let mut database =
+            Database::create_encrypted(DATABASE_NAME, temp_dir.path(), "secret").unwrap();
+        database.create_collection("notes").unwrap();
+        database.set_format(Format::Cbor).unwrap();
+        database.set_compression(Compression::Zstd).unwrap();
+        database
+            .collection("notes")
+            .unwrap()
+            .insert(json!({"note": "Phrasal verbs"}))
+            .unwrap();
+        drop(database);
+
+        let path = temp_dir.path().join(DATABASE_NAME);
+        let mut database = Database::open_encrypted(&path, OpenMode::ReadWrite, "secret").unwrap();
+        assert_eq!(Compression::Zstd, database.compression());
+        assert_eq!(1, database.collection("notes").unwrap().len());
+        drop(database);
+
+        temp_dir.close().unwrap();
+    }
+
+    #[rstest]
+    fn checksums_of_collection_files_are_recorded(storage: TestStorage) {
+        let mut database = database_with_words_in(&storage);
+        let metadata = storage.json(".metadata/metadata.json");
+        for name in ["words", "history"] {
+            let content = storage.read(Path::new(&format!("{}.json", name))).unwrap();
+            assert_eq!(
+                json!([Io::checksum(&content)]),
+                metadata["collections"][name]["checksums"]
+            );
+        }
+
+        database.set_compression(Compression::Gzip).unwrap();
+        let content = storage.read(Path::new("words.json")).unwrap();
+        assert_eq!(
+            json!([Io::checksum(&content)]),
+            storage.json(".metadata/metadata.json")["collections"]["words"]["checksums"]
+        );
+    }
+
+    #[rstest]
+    #[case::edited(|content: &mut Vec<u8>| content[2] = b'X')]
+    #[case::truncated(|content: &mut Vec<u8>| content.truncate(10))]
+    #[case::emptied(|content: &mut Vec<u8>| content.clear())]
+    fn altered_collection_file_is_reported_as_corrupted(
+        #[case] alter: fn(&mut Vec<u8>),
+        storage: TestStorage,
+    ) {
+        drop(database_with_words_in(&storage));
+        let mut content = storage.read(Path::new("words.json")).unwrap();
+        alter(&mut content);
+        storage.write(Path::new("words.json"), &content).unwrap();
+
+        let mut database = reopen(&storage, OpenMode::ReadOnly);
+        let err = database.collection("words").err().unwrap();
+        assert_eq!(CustomKind::Corrupted, *err.get_custom_kind().unwrap());
+        assert!(err.to_string().contains("words.json"));
+        assert!(database.collection("history").is_ok());
+    }
+
+    #[rstest]
+    fn interrupted_checkpoint_is_not_reported_as_corruption(storage: TestStorage) {
+        let mut database = database_with_words_in(&storage);
+        let old_content = storage.read(Path::new("words.json")).unwrap();
+        database
+            .collection("words")
+            .unwrap()
+            .insert(json!({"word": "tree"}))
+            .unwrap();
+        database.checkpoint().unwrap();
+
+        // Simulate a crash right after checksums of new files have been recorded
+        let new_content = storage.read(Path::new("words.json")).unwrap();
+        let mut metadata = storage.json(".metadata/metadata.json");
+        metadata["collections"]["words"]["checksums"] =
+            json!([Io::checksum(&old_content), Io::checksum(&new_content)]);
+        storage
+            .write(
+                Path::new(".metadata/metadata.json"),
+                &serde_json::to_vec(&metadata).unwrap(),
+            )
+            .unwrap();
+        storage
+            .write(Path::new("words.json"), &old_content)
+            .unwrap();
+        drop(database);
+
+        let mut database = reopen(&storage, OpenMode::ReadWrite);
+        assert_eq!(2, database.collection("words").unwrap().len());
+    }
+
+    #[rstest]
+    fn collection_without_checksums_is_not_verified(storage: TestStorage) {
+        drop(database_with_words_in(&storage));
+        let mut metadata = storage.json(".metadata/metadata.json");
+        metadata["collections"]["words"]
+            .as_object_mut()
+            .unwrap()
+            .remove("checksums");
+        storage
+            .write(
+                Path::new(".metadata/metadata.json"),
+                &serde_json::to_vec(&metadata).unwrap(),
+            )
+            .unwrap();
+        storage
+            .write(Path::new("words.json"), b"{\"pear\": {\"word\": \"pear\"}}")
+            .unwrap();
+
+        let mut database = reopen(&storage, OpenMode::ReadOnly);
+        assert!(database.collection("words").unwrap().get("pear").is_some());
+    }
+
+    // Damage a database kept in the storage in every way the check should notice
+    fn damage(storage: &TestStorage) {
+        let mut database = database_with_words_in(storage);
+        database.create_collection("notes").unwrap();
+        database
+            .set_schema("notes", json!({"type": "object"}))
+            .unwrap();
+        drop(database);
+
+        storage
+            .write(Path::new("words.json"), b"{\"apple\"")
+            .unwrap();
+        storage.remove(Path::new("history.json")).unwrap();
+        storage
+            .write(
+                Path::new("notes.json"),
+                b"{\"memo\": {\"text\": \"hello\"}}",
+            )
+            .unwrap();
+        storage
+            .write(Path::new("extra.json"), b"{\"pear\": {\"word\": \"pear\"}}")
+            .unwrap();
+        storage.write(Path::new(".extra.json.tmp"), b"{").unwrap();
+    }
+
+    #[rstest]
+    fn healthy_database_passes_check(storage: TestStorage) {
+        drop(database_with_words_in(&storage));
+
+        let report = Database::check_with(Box::new(storage.clone()), true).unwrap();
+        assert!(report.is_healthy());
+        assert!(report.quarantined.is_empty());
+        assert!(storage
+            .list()
+            .unwrap()
+            .iter()
+            .all(|path| !path.starts_with(".quarantine")));
+    }
+
+    #[rstest]
+    fn check_reports_issues_without_altering_database(storage: TestStorage) {
+        damage(&storage);
+        let files = storage.list().unwrap();
+
+        let report = Database::check_with(Box::new(storage.clone()), false).unwrap();
+        let kinds: Vec<JValue> = report
+            .issues
+            .iter()
+            .map(|issue| serde_json::to_value(issue).unwrap()["kind"].clone())
+            .collect();
+        assert_eq!(
+            vec![
+                json!("temp_file"),
+                json!("missing_file"),
+                json!("checksum_mismatch"),
+                json!("unparsable"),
+                json!("orphan_file"),
+            ],
+            kinds
+        );
+        assert_eq!(
+            Issue::TempFile {
+                path: PathBuf::from(".extra.json.tmp")
+            },
+            report.issues[0]
+        );
+        assert_eq!(
+            Issue::MissingFile {
+                collection: "history".to_string()
+            },
+            report.issues[1]
+        );
+        assert_eq!(
+            Issue::ChecksumMismatch {
+                collection: "notes".to_string()
+            },
+            report.issues[2]
+        );
+        assert!(matches!(
+            &report.issues[3],
+            Issue::Unparsable { path, .. } if path == Path::new("words.json")
+        ));
+        assert_eq!(
+            Issue::OrphanFile {
+                path: PathBuf::from("extra.json")
+            },
+            report.issues[4]
+        );
+        assert!(!report.repaired);
+        assert_eq!(files, storage.list().unwrap());
+    }
+
+    #[rstest]
+    fn repair_restores_consistency(storage: TestStorage) {
+        damage(&storage);
+
+        let report = Database::check_with(Box::new(storage.clone()), true).unwrap();
+        assert!(report.repaired);
+        assert_eq!(5, report.issues.len());
+        assert_eq!(1, report.quarantined.len());
+        let (original, quarantined) = &report.quarantined[0];
+        assert_eq!(Path::new("words.json"), original);
+        assert!(quarantined.starts_with(".quarantine"));
+        assert!(quarantined.ends_with("words.json"));
+        assert_eq!(b"{\"apple\"".to_vec(), storage.read(quarantined).unwrap());
+        assert!(!storage.exists(Path::new(".extra.json.tmp")));
+
+        assert!(Database::check_with(Box::new(storage.clone()), false)
+            .unwrap()
+            .is_healthy());
+        let mut database = reopen(&storage, OpenMode::ReadOnly);
+        assert_eq!(0, database.collection("words").unwrap().len());
+        assert_eq!(0, database.collection("history").unwrap().len());
+        let notes = database.collection("notes").unwrap();
+        assert_eq!(json!({"text": "hello"}), *notes.get("memo").unwrap());
+        assert!(notes.schema().is_some());
+        let extra = database.collection("extra").unwrap();
+        assert_eq!(json!({"word": "pear"}), *extra.get("pear").unwrap());
+    }
+
+    #[rstest]
+    fn repair_keeps_indexes_of_broken_collection(storage: TestStorage) {
+        let mut database = database_with_words_in(&storage);
+        database.create_unique_index("words", "/word").unwrap();
+        drop(database);
+        storage.write(Path::new("words.json"), b"[").unwrap();
+
+        Database::check_with(Box::new(storage.clone()), true).unwrap();
+        let metadata = storage.json(".metadata/metadata.json");
+        assert!(metadata["collections"]["words"]["indexes"]["/word"].is_object());
+    }
+
+    #[rstest]
+    fn repair_rebuilds_broken_metadata(storage: TestStorage) {
+        drop(database_with_words_in(&storage));
+        storage
+            .write(Path::new(".metadata/metadata.json"), b"{\"name\":")
+            .unwrap();
+
+        let report = Database::check_with(Box::new(storage.clone()), true).unwrap();
+        assert!(matches!(
+            &report.issues[0],
+            Issue::Unparsable { path, .. } if path == Path::new(".metadata/metadata.json")
+        ));
+        let (_, quarantined) = &report.quarantined[0];
+        assert_eq!(b"{\"name\":".to_vec(), storage.read(quarantined).unwrap());
+
+        let mut database = reopen(&storage, OpenMode::ReadOnly);
+        assert_eq!(2, database.collection("words").unwrap().len());
+        assert_eq!(0, database.collection("history").unwrap().len());
+    }
+
+    #[rstest]
+    fn repair_quarantines_broken_wal_and_schemas(storage: TestStorage) {
+        let mut database = database_with_words_in(&storage);
+        database
+            .set_schema("words", json!({"type": "object"}))
+            .unwrap();
+        drop(database);
+        storage.write(Path::new(".wal"), b"not a record\n").unwrap();
+        storage
+            .write(Path::new(".metadata/schemas/words.json"), b"{\"type\": 1}")
+            .unwrap();
+        storage
+            .write(Path::new(".metadata/schemas/gone.json"), b"{}")
+            .unwrap();
+
+        let report = Database::check_with(Box::new(storage.clone()), true).unwrap();
+        let originals: Vec<&Path> = report
+            .quarantined
+            .iter()
+            .map(|(original, _)| original.as_path())
+            .collect();
+        assert_eq!(
+            vec![
+                Path::new(".metadata/schemas/gone.json"),
+                Path::new(".metadata/schemas/words.json"),
+                Path::new(".wal"),
+            ],
+            originals
+        );
+
+        let mut database = reopen(&storage, OpenMode::ReadWrite);
+        assert_eq!(2, database.collection("words").unwrap().len());
+        assert!(database.collection("words").unwrap().schema().is_none());
+    }
+
+    #[rstest]
+    #[case::torn_record_only(b"[{\"op\":\"put\",\"coll".to_vec(), b"".to_vec())]
+    #[case::torn_record_after_complete_one(
+        b"[{\"op\":\"delete\",\"collection\":\"words\",\"key\":\"apple\"}]\n[{\"op\"".to_vec(),
+        b"[{\"op\":\"delete\",\"collection\":\"words\",\"key\":\"apple\"}]\n".to_vec()
+    )]
+    fn repair_cuts_off_torn_write_ahead_log_record(
+        #[case] content: Vec<u8>,
+        #[case] repaired: Vec<u8>,
+        storage: TestStorage,
+    ) {
+        drop(database_with_words_in(&storage));
+        storage.write(Path::new(".wal"), &content).unwrap();
+
+        let report = Database::check_with(Box::new(storage.clone()), false).unwrap();
+        assert_eq!(
+            vec![Issue::TornRecord {
+                path: PathBuf::from(".wal")
+            }],
+            report.issues
+        );
+        assert_eq!(content, storage.read(Path::new(".wal")).unwrap());
+
+        Database::check_with(Box::new(storage.clone()), true).unwrap();
+        assert_eq!(repaired, storage.read(Path::new(".wal")).unwrap());
+        assert!(Database::check_with(Box::new(storage.clone()), false)
+            .unwrap()
+            .is_healthy());
+    }
+
+    #[rstest]
+    fn repair_requires_exclusive_access() {
+        let temp_dir = TempDir::new("").unwrap();
+        let _database = Database::create(DATABASE_NAME, temp_dir.path()).unwrap();
+        let path = temp_dir.path().join(DATABASE_NAME);
+
+        let err = Database::check(&path, true).unwrap_err();
+        assert_eq!(CustomKind::Locked, *err.get_custom_kind().unwrap());
+        let err = Database::check(&path, false).unwrap_err();
+        assert_eq!(CustomKind::Locked, *err.get_custom_kind().unwrap());
+    }
+
+    #[rstest]
+    fn encrypted_database_is_checked(storage: TestStorage) {
+        let mut database =
+            Database::create_encrypted_with(DATABASE_NAME, Box::new(storage.clone()), "secret")
+                .unwrap();
+        database.create_collection("words").unwrap();
+        drop(database);
 
-        // Set up mock on Io::open which checks whether function arguments are valid
-        let ctx = Io::open_context();
-        ctx.expect()
-            .times(1)
-            .withf(move |path_arg: &&str| *path_arg == path)
-            .returning(|_path: &str| Ok((Io::new(), fake_metadata())));
+        assert!(
+            Database::check_encrypted_with(Box::new(storage.clone()), false, "secret")
+                .unwrap()
+                .is_healthy()
+        );
+        let err = Database::check_with(Box::new(storage.clone()), false).unwrap_err();
+        assert_eq!(CustomKind::WrongKey, *err.get_custom_kind().unwrap());
 
-        let database = Database::open(path).unwrap();
+        storage.write(Path::new("words.json"), b"{}").unwrap();
+        let report =
+            Database::check_encrypted_with(Box::new(storage.clone()), false, "secret").unwrap();
+        assert!(matches!(&report.issues[0], Issue::Unparsable { .. }));
 
-        assert_eq!(DATABASE_FAKE_NAME, database.metadata.name);
+        // Broken content is quarantined verbatim
+        let report =
+            Database::check_encrypted_with(Box::new(storage.clone()), true, "secret").unwrap();
+        let (_, quarantined) = &report.quarantined[0];
+        assert_eq!(b"{}".to_vec(), storage.read(quarantined).unwrap());
+        assert!(
+            Database::check_encrypted_with(Box::new(storage.clone()), false, "secret")
+                .unwrap()
+                .is_healthy()
+        );
     }
 }
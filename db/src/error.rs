@@ -11,6 +11,20 @@ pub enum CustomKind {
     DbIo,
     /// JSON error
     Json,
+    /// Binary encoding error
+    Encoding,
+    /// Requested item does not exist
+    NotFound,
+    /// Database is locked by another instance
+    Locked,
+    /// Database has been opened in read-only mode
+    ReadOnly,
+    /// Value violates a unique constraint
+    UniqueViolation,
+    /// Passphrase does not match an encrypted database
+    WrongKey,
+    /// Stored file does not match its checksum
+    Corrupted,
 }
 
 /// Library error structure.
@@ -0,0 +1,273 @@
+//! Secondary indexes.
+//!
+//! An index maps values denoted by a JSON pointer to keys of documents holding them. Indexes are
+//! defined in collection metadata and built in memory whenever a collection is loaded. Afterwards
+//! they are kept up to date on every mutation of the collection.
+//!
+//! Indexes do not decide whether a document matches a query. They only narrow down the set of
+//! documents a query has to be evaluated against.
+
+use crate::jutil::total_compare;
+use crate::query::{resolve, Predicate};
+use serde_json::{Map, Value as JValue};
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, BTreeSet};
+use std::ops::Bound;
+
+// An indexed value, ordered as described in `total_compare`
+#[derive(Debug, Clone)]
+struct IndexKey(JValue);
+
+impl PartialEq for IndexKey {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for IndexKey {}
+
+impl PartialOrd for IndexKey {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for IndexKey {
+    fn cmp(&self, other: &Self) -> Ordering {
+        total_compare(&self.0, &other.0)
+    }
+}
+
+/// An index built on values denoted by a single JSON pointer.
+///
+/// Documents which do not contain a value denoted by the pointer are not indexed. An index does
+/// not enforce uniqueness on its own, it is up to the caller to check whether a mutation would
+/// violate a unique index before the mutation is applied.
+#[derive(Debug, Clone)]
+pub struct Index {
+    pointer: String,
+    unique: bool,
+    entries: BTreeMap<IndexKey, BTreeSet<String>>,
+}
+
+impl Index {
+    /// Build an index of documents of a collection.
+    pub fn build(pointer: &str, unique: bool, documents: &Map<String, JValue>) -> Self {
+        let mut index = Self {
+            pointer: pointer.to_string(),
+            unique,
+            entries: BTreeMap::new(),
+        };
+        for (key, document) in documents {
+            index.insert(key, document);
+        }
+        index
+    }
+
+    /// Return the indexed pointer.
+    pub fn pointer(&self) -> &str {
+        &self.pointer
+    }
+
+    /// Check whether indexed values have to be unique.
+    pub const fn is_unique(&self) -> bool {
+        self.unique
+    }
+
+    /// Return the value a document is indexed by.
+    pub fn value_of<'a>(&self, document: &'a JValue) -> Option<&'a JValue> {
+        // Pointers are validated when an index is defined
+        resolve(document, &self.pointer).ok().flatten()
+    }
+
+    /// Return keys of documents indexed by a value.
+    pub fn keys_of(&self, value: &JValue) -> impl Iterator<Item = &str> {
+        self.entries
+            .get(&IndexKey(value.clone()))
+            .into_iter()
+            .flatten()
+            .map(String::as_str)
+    }
+
+    /// Return any value held by more than one document.
+    pub fn duplicate(&self) -> Option<&JValue> {
+        self.entries
+            .iter()
+            .find(|(_, keys)| keys.len() > 1)
+            .map(|(value, _)| &value.0)
+    }
+
+    /// Add a document stored under a key to the index.
+    pub fn insert(&mut self, key: &str, document: &JValue) {
+        if let Some(value) = self.value_of(document) {
+            self.entries
+                .entry(IndexKey(value.clone()))
+                .or_default()
+                .insert(key.to_string());
+        }
+    }
+
+    /// Remove a document stored under a key from the index.
+    pub fn remove(&mut self, key: &str, document: &JValue) {
+        let Some(value) = self.value_of(document) else {
+            return;
+        };
+        let value = IndexKey(value.clone());
+        if let Some(keys) = self.entries.get_mut(&value) {
+            keys.remove(key);
+            if keys.is_empty() {
+                self.entries.remove(&value);
+            }
+        }
+    }
+
+    /// Return keys of documents which may satisfy a predicate.
+    ///
+    /// None is returned if the predicate does not refer to the indexed pointer or the index cannot
+    /// serve it. Otherwise every document satisfying the predicate is guaranteed to be returned.
+    pub fn lookup(&self, predicate: &Predicate) -> Option<BTreeSet<&str>> {
+        if predicate.pointer() != self.pointer {
+            return None;
+        }
+
+        let (lower, upper) = match predicate {
+            Predicate::Eq(_, value) => {
+                let value = IndexKey(value.clone());
+                (Bound::Included(value.clone()), Bound::Included(value))
+            }
+            Predicate::Lt(_, bound) => (Bound::Unbounded, Bound::Excluded(IndexKey(bound.clone()))),
+            Predicate::Gt(_, bound) => (Bound::Excluded(IndexKey(bound.clone())), Bound::Unbounded),
+            Predicate::In(_, values) => {
+                let keys = values
+                    .iter()
+                    .filter_map(|value| self.entries.get(&IndexKey(value.clone())))
+                    .flatten()
+                    .map(String::as_str)
+                    .collect();
+                return Some(keys);
+            }
+            _ => return None,
+        };
+
+        let keys = self
+            .entries
+            .range((lower, upper))
+            .flat_map(|(_, keys)| keys)
+            .map(String::as_str)
+            .collect();
+        Some(keys)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::*;
+    use serde_json::json;
+
+    /* ------------------ */
+    /* ---- Fixtures ---- */
+    /* ------------------ */
+
+    #[fixture]
+    fn documents() -> Map<String, JValue> {
+        json!({
+            "apple": {"word": "apple", "level": 3},
+            "house": {"word": "house", "level": 1},
+            "tree": {"word": "tree", "level": 2},
+            "sun": {"word": "sun", "level": "unknown"},
+            "moon": {"word": "moon"}
+        })
+        .as_object()
+        .unwrap()
+        .clone()
+    }
+
+    #[fixture]
+    fn index(documents: Map<String, JValue>) -> Index {
+        Index::build("/level", false, &documents)
+    }
+
+    /* -------------------------- */
+    /* ---- Test definitions ---- */
+    /* -------------------------- */
+
+    #[rstest]
+    #[case::eq(Predicate::Eq("/level".to_string(), json!(2)), vec!["tree"])]
+    #[case::lt(Predicate::Lt("/level".to_string(), json!(3)), vec!["house", "tree"])]
+    #[case::gt(Predicate::Gt("/level".to_string(), json!(1)), vec!["apple", "sun", "tree"])]
+    #[case::is_in(
+        Predicate::In("/level".to_string(), vec![json!(1), json!("unknown"), json!(7)]),
+        vec!["house", "sun"]
+    )]
+    fn documents_are_looked_up(
+        #[case] predicate: Predicate,
+        #[case] expected: Vec<&str>,
+        index: Index,
+    ) {
+        let expected: BTreeSet<&str> = expected.into_iter().collect();
+        assert_eq!(Some(expected), index.lookup(&predicate));
+    }
+
+    #[rstest]
+    #[case::other_pointer(Predicate::Eq("/word".to_string(), json!("tree")))]
+    #[case::unsupported_predicate(Predicate::Exists("/level".to_string()))]
+    fn index_does_not_serve_unrelated_predicates(#[case] predicate: Predicate, index: Index) {
+        assert!(index.lookup(&predicate).is_none());
+    }
+
+    #[rstest]
+    fn index_follows_document_changes(mut index: Index) {
+        let eq = |level| Predicate::Eq("/level".to_string(), json!(level));
+
+        index.remove("tree", &json!({"word": "tree", "level": 2}));
+        assert!(index.lookup(&eq(2)).unwrap().is_empty());
+        index.insert("moon", &json!({"word": "moon", "level": 1}));
+        assert_eq!(
+            BTreeSet::from(["house", "moon"]),
+            index.lookup(&eq(1)).unwrap()
+        );
+    }
+
+    #[rstest]
+    fn values_are_indexed_at_nested_pointers() {
+        let documents = json!({
+            "apple": {"tags": ["fruit", "red"]},
+            "tree": {"tags": ["plant"]},
+            "sun": {}
+        });
+        let index = Index::build("/tags/0", false, documents.as_object().unwrap());
+
+        let predicate = Predicate::Eq("/tags/0".to_string(), json!("fruit"));
+        assert_eq!(BTreeSet::from(["apple"]), index.lookup(&predicate).unwrap());
+    }
+
+    #[rstest]
+    fn duplicate_value_is_found(mut index: Index) {
+        assert!(index.duplicate().is_none());
+
+        index.insert("moon", &json!({"word": "moon", "level": 1.0}));
+        assert_eq!(Some(&json!(1)), index.duplicate());
+        assert_eq!(
+            vec!["house", "moon"],
+            index.keys_of(&json!(1)).collect::<Vec<_>>()
+        );
+    }
+
+    #[rstest]
+    fn large_integers_are_distinct_values() {
+        let documents = json!({
+            "first": {"id": 9_007_199_254_740_992_u64},
+            "second": {"id": 9_007_199_254_740_993_u64},
+            "third": {"id": 18_446_744_073_709_551_615_u64}
+        });
+        let index = Index::build("/id", true, documents.as_object().unwrap());
+        assert!(index.duplicate().is_none());
+
+        let predicate = Predicate::Gt("/id".to_string(), json!(9_007_199_254_740_992_u64));
+        assert_eq!(
+            BTreeSet::from(["second", "third"]),
+            index.lookup(&predicate).unwrap()
+        );
+    }
+}
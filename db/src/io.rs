@@ -1,46 +1,125 @@
 //! Database filesystem abstraction layer.
 //!
 //! The internal structure of a database is hidden to an end-user. Thereby this module acts as
-//! a middleware between database instance and OS filesystem.
-
-#![cfg_attr(test, allow(dead_code))]
-#[cfg(test)]
-use mockall::automock;
+//! a middleware between database instance and its storage. Files are kept by a storage backend
+//! (see [`crate::storage`]), which is usually a directory of the OS filesystem.
+//!
+//! Objects may be written in one of several formats (see [`Format`]) and optionally compressed
+//! (see [`Compression`]). Both the format and the compression of a file are recognized
+//! automatically when the file is read back.
+//!
+//! A database may be encrypted with a passphrase. In such a case every file except the lock file
+//! and a small header holding key derivation parameters is encrypted before it reaches storage.
 
+use crate::crypto::{Cipher, Header};
 use crate::error::{CustomKind, Error, Result};
 use crate::metadata::Database as DbMeta;
+use crate::storage::{FileStorage, Storage};
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
 use serde::de::DeserializeOwned;
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 use std::ffi::OsStr;
-use std::fs;
-use std::fs::File;
-use std::io::BufReader;
-use std::path::{Path, PathBuf};
+use std::fmt::{Debug, Display};
+use std::io::{Read, Write};
+use std::path::{Component, Path, PathBuf};
 
 /// A structure representing a filesystem abstraction layer.
+///
+/// Every instance holds a lock of a database for its whole lifetime. The lock is either shared
+/// or exclusive depending on the mode a database has been opened in (see [`OpenMode`]).
 #[non_exhaustive]
 #[derive(Debug)]
 pub struct Io {
-    path: PathBuf,
+    storage: Box<dyn Storage>,
+    // Cipher of an encrypted database
+    cipher: Option<Cipher>,
+}
+
+/// Possible modes of opening a database.
+///
+/// Any number of processes may open a database in read-only mode at the same time. A database
+/// opened in read-write mode is held exclusively by a single instance, though.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum OpenMode {
+    /// Read-only mode, backed by a shared lock
+    ReadOnly,
+    /// Read-write mode, backed by an exclusive lock
+    ReadWrite,
+}
+
+/// Possible on-disk encodings of serialized objects.
+///
+/// JSON files are human readable, whereas binary encodings are usually several times smaller
+/// and faster to decode. Files written in any of the formats may be read back by
+/// [`Io::deserialize`] which recognizes the format on its own.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Format {
+    /// Compact JSON
+    #[default]
+    Json,
+    /// JSON which retains formatting, thus providing better readability at a cost of file size
+    PrettyJson,
+    /// Concise Binary Object Representation (RFC 8949)
+    Cbor,
+    /// `MessagePack` binary encoding
+    MessagePack,
+}
+
+/// Possible compression codecs of serialized objects.
+///
+/// Compression is applied on top of any [`Format`]. It pays off especially for large files holding
+/// repetitive text. Compressed files are recognized by [`Io::deserialize`] on its own.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Compression {
+    /// No compression
+    #[default]
+    None,
+    /// Gzip (RFC 1952), widely supported by external tools
+    Gzip,
+    /// Zstandard (RFC 8878), usually both faster and more effective than gzip
+    Zstd,
 }
 
 // Possible file open modes when dealing with files
 #[derive(Copy, Clone)]
 enum FileOpenMode {
+    // Open an existing file
     Open,
+    // Write a file which does not have to exist
     Write,
-    WriteCreate,
 }
 
-#[cfg_attr(test, automock)]
+// Return an error of a failed binary encoding, decoding or decompression
+fn encoding_err(action: &str, codec: &impl Debug, err: &impl Display) -> Error {
+    Error::custom_err(
+        CustomKind::Encoding,
+        &format!("Cannot {} {:?}: {}", action, codec, err),
+    )
+}
+
+/// Check whether a name may be used as a database or collection name.
+///
+/// Only alphanumeric characters and underscore are supported at the moment.
+pub(crate) fn is_name_valid(filename: &str) -> bool {
+    !filename.is_empty() && filename.chars().all(|ch| ch.is_alphanumeric() || ch == '_')
+}
+
 impl Io {
     const METADATA_DIR: &'static str = ".metadata";
     const METADATA_FILE: &'static str = "metadata.json";
-
-    fn is_name_valid(filename: &str) -> bool {
-        // Only alphanumeric characters + underscore supported at the moment
-        !filename.is_empty() && filename.chars().all(|ch| ch.is_alphanumeric() || ch == '_')
-    }
+    const LOCK_FILE: &'static str = "lock";
+    const ENCRYPTION_FILE: &'static str = "encryption.json";
+    // CBOR files start with the self-described CBOR tag which tells them apart from other formats
+    const CBOR_MAGIC: [u8; 3] = [0xd9, 0xd9, 0xf7];
+    // Magic numbers of compressed content
+    const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+    const ZSTD_MAGIC: [u8; 4] = [0x28, 0xb5, 0x2f, 0xfd];
+    // Bytes a JSON value may start with. Binary formats are not expected to start with any of
+    // them as long as objects or arrays are stored
+    const JSON_START: &'static [u8] = b"{[\"-0123456789tfn";
 
     /// Create a database filesystem structure.
     ///
@@ -50,19 +129,66 @@ impl Io {
     /// Currently metadata's `name` has restrictions and can only contain
     /// alphanumeric characters. An exception to this rule is underscore character.
     ///
+    /// The database is locked exclusively as if it was opened in [`OpenMode::ReadWrite`] mode.
+    ///
     /// # Errors
     /// The function may return either an OS specific error in case system call has failed
     /// or a custom library error.
     pub fn create<P>(path: P, db_meta: &DbMeta) -> Result<Self>
     where
-        P: AsRef<OsStr> + 'static,
+        P: AsRef<OsStr>,
     {
-        if !Self::is_name_valid(&db_meta.name) {
-            return Err(Error::custom_err(
-                CustomKind::InvalidArgument,
-                "Database name contains forbidden characters",
-            ));
-        }
+        Self::create_with(Self::new_storage(path, db_meta)?, db_meta)
+    }
+
+    /// Create an encrypted database filesystem structure.
+    ///
+    /// The function is a counterpart of [`Io::create`] which encrypts every file of the database
+    /// with a key derived from `passphrase`. The database may be opened afterwards only with
+    /// [`Io::open_encrypted`] given the same passphrase.
+    ///
+    /// # Errors
+    /// The function may return either an OS specific error in case system call has failed
+    /// or a custom library error.
+    pub fn create_encrypted<P>(path: P, db_meta: &DbMeta, passphrase: &str) -> Result<Self>
+    where
+        P: AsRef<OsStr>,
+    {
+        Self::create_encrypted_with(Self::new_storage(path, db_meta)?, db_meta, passphrase)
+    }
+
+    /// Create a database structure inside a given storage.
+    ///
+    /// The function is a counterpart of [`Io::create`] which allows any storage backend to be used.
+    /// The storage is expected to be empty.
+    ///
+    /// # Errors
+    /// The function may return either a storage specific error or a custom library error.
+    pub fn create_with(storage: Box<dyn Storage>, db_meta: &DbMeta) -> Result<Self> {
+        Self::init(storage, db_meta, None)
+    }
+
+    /// Create an encrypted database structure inside a given storage.
+    ///
+    /// The function is a counterpart of [`Io::create_encrypted`] which allows any storage backend
+    /// to be used. The storage is expected to be empty.
+    ///
+    /// # Errors
+    /// The function may return either a storage specific error or a custom library error.
+    pub fn create_encrypted_with(
+        storage: Box<dyn Storage>,
+        db_meta: &DbMeta,
+        passphrase: &str,
+    ) -> Result<Self> {
+        Self::init(storage, db_meta, Some(passphrase))
+    }
+
+    // Return a storage for a database which is about to be created inside a directory
+    fn new_storage<P>(path: P, db_meta: &DbMeta) -> Result<Box<dyn Storage>>
+    where
+        P: AsRef<OsStr>,
+    {
+        Self::validate_name(db_meta)?;
 
         // Check if a directory already exist
         let database_path = Path::new(&path).canonicalize()?.join(&db_meta.name);
@@ -73,14 +199,37 @@ impl Io {
             ));
         }
 
-        // Serialize metadata structure before returning IO object
-        let metadata_file_path = database_path
-            .join(Self::METADATA_DIR)
-            .join(Self::METADATA_FILE);
-        let io = Self {
-            path: database_path,
+        Ok(Box::new(FileStorage::new(database_path)))
+    }
+
+    // Create a database structure inside a storage, encrypted if a passphrase is given
+    fn init(storage: Box<dyn Storage>, db_meta: &DbMeta, passphrase: Option<&str>) -> Result<Self> {
+        Self::validate_name(db_meta)?;
+
+        let mut io = Self {
+            storage,
+            cipher: None,
         };
-        io.serialize_new(db_meta, metadata_file_path, true)?;
+        // The header is the only file which is never encrypted
+        if let Some(passphrase) = passphrase {
+            let (cipher, header) = Cipher::create(passphrase)?;
+            io.serialize_new(
+                &header,
+                Self::encryption_path(),
+                Format::PrettyJson,
+                Compression::None,
+            )?;
+            io.cipher = Some(cipher);
+        }
+
+        // Serialize metadata structure before returning IO object
+        io.serialize_new(
+            db_meta,
+            Self::metadata_path(),
+            Format::PrettyJson,
+            Compression::None,
+        )?;
+        io.storage.lock(&Self::lock_path(), OpenMode::ReadWrite)?;
         Ok(io)
     }
 
@@ -88,119 +237,381 @@ impl Io {
     ///
     /// This function may be called only after a specified database has been already created.
     ///
+    /// The database is locked according to `mode` before its metadata is read. The lock is released
+    /// once the returned instance is dropped.
+    ///
     /// # Errors
     /// The function may return a custom library error in case a database specified by `path`
-    /// does not exists or has corrupted internal structure.
-    pub fn open<P>(path: P) -> Result<(Self, DbMeta)>
+    /// does not exists or has corrupted internal structure. [`CustomKind::Locked`] error is
+    /// returned if the database is locked by another instance in a conflicting mode.
+    pub fn open<P>(path: P, mode: OpenMode) -> Result<(Self, DbMeta)>
+    where
+        P: AsRef<OsStr>,
+    {
+        Self::open_with(Self::existing_storage(path)?, mode)
+    }
+
+    /// Open an existing encrypted database filesystem structure.
+    ///
+    /// The function is a counterpart of [`Io::open`] for databases created by
+    /// [`Io::create_encrypted`]. The key is derived from `passphrase` once the database is locked.
+    ///
+    /// # Errors
+    /// The function returns [`CustomKind::WrongKey`] error if the passphrase does not match the
+    /// database and [`CustomKind::InvalidArgument`] error if the database is not encrypted.
+    /// Otherwise it fails in the same cases as [`Io::open`].
+    pub fn open_encrypted<P>(path: P, mode: OpenMode, passphrase: &str) -> Result<(Self, DbMeta)>
+    where
+        P: AsRef<OsStr>,
+    {
+        Self::open_encrypted_with(Self::existing_storage(path)?, mode, passphrase)
+    }
+
+    /// Open an existing database structure kept by a given storage.
+    ///
+    /// The function is a counterpart of [`Io::open`] which allows any storage backend to be used.
+    ///
+    /// # Errors
+    /// The function may return a custom library error in case the storage does not hold a valid
+    /// database structure. [`CustomKind::Locked`] error is returned if the database is locked by
+    /// another instance in a conflicting mode.
+    pub fn open_with(storage: Box<dyn Storage>, mode: OpenMode) -> Result<(Self, DbMeta)> {
+        Self::load(storage, mode, None)
+    }
+
+    /// Open an existing encrypted database structure kept by a given storage.
+    ///
+    /// The function is a counterpart of [`Io::open_encrypted`] which allows any storage backend
+    /// to be used.
+    ///
+    /// # Errors
+    /// The function returns [`CustomKind::WrongKey`] error if the passphrase does not match the
+    /// database and [`CustomKind::InvalidArgument`] error if the database is not encrypted.
+    /// Otherwise it fails in the same cases as [`Io::open_with`].
+    pub fn open_encrypted_with(
+        storage: Box<dyn Storage>,
+        mode: OpenMode,
+        passphrase: &str,
+    ) -> Result<(Self, DbMeta)> {
+        Self::load(storage, mode, Some(passphrase))
+    }
+
+    /// Open an existing database filesystem structure without reading its metadata.
+    ///
+    /// The function is a counterpart of [`Io::attach_with`] for databases kept in a directory.
+    ///
+    /// # Errors
+    /// The function fails in the same cases as [`Io::attach_with`] and additionally if the
+    /// directory does not exist.
+    pub fn attach<P>(path: P, mode: OpenMode, passphrase: Option<&str>) -> Result<Self>
+    where
+        P: AsRef<OsStr>,
+    {
+        Self::attach_with(Self::existing_storage(path)?, mode, passphrase)
+    }
+
+    /// Open an existing database structure kept by a given storage without reading its metadata.
+    ///
+    /// The function is meant for tools inspecting a database which may be damaged, hence it does
+    /// not require the metadata file to be readable. The file has to exist, though. The database
+    /// is decrypted if `passphrase` is given.
+    ///
+    /// # Errors
+    /// The function returns a custom library error in case the storage does not hold a database
+    /// structure. [`CustomKind::Locked`] error is returned if the database is locked by another
+    /// instance in a conflicting mode. [`CustomKind::WrongKey`] error is returned if the
+    /// passphrase does not match the database or none is given to an encrypted database.
+    pub fn attach_with(
+        storage: Box<dyn Storage>,
+        mode: OpenMode,
+        passphrase: Option<&str>,
+    ) -> Result<Self> {
+        let mut io = Self {
+            storage,
+            cipher: None,
+        };
+        // Make sure the database has a valid structure before a lock file is created inside
+        io.resolve_path(Self::metadata_path(), FileOpenMode::Open)?;
+        io.storage.lock(&Self::lock_path(), mode)?;
+
+        match (passphrase, io.exists(Self::encryption_path())) {
+            (Some(passphrase), true) => {
+                let header: Header = io.deserialize(Self::encryption_path())?;
+                io.cipher = Some(Cipher::open(passphrase, &header)?);
+            }
+            (None, true) => {
+                return Err(Error::custom_err(
+                    CustomKind::WrongKey,
+                    "Database is encrypted, a passphrase is required to open it",
+                ))
+            }
+            (Some(_), false) => {
+                return Err(Error::custom_err(
+                    CustomKind::InvalidArgument,
+                    "Database is not encrypted",
+                ))
+            }
+            (None, false) => {}
+        }
+        Ok(io)
+    }
+
+    /// Check whether the database is encrypted.
+    #[must_use]
+    pub fn is_encrypted(&self) -> bool {
+        self.cipher.is_some()
+    }
+
+    /// Return the root path of the database, as reported by its storage.
+    #[must_use]
+    pub fn root(&self) -> &Path {
+        self.storage.root()
+    }
+
+    // Return a storage of an existing database kept inside a directory
+    fn existing_storage<P>(path: P) -> Result<Box<dyn Storage>>
     where
-        P: AsRef<OsStr> + 'static,
+        P: AsRef<OsStr>,
     {
-        let canonicalized_path_res = Path::new(&path).canonicalize();
         // Path::canonicalize returns an error in case specified directory does not exist.
         // Capture any IO error and generate custom one instead
-        if canonicalized_path_res.is_err() {
+        let Ok(canonicalized_path) = Path::new(&path).canonicalize() else {
             return Err(Error::custom_err(
                 CustomKind::DbIo,
                 "Database does not exist",
             ));
-        }
-        let canonicalized_path = canonicalized_path_res?;
-
-        let metadata_file_path = canonicalized_path
-            .join(Self::METADATA_DIR)
-            .join(Self::METADATA_FILE);
-        let io = Self {
-            path: canonicalized_path,
         };
-        let metadata = io.deserialize(metadata_file_path)?;
+
+        Ok(Box::new(FileStorage::new(canonicalized_path)))
+    }
+
+    // Open a database structure kept by a storage, decrypting it if a passphrase is given
+    fn load(
+        storage: Box<dyn Storage>,
+        mode: OpenMode,
+        passphrase: Option<&str>,
+    ) -> Result<(Self, DbMeta)> {
+        let io = Self::attach_with(storage, mode, passphrase)?;
+        let metadata = io.deserialize(Self::metadata_path())?;
         Ok((io, metadata))
     }
 
-    // Open a file creating it optionally if needed
-    fn open_file<P>(&self, path: P, mode: FileOpenMode) -> Result<File>
+    // Return an error if a database name is invalid
+    fn validate_name(db_meta: &DbMeta) -> Result<()> {
+        if is_name_valid(&db_meta.name) {
+            Ok(())
+        } else {
+            Err(Error::custom_err(
+                CustomKind::InvalidArgument,
+                "Database name contains forbidden characters",
+            ))
+        }
+    }
+
+    // Return path to the metadata file, relative to a database's base directory
+    pub(crate) fn metadata_path() -> PathBuf {
+        Path::new(Self::METADATA_DIR).join(Self::METADATA_FILE)
+    }
+
+    // Return path to the encryption header, relative to a database's base directory
+    fn encryption_path() -> PathBuf {
+        Path::new(Self::METADATA_DIR).join(Self::ENCRYPTION_FILE)
+    }
+
+    // Return path to the lock file, relative to a database's base directory
+    fn lock_path() -> PathBuf {
+        Path::new(Self::METADATA_DIR).join(Self::LOCK_FILE)
+    }
+
+    // Resolve a path into a path relative to a database's base directory, as expected by storage
+    fn resolve_path<P>(&self, path: P, mode: FileOpenMode) -> Result<PathBuf>
     where
-        P: AsRef<Path> + 'static,
+        P: AsRef<Path>,
     {
-        let file_path = self.path.join(&path);
-
-        // Create directory structure in case file creation has been requested
-        if matches!(mode, FileOpenMode::WriteCreate) {
-            // Obtain directories leading to the file. At least one parent directory is always
-            // expected since the path is relative to a database's base directory
-            let dirs = Path::new(&file_path).parent().unwrap();
-            fs::create_dir_all(dirs)?;
-        }
+        let root = self.storage.root();
+        let file_path = root.join(&path);
+
+        // Absolute paths are accepted as long as they lead into a database's base directory.
+        // Anything else would allow to touch files which do not belong to the database. Joined
+        // paths are not normalized, hence components which could escape the directory are
+        // rejected upfront
+        let escapes = path
+            .as_ref()
+            .components()
+            .any(|component| matches!(component, Component::ParentDir | Component::Prefix(_)));
+        let relative_path = match file_path.strip_prefix(root) {
+            Ok(relative_path) if !escapes => relative_path,
+            _ => {
+                return Err(Error::custom_err(
+                    CustomKind::InvalidArgument,
+                    &format!(
+                        "Path lies outside of the database directory: {}",
+                        file_path.display()
+                    ),
+                ));
+            }
+        };
 
-        // Check if a path exists and is not a directory when open/write mode is selected.
-        // This might be handled by `open` method below but the error would not contain
-        // problematic path details. Debugging is much easier this way
-        if !matches!(mode, FileOpenMode::WriteCreate) && (!file_path.exists() || file_path.is_dir())
-        {
+        // Check if a path points to an existing file when open mode is selected.
+        // Storage would fail anyway but the error would not contain problematic path details.
+        // Debugging is much easier this way
+        if matches!(mode, FileOpenMode::Open) && !self.storage.exists(relative_path) {
             return Err(Error::custom_err(
                 CustomKind::InvalidArgument,
                 &format!(
-                    "Cannot serialize an object into invalid path: {}",
+                    "Path does not point to an existing file: {}",
                     file_path.display()
                 ),
             ));
         }
 
-        // Set file options depending upon input mode
-        let mut open_options = fs::OpenOptions::new();
-        match mode {
-            FileOpenMode::Open => {
-                open_options.read(true);
-            }
-            FileOpenMode::Write => {
-                open_options.write(true);
-                open_options.truncate(true);
-            }
-            FileOpenMode::WriteCreate => {
-                open_options.create_new(true);
-                open_options.write(true);
-            }
-        }
+        Ok(relative_path.to_path_buf())
+    }
 
-        // Automatic result conversion cannot be handled - must be done manually
-        let file_result = open_options.open(file_path);
-        match file_result {
-            Ok(file) => Ok(file),
-            Err(err) => Err(Error::Io(err)),
+    // Serialize a serializable object in a given format
+    fn encode<S>(object: &S, format: Format) -> Result<Vec<u8>>
+    where
+        S: Serialize,
+    {
+        match format {
+            Format::Json => Ok(serde_json::to_vec(object)?),
+            Format::PrettyJson => Ok(serde_json::to_vec_pretty(object)?),
+            Format::Cbor => {
+                let mut bytes = Self::CBOR_MAGIC.to_vec();
+                ciborium::ser::into_writer(object, &mut bytes)
+                    .map_err(|err| encoding_err("encode an object as", &format, &err))?;
+                Ok(bytes)
+            }
+            Format::MessagePack => rmp_serde::to_vec_named(object)
+                .map_err(|err| encoding_err("encode an object as", &format, &err)),
         }
     }
 
-    // Serialize a serializable object into a file
-    fn do_serialize<S>(object: &S, file: File, pretty: bool) -> Result<()>
+    // Deserialize an object from content written in any of the supported formats
+    fn decode<S>(content: &[u8]) -> Result<S>
     where
-        S: Serialize + 'static,
+        S: DeserializeOwned,
     {
-        if pretty {
-            serde_json::to_writer_pretty(file, &object)?;
+        let format = Self::detect_format(content);
+        match format {
+            Format::Json | Format::PrettyJson => Ok(serde_json::from_slice(content)?),
+            Format::Cbor => ciborium::de::from_reader(&content[Self::CBOR_MAGIC.len()..])
+                .map_err(|err| encoding_err("decode an object from", &format, &err)),
+            Format::MessagePack => rmp_serde::from_slice(content)
+                .map_err(|err| encoding_err("decode an object from", &format, &err)),
+        }
+    }
+
+    // Return data authenticated together with encrypted content of a file, which binds the
+    // content to the path of the file. Components are joined by a slash on every platform
+    fn associated_data(file_path: &Path) -> Vec<u8> {
+        file_path
+            .components()
+            .map(|component| component.as_os_str().to_string_lossy())
+            .collect::<Vec<_>>()
+            .join("/")
+            .into_bytes()
+    }
+
+    // Encrypt content of a file if the database is encrypted
+    fn seal(&self, bytes: Vec<u8>, file_path: &Path) -> Result<Vec<u8>> {
+        match &self.cipher {
+            Some(cipher) => cipher.seal(&bytes, &Self::associated_data(file_path)),
+            None => Ok(bytes),
+        }
+    }
+
+    // Decrypt content of a file if the database is encrypted
+    fn unseal(&self, content: Vec<u8>, file_path: &Path) -> Result<Vec<u8>> {
+        match &self.cipher {
+            Some(cipher) => cipher.unseal(&content, &Self::associated_data(file_path)),
+            None => Ok(content),
+        }
+    }
+
+    // Compress serialized content with a given codec
+    fn compress(bytes: Vec<u8>, compression: Compression) -> Result<Vec<u8>> {
+        match compression {
+            Compression::None => Ok(bytes),
+            Compression::Gzip => {
+                let mut encoder = GzEncoder::new(Vec::new(), flate2::Compression::default());
+                encoder.write_all(&bytes)?;
+                Ok(encoder.finish()?)
+            }
+            Compression::Zstd => Ok(zstd::encode_all(bytes.as_slice(), 0)?),
+        }
+    }
+
+    // Decompress content if it starts with a magic number of any supported codec
+    fn decompress(content: Vec<u8>) -> Result<Vec<u8>> {
+        let compression = Self::detect_compression(&content);
+        let result = match compression {
+            Compression::None => return Ok(content),
+            Compression::Gzip => {
+                let mut bytes = Vec::new();
+                GzDecoder::new(content.as_slice())
+                    .read_to_end(&mut bytes)
+                    .map(|_| bytes)
+            }
+            Compression::Zstd => zstd::decode_all(content.as_slice()),
+        };
+        result.map_err(|err| encoding_err("decompress content with", &compression, &err))
+    }
+
+    // Recognize the codec serialized content has been compressed with
+    fn detect_compression(content: &[u8]) -> Compression {
+        if content.starts_with(&Self::GZIP_MAGIC) {
+            Compression::Gzip
+        } else if content.starts_with(&Self::ZSTD_MAGIC) {
+            Compression::Zstd
         } else {
-            serde_json::to_writer(file, &object)?;
+            Compression::None
         }
+    }
 
-        Ok(())
+    // Recognize the format of serialized content. Pretty JSON is reported as compact one, since
+    // both are decoded the same way. Empty content is assumed to be JSON
+    fn detect_format(content: &[u8]) -> Format {
+        if content.starts_with(&Self::CBOR_MAGIC) {
+            return Format::Cbor;
+        }
+        match content.iter().find(|byte| !byte.is_ascii_whitespace()) {
+            Some(byte) if !Self::JSON_START.contains(byte) => Format::MessagePack,
+            _ => Format::Json,
+        }
     }
 
-    /// Serialize an object into a file truncating old content.
+    /// Serialize an object into a file replacing old content.
     ///
     /// The path is relative to a database's base path and has to end with a file which has been
     /// created prior to call to this function. If an output file has not been created yet, then
     /// [`Io::serialize_new`] should be used.
     ///
-    /// Depending on `pretty` flag the output may be a pretty JSON which retain formatting, thus
-    /// providing better readability but the output file may be significantly larger.
+    /// The object is written in a given `format`. Pretty JSON retains formatting, thus providing
+    /// better readability but the output file may be significantly larger. Binary formats produce
+    /// the smallest files.
+    ///
+    /// The serialized object is compressed afterwards according to `compression`. Content of
+    /// an encrypted database is encrypted as the last step.
+    ///
+    /// The file is replaced atomically, thus the original content is retained if serialization
+    /// fails or the system crashes in the middle of writing.
     ///
     /// # Errors
     /// The function may return an IO, serde or a custom library error.
-    pub fn serialize<S, P>(&self, object: &S, path: P, pretty: bool) -> Result<()>
+    pub fn serialize<S, P>(
+        &self,
+        object: &S,
+        path: P,
+        format: Format,
+        compression: Compression,
+    ) -> Result<()>
     where
-        S: Serialize + 'static,
-        P: AsRef<Path> + 'static,
+        S: Serialize,
+        P: AsRef<Path>,
     {
-        let file = self.open_file(path, FileOpenMode::Write)?;
-        Self::do_serialize(object, file, pretty)
+        let content = self.serialize_to_vec(object, &path, format, compression)?;
+        self.write(path, &content)
     }
 
     /// Serialize an object into a new file.
@@ -210,48 +621,321 @@ impl Io {
     ///
     /// # Errors
     /// The function may return an IO, serde or a custom library error.
-    pub fn serialize_new<S, P>(&self, object: &S, path: P, pretty: bool) -> Result<()>
+    pub fn serialize_new<S, P>(
+        &self,
+        object: &S,
+        path: P,
+        format: Format,
+        compression: Compression,
+    ) -> Result<()>
+    where
+        S: Serialize,
+        P: AsRef<Path>,
+    {
+        let content = self.serialize_to_vec(object, &path, format, compression)?;
+        self.write_new(path, &content)
+    }
+
+    /// Serialize an object into content of a file.
+    ///
+    /// The content is the same as [`Io::serialize`] would write into a file under `path`. It may
+    /// be written afterwards with [`Io::write`] or [`Io::write_new`] under the same path, which is
+    /// useful when e.g. a checksum of a file has to be known before the file is written.
+    ///
+    /// # Errors
+    /// The function may return a serde or a custom library error, e.g. in case the path is
+    /// invalid.
+    pub fn serialize_to_vec<S, P>(
+        &self,
+        object: &S,
+        path: P,
+        format: Format,
+        compression: Compression,
+    ) -> Result<Vec<u8>>
+    where
+        S: Serialize,
+        P: AsRef<Path>,
+    {
+        let file_path = self.resolve_path(path, FileOpenMode::Write)?;
+        self.seal(
+            Self::compress(Self::encode(object, format)?, compression)?,
+            &file_path,
+        )
+    }
+
+    /// Write content produced by [`Io::serialize_to_vec`] into a file replacing old content.
+    ///
+    /// The file has to exist and is replaced atomically, like in case of [`Io::serialize`].
+    ///
+    /// # Errors
+    /// The function may return either an IO error or a custom library error in case the path
+    /// is invalid.
+    pub fn write<P>(&self, path: P, content: &[u8]) -> Result<()>
+    where
+        P: AsRef<Path>,
+    {
+        let file_path = self.resolve_path(path, FileOpenMode::Open)?;
+        self.storage.write(&file_path, content)
+    }
+
+    /// Write content produced by [`Io::serialize_to_vec`] into a new file.
+    ///
+    /// The function fails when the file already exists, like [`Io::serialize_new`].
+    ///
+    /// # Errors
+    /// The function may return either an IO error or a custom library error in case the path
+    /// is invalid.
+    pub fn write_new<P>(&self, path: P, content: &[u8]) -> Result<()>
     where
-        S: Serialize + 'static,
-        P: AsRef<Path> + 'static,
+        P: AsRef<Path>,
     {
-        let file = self.open_file(path, FileOpenMode::WriteCreate)?;
-        Self::do_serialize(object, file, pretty)
+        let file_path = self.resolve_path(path, FileOpenMode::Write)?;
+        self.storage.write_new(&file_path, content)
+    }
+
+    /// Return a CRC32C checksum of content of a file.
+    ///
+    /// The checksum is computed over the content exactly as stored, i.e. after compression and
+    /// encryption. It may be verified later on with [`Io::deserialize_verified`].
+    #[must_use]
+    pub fn checksum(content: &[u8]) -> u32 {
+        crc32c::crc32c(content)
     }
 
     /// Deserialize an object from an existing file.
     ///
     /// The file has to exists in the filesystem and contains a serialized instance of the same
     /// type. Either [`Io::serialize`] or [`Io::serialize_new`] is required prior to a call to this
-    /// function.
+    /// function. The format and the compression the object has been written with are recognized
+    /// automatically.
     ///
     /// # Errors
     /// The function may return both custom library as well as IO and serde internal errors.
+    /// [`CustomKind::Encoding`] error is returned if a binary encoded object cannot be decoded,
+    /// compressed content is corrupted or encrypted content has been altered.
     pub fn deserialize<S, P>(&self, path: P) -> Result<S>
     where
-        S: DeserializeOwned + 'static,
-        P: AsRef<Path> + 'static,
+        S: DeserializeOwned,
+        P: AsRef<Path>,
+    {
+        self.deserialize_verified(path, &[])
+    }
+
+    /// Return paths of all files of the database, relative to a database's base path.
+    ///
+    /// # Errors
+    /// The function returns an IO error if the files cannot be listed.
+    pub fn list(&self) -> Result<Vec<PathBuf>> {
+        self.storage.list()
+    }
+
+    /// Deserialize an object from an existing file whose content has to match a checksum.
+    ///
+    /// The function is a counterpart of [`Io::deserialize`] which verifies the content of a file
+    /// before it is decoded. The content has to match any of `checksums` (see [`Io::checksum`]).
+    /// Nothing is verified if no checksum is given.
+    ///
+    /// # Errors
+    /// The function returns [`CustomKind::Corrupted`] error naming the file if its content does
+    /// not match any of the checksums, e.g. because it has been edited by hand or truncated.
+    /// Otherwise it fails in the same cases as [`Io::deserialize`].
+    pub fn deserialize_verified<S, P>(&self, path: P, checksums: &[u32]) -> Result<S>
+    where
+        S: DeserializeOwned,
+        P: AsRef<Path>,
+    {
+        let file_path = self.resolve_path(path, FileOpenMode::Open)?;
+        let content = self.storage.read(&file_path)?;
+
+        let checksum = Self::checksum(&content);
+        if !checksums.is_empty() && !checksums.contains(&checksum) {
+            return Err(Error::custom_err(
+                CustomKind::Corrupted,
+                &format!(
+                    "File is corrupted, its checksum {:08x} does not match: {}",
+                    checksum,
+                    self.storage.root().join(file_path).display()
+                ),
+            ));
+        }
+        Self::decode(&Self::decompress(self.unseal(content, &file_path)?)?)
+    }
+
+    /// Read raw content of an existing file.
+    ///
+    /// The content is returned exactly as stored, e.g. to compute its checksum (see
+    /// [`Io::checksum`]).
+    ///
+    /// # Errors
+    /// The function may return either an IO error or a custom library error in case the path
+    /// is invalid.
+    pub fn read<P>(&self, path: P) -> Result<Vec<u8>>
+    where
+        P: AsRef<Path>,
+    {
+        let file_path = self.resolve_path(path, FileOpenMode::Open)?;
+        self.storage.read(&file_path)
+    }
+
+    /// Append a serialized object to a file as a single line.
+    ///
+    /// The file is created if it does not exist yet. The object is always serialized into a compact
+    /// JSON followed by a new line character and flushed to the disk before the function returns.
+    /// Objects appended this way may be read back with [`Io::deserialize_lines`]. Lines of
+    /// an encrypted database are encrypted one by one and hex encoded.
+    ///
+    /// # Errors
+    /// The function may return an IO, serde or a custom library error.
+    pub fn append<S, P>(&self, object: &S, path: P) -> Result<()>
+    where
+        S: Serialize,
+        P: AsRef<Path>,
+    {
+        let file_path = self.resolve_path(path, FileOpenMode::Write)?;
+
+        let mut line = Self::encode(object, Format::Json)?;
+        if let Some(cipher) = &self.cipher {
+            line =
+                hex::encode(cipher.seal(&line, &Self::associated_data(&file_path))?).into_bytes();
+        }
+        line.push(b'\n');
+        self.storage.append(&file_path, &line)
+    }
+
+    /// Deserialize all objects appended to a file.
+    ///
+    /// The file is expected to contain objects appended by [`Io::append`]. The last line is
+    /// ignored if it is not terminated by a new line character, since it is a leftover of
+    /// an interrupted write.
+    ///
+    /// # Errors
+    /// The function may return both custom library as well as IO and serde internal errors.
+    pub fn deserialize_lines<S, P>(&self, path: P) -> Result<Vec<S>>
+    where
+        S: DeserializeOwned,
+        P: AsRef<Path>,
+    {
+        let file_path = self.resolve_path(path, FileOpenMode::Open)?;
+        let content = self.storage.read(&file_path)?;
+
+        // The last chunk is either empty or an incomplete line
+        let mut lines: Vec<&[u8]> = content.split(|byte| *byte == b'\n').collect();
+        lines.pop();
+
+        let aad = Self::associated_data(&file_path);
+        lines
+            .into_iter()
+            .map(|line| match &self.cipher {
+                Some(cipher) => {
+                    let sealed = hex::decode(line).map_err(|err| {
+                        Error::custom_err(
+                            CustomKind::Encoding,
+                            &format!("Cannot decode an encrypted line: {}", err),
+                        )
+                    })?;
+                    Ok(serde_json::from_slice(&cipher.unseal(&sealed, &aad)?)?)
+                }
+                None => Ok(serde_json::from_slice(line)?),
+            })
+            .collect()
+    }
+
+    /// Check whether a file exists.
+    ///
+    /// The path is relative to a database's base path.
+    pub fn exists<P>(&self, path: P) -> bool
+    where
+        P: AsRef<Path>,
+    {
+        self.resolve_path(path, FileOpenMode::Open).is_ok()
+    }
+
+    /// Serialize database metadata into its dedicated file.
+    ///
+    /// The metadata file is created by [`Io::create`], hence the function only overwrites its
+    /// content. It should be called whenever metadata of a database has been altered.
+    ///
+    /// # Errors
+    /// The function may return an IO, serde or a custom library error.
+    pub fn serialize_metadata(&self, db_meta: &DbMeta) -> Result<()> {
+        self.serialize(
+            db_meta,
+            Self::metadata_path(),
+            Format::PrettyJson,
+            Compression::None,
+        )
+    }
+
+    /// Remove an existing file.
+    ///
+    /// The path is relative to a database's base path and has to point to a file.
+    ///
+    /// # Errors
+    /// The function may return either an IO error or a custom library error in case the path
+    /// is invalid.
+    pub fn remove<P>(&self, path: P) -> Result<()>
+    where
+        P: AsRef<Path>,
     {
-        let file = self.open_file(path, FileOpenMode::Open)?;
-        let reader = BufReader::new(file);
-        let object = serde_json::from_reader(reader)?;
-        Ok(object)
+        let file_path = self.resolve_path(path, FileOpenMode::Open)?;
+        self.storage.remove(&file_path)
+    }
+
+    /// Rename an existing file.
+    ///
+    /// Both paths are relative to a database's base path. The function refuses to overwrite
+    /// a file which already exists under the `to` path. Encrypted content is bound to the path
+    /// of a file, hence a file of an encrypted database is decrypted and encrypted again under
+    /// the new path. Such a file has to be written by [`Io::serialize`] or [`Io::serialize_new`].
+    ///
+    /// # Errors
+    /// The function may return either an IO error or a custom library error in case any of the
+    /// paths is invalid. [`CustomKind::Encoding`] error is returned if encrypted content of the
+    /// file has been altered.
+    pub fn rename<P>(&self, from: P, to: P) -> Result<()>
+    where
+        P: AsRef<Path>,
+    {
+        let from_path = self.resolve_path(from, FileOpenMode::Open)?;
+        let to_path = self.resolve_path(to, FileOpenMode::Write)?;
+        if self.storage.exists(&to_path) {
+            return Err(Error::custom_err(
+                CustomKind::InvalidArgument,
+                &format!(
+                    "Cannot rename into existing path: {}",
+                    self.storage.root().join(to_path).display()
+                ),
+            ));
+        }
+
+        if self.cipher.is_none() {
+            return self.storage.rename(&from_path, &to_path);
+        }
+        let content = self.unseal(self.storage.read(&from_path)?, &from_path)?;
+        self.storage
+            .write_new(&to_path, &self.seal(content, &to_path)?)?;
+        self.storage.remove(&from_path)
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::storage::MemoryStorage;
     use more_asserts::*;
     use rstest::*;
-    use serde::Deserialize;
+    use serde::ser::Error as SerError;
+    use serde::{Deserialize, Serializer};
+    use std::fs;
+    use std::fs::File;
+    use std::io::Write;
     use tempdir::TempDir;
 
     /* ----------------- */
     /* ---- Helpers ---- */
     /* ----------------- */
 
-    const TEST_DATABASE_NAME: &'static str = "DB_UT";
+    const TEST_DATABASE_NAME: &str = "DB_UT";
 
     fn database_dir(dir: &TempDir) -> PathBuf {
         dir.path().join(TEST_DATABASE_NAME)
@@ -295,7 +979,7 @@ mod tests {
     #[fixture]
     fn io_created() -> IoInstanceFixture {
         let temp_dir = temp_dir();
-        let io = Io::create(temp_dir.path().to_path_buf(), &db_meta()).unwrap();
+        let io = Io::create(temp_dir.path(), &db_meta()).unwrap();
         (io, temp_dir)
     }
 
@@ -303,7 +987,11 @@ mod tests {
     #[fixture]
     fn io_opened() -> IoInstanceFixture {
         let (_, temp_dir) = io_created();
-        let (io, _) = Io::open(temp_dir.path().join(TEST_DATABASE_NAME)).unwrap();
+        let (io, _) = Io::open(
+            temp_dir.path().join(TEST_DATABASE_NAME),
+            OpenMode::ReadWrite,
+        )
+        .unwrap();
         (io, temp_dir)
     }
 
@@ -313,6 +1001,15 @@ mod tests {
         field2: f32,
     }
 
+    // An object whose serialization always fails
+    struct FailingObject;
+
+    impl Serialize for FailingObject {
+        fn serialize<S: Serializer>(&self, _serializer: S) -> std::result::Result<S::Ok, S::Error> {
+            Err(S::Error::custom("Serialization failure"))
+        }
+    }
+
     // Return a serializable test object
     #[fixture]
     fn serializable_object() -> Object {
@@ -330,7 +1027,7 @@ mod tests {
     #[case("<123+45>")]
     #[case("&!@Name12")]
     fn invalid_database_name_is_caught(#[case] name: &str) {
-        assert!(!Io::is_name_valid(name));
+        assert!(!is_name_valid(name));
     }
 
     #[rstest]
@@ -340,13 +1037,13 @@ mod tests {
     #[case("_2022_database")]
     #[case("SomeDatabase_2022_backup")]
     fn valid_database_name_does_not_pose_problems(#[case] name: &str) {
-        assert!(Io::is_name_valid(name));
+        assert!(is_name_valid(name));
     }
 
     #[rstest]
     fn invalid_database_name_produces_error(temp_dir: TempDir) {
         let metadata = DbMeta::new("!!InvalidName!!");
-        let io = Io::create(temp_dir.path().to_path_buf(), &metadata);
+        let io = Io::create(temp_dir.path(), &metadata);
         let err = io.unwrap_err();
         assert_eq!(CustomKind::InvalidArgument, *err.get_custom_kind().unwrap());
 
@@ -360,7 +1057,7 @@ mod tests {
     ) {
         let (_io, temp_dir) = io_created;
 
-        let result = Io::create(temp_dir.path().to_path_buf(), &db_meta);
+        let result = Io::create(temp_dir.path(), &db_meta);
         let err = result.unwrap_err();
         assert_eq!(CustomKind::DbIo, *err.get_custom_kind().unwrap());
 
@@ -370,7 +1067,7 @@ mod tests {
     #[rstest]
     fn returned_database_path_is_absolute_after_database_creation(io_created: IoInstanceFixture) {
         let (io, temp_dir) = io_created;
-        assert!(io.path.is_absolute());
+        assert!(io.storage.root().is_absolute());
 
         remove_temp_dir(temp_dir);
     }
@@ -398,7 +1095,10 @@ mod tests {
     fn invalid_path_generates_error_when_opening_database(temp_dir: TempDir) {
         // Temporary directory is empty at this point.
         // Append invalid trailing directory to the path and see if it produces and error
-        let io = Io::open(temp_dir.path().join("InvalidDirectory"));
+        let io = Io::open(
+            temp_dir.path().join("InvalidDirectory"),
+            OpenMode::ReadWrite,
+        );
         let err = io.unwrap_err();
         assert_eq!(CustomKind::DbIo, *err.get_custom_kind().unwrap());
 
@@ -408,7 +1108,7 @@ mod tests {
     #[rstest]
     fn missing_metadata_dir_produces_error_when_opening_database(temp_dir: TempDir) {
         // At this point temporary directory exists but contains nothing inside
-        let io = Io::open(temp_dir.path().to_path_buf());
+        let io = Io::open(temp_dir.path(), OpenMode::ReadWrite);
         let err = io.unwrap_err();
         assert_eq!(CustomKind::InvalidArgument, *err.get_custom_kind().unwrap());
 
@@ -420,7 +1120,7 @@ mod tests {
         // Build partial database structure by creating metadata directory only
         fs::create_dir(temp_dir.path().join(Io::METADATA_DIR)).unwrap();
 
-        let io = Io::open(temp_dir.path().to_path_buf());
+        let io = Io::open(temp_dir.path(), OpenMode::ReadWrite);
         let err = io.unwrap_err();
         assert_eq!(CustomKind::InvalidArgument, *err.get_custom_kind().unwrap());
 
@@ -431,7 +1131,7 @@ mod tests {
     fn valid_directory_returns_io_instance_when_opening_database(io_opened: IoInstanceFixture) {
         let (io, temp_dir) = io_opened;
         // Internal path should always be absolute
-        assert!(io.path.is_absolute());
+        assert!(io.storage.root().is_absolute());
 
         remove_temp_dir(temp_dir);
     }
@@ -448,7 +1148,12 @@ mod tests {
     ) {
         let (io, temp_dir) = io_opened;
 
-        let result = io.serialize(&serializable_object, path, true);
+        let result = io.serialize(
+            &serializable_object,
+            path,
+            Format::PrettyJson,
+            Compression::None,
+        );
         let err = result.unwrap_err();
         assert_eq!(CustomKind::InvalidArgument, *err.get_custom_kind().unwrap());
 
@@ -464,8 +1169,13 @@ mod tests {
         let new_object = 100;
 
         let len = file_len(&metadata_file_path);
-        io.serialize(&new_object, metadata_file_path.clone(), true)
-            .unwrap();
+        io.serialize(
+            &new_object,
+            metadata_file_path.clone(),
+            Format::PrettyJson,
+            Compression::None,
+        )
+        .unwrap();
         // Metadata file shall have content updated
         assert_lt!(file_len(&metadata_file_path), len);
 
@@ -480,7 +1190,12 @@ mod tests {
         let (io, temp_dir) = io_opened;
         let path = test_database_metadata_file_path(&temp_dir);
 
-        let result = io.serialize_new(&serializable_object, path, true);
+        let result = io.serialize_new(
+            &serializable_object,
+            path,
+            Format::PrettyJson,
+            Compression::None,
+        );
         let err = result.unwrap_err();
         // Expect IO error
         assert!(!err.is_custom());
@@ -489,7 +1204,30 @@ mod tests {
     }
 
     #[rstest]
-    #[case::absolute_path(Path::new("/").join(Io::METADATA_DIR).join(Io::METADATA_FILE))]
+    #[case::absolute(Path::new("/").join(Io::METADATA_DIR).join(Io::METADATA_FILE))]
+    #[case::parent_dir(PathBuf::from("../serialized.json"))]
+    #[case::nested_parent_dir(PathBuf::from("sub/../../serialized.json"))]
+    fn path_outside_database_throws_error_when_serializing_new(
+        #[case] path: PathBuf,
+        io_opened: IoInstanceFixture,
+        serializable_object: Object,
+    ) {
+        let (io, temp_dir) = io_opened;
+
+        let result = io.serialize_new(
+            &serializable_object,
+            path,
+            Format::PrettyJson,
+            Compression::None,
+        );
+        let err = result.unwrap_err();
+        assert_eq!(CustomKind::InvalidArgument, *err.get_custom_kind().unwrap());
+        assert!(!temp_dir.path().join("serialized.json").exists());
+
+        remove_temp_dir(temp_dir);
+    }
+
+    #[rstest]
     #[case::path_to_directory(Path::new(Io::METADATA_DIR).to_path_buf())]
     #[case::empty_path("")]
     fn wrong_path_throws_error_when_serializing_new(
@@ -499,7 +1237,12 @@ mod tests {
     ) {
         let (io, temp_dir) = io_opened;
 
-        let result = io.serialize_new(&serializable_object, path, true);
+        let result = io.serialize_new(
+            &serializable_object,
+            path,
+            Format::PrettyJson,
+            Compression::None,
+        );
         let err = result.unwrap_err();
         // Expect IO error
         assert!(!err.is_custom());
@@ -520,8 +1263,13 @@ mod tests {
         let full_path = database_dir(&temp_dir).join(&path);
 
         assert!(!full_path.exists());
-        io.serialize_new(&serializable_object, path.clone(), true)
-            .unwrap();
+        io.serialize_new(
+            &serializable_object,
+            path.clone(),
+            Format::PrettyJson,
+            Compression::None,
+        )
+        .unwrap();
         assert!(full_path.exists());
         assert_gt!(file_len(&full_path), 0);
 
@@ -540,13 +1288,25 @@ mod tests {
 
         // Serialize new object at first
         assert!(!full_path.exists());
-        io.serialize_new(&old_object, path.clone(), true).unwrap();
+        io.serialize_new(
+            &old_object,
+            path.clone(),
+            Format::PrettyJson,
+            Compression::None,
+        )
+        .unwrap();
         assert!(full_path.exists());
         let old_file_len = file_len(&full_path);
         assert_gt!(old_file_len, 0);
 
         // Overwrite the same object file
-        io.serialize(&new_object, path.clone(), true).unwrap();
+        io.serialize(
+            &new_object,
+            path.clone(),
+            Format::PrettyJson,
+            Compression::None,
+        )
+        .unwrap();
         assert_gt!(file_len(&full_path), old_file_len);
 
         remove_temp_dir(temp_dir);
@@ -566,15 +1326,25 @@ mod tests {
 
         // Serialize first object
         assert!(!file1_full_path.exists());
-        io.serialize_new(&serializable_object, file1_path.clone(), true)
-            .unwrap();
+        io.serialize_new(
+            &serializable_object,
+            file1_path.clone(),
+            Format::PrettyJson,
+            Compression::None,
+        )
+        .unwrap();
         assert!(file1_full_path.exists());
         assert_gt!(file_len(&file1_full_path), 0);
 
         // Serialize second object whose path already exists in the filesystem
         assert!(!file2_full_path.exists());
-        io.serialize_new(&serializable_object, file2_path.clone(), true)
-            .unwrap();
+        io.serialize_new(
+            &serializable_object,
+            file2_path.clone(),
+            Format::PrettyJson,
+            Compression::None,
+        )
+        .unwrap();
         assert!(file2_full_path.exists());
         assert_gt!(file_len(&file2_full_path), 0);
 
@@ -592,14 +1362,24 @@ mod tests {
         let full_path = database_dir(&temp_dir).join(&path);
 
         // Write pretty JSON
-        io.serialize_new(&serializable_object, path.clone(), true)
-            .unwrap();
+        io.serialize_new(
+            &serializable_object,
+            path.clone(),
+            Format::PrettyJson,
+            Compression::None,
+        )
+        .unwrap();
         let first_len = file_len(&full_path);
         assert_gt!(first_len, 0);
 
         // Write non-pretty JSON into the same file
-        io.serialize(&serializable_object, path.clone(), false)
-            .unwrap();
+        io.serialize(
+            &serializable_object,
+            path.clone(),
+            Format::Json,
+            Compression::None,
+        )
+        .unwrap();
         assert_lt!(file_len(&full_path), first_len);
 
         remove_temp_dir(temp_dir);
@@ -645,13 +1425,20 @@ mod tests {
         let (io, temp_dir) = io_opened;
         let path = "serialized.json";
 
+        // Fields are never read since deserialization is expected to fail
+        #[allow(dead_code)]
         #[derive(Deserialize, Debug)]
         struct AnotherObject {
             some_field: i32,
             another_field: u8,
         }
-        io.serialize_new(&serializable_object, path.clone(), true)
-            .unwrap();
+        io.serialize_new(
+            &serializable_object,
+            path,
+            Format::PrettyJson,
+            Compression::None,
+        )
+        .unwrap();
         let result: Result<AnotherObject> = io.deserialize(path);
         let err = result.unwrap_err();
         // Expect serde error
@@ -671,11 +1458,667 @@ mod tests {
     ) {
         let (io, temp_dir) = io_opened;
 
-        io.serialize_new(&serializable_object, path.clone(), true)
-            .unwrap();
+        io.serialize_new(
+            &serializable_object,
+            path.clone(),
+            Format::PrettyJson,
+            Compression::None,
+        )
+        .unwrap();
         let deserialized = io.deserialize(path).unwrap();
         assert_eq!(serializable_object, deserialized);
 
         remove_temp_dir(temp_dir);
     }
+
+    #[rstest]
+    fn metadata_is_serialized_into_metadata_file(io_opened: IoInstanceFixture) {
+        let (io, temp_dir) = io_opened;
+        let mut metadata = DbMeta::new(TEST_DATABASE_NAME);
+        metadata
+            .collections
+            .insert("words".to_string(), crate::metadata::Collection::new());
+
+        io.serialize_metadata(&metadata).unwrap();
+        drop(io);
+        let (_, deserialized) = Io::open(database_dir(&temp_dir), OpenMode::ReadOnly).unwrap();
+        assert!(deserialized.collections.contains_key("words"));
+
+        remove_temp_dir(temp_dir);
+    }
+
+    #[rstest]
+    fn file_is_removed(io_opened: IoInstanceFixture, serializable_object: Object) {
+        let (io, temp_dir) = io_opened;
+        let path = "serialized.json";
+        let full_path = database_dir(&temp_dir).join(path);
+
+        io.serialize_new(
+            &serializable_object,
+            path,
+            Format::PrettyJson,
+            Compression::None,
+        )
+        .unwrap();
+        assert!(full_path.exists());
+        io.remove(path).unwrap();
+        assert!(!full_path.exists());
+
+        remove_temp_dir(temp_dir);
+    }
+
+    #[rstest]
+    #[case::non_existing_file("serialized.json")]
+    #[case::path_to_directory(Path::new(Io::METADATA_DIR).to_path_buf())]
+    #[case::path_outside_database("/tmp")]
+    fn invalid_path_throws_error_when_removing(
+        #[case] path: PathBuf,
+        io_opened: IoInstanceFixture,
+    ) {
+        let (io, temp_dir) = io_opened;
+
+        let err = io.remove(path).unwrap_err();
+        assert_eq!(CustomKind::InvalidArgument, *err.get_custom_kind().unwrap());
+
+        remove_temp_dir(temp_dir);
+    }
+
+    #[rstest]
+    fn file_is_renamed(io_opened: IoInstanceFixture, serializable_object: Object) {
+        let (io, temp_dir) = io_opened;
+        let old_full_path = database_dir(&temp_dir).join("old.json");
+        let new_full_path = database_dir(&temp_dir).join("new.json");
+
+        io.serialize_new(
+            &serializable_object,
+            "old.json",
+            Format::PrettyJson,
+            Compression::None,
+        )
+        .unwrap();
+        io.rename("old.json", "new.json").unwrap();
+        assert!(!old_full_path.exists());
+        assert_eq!(
+            serializable_object,
+            io.deserialize::<Object, _>("new.json").unwrap()
+        );
+        assert!(new_full_path.exists());
+
+        remove_temp_dir(temp_dir);
+    }
+
+    #[rstest]
+    fn file_is_not_renamed_into_existing_file(
+        io_opened: IoInstanceFixture,
+        serializable_object: Object,
+    ) {
+        let (io, temp_dir) = io_opened;
+
+        io.serialize_new(
+            &serializable_object,
+            "old.json",
+            Format::PrettyJson,
+            Compression::None,
+        )
+        .unwrap();
+        io.serialize_new(
+            &serializable_object,
+            "new.json",
+            Format::PrettyJson,
+            Compression::None,
+        )
+        .unwrap();
+        let err = io.rename("old.json", "new.json").unwrap_err();
+        assert_eq!(CustomKind::InvalidArgument, *err.get_custom_kind().unwrap());
+        assert!(database_dir(&temp_dir).join("old.json").exists());
+
+        remove_temp_dir(temp_dir);
+    }
+
+    #[rstest]
+    fn no_temporary_file_is_left_after_serializing(
+        io_opened: IoInstanceFixture,
+        serializable_object: Object,
+    ) {
+        let (io, temp_dir) = io_opened;
+        let path = "serialized.json";
+
+        io.serialize_new(
+            &serializable_object,
+            path,
+            Format::PrettyJson,
+            Compression::None,
+        )
+        .unwrap();
+        io.serialize(&serializable_object, path, Format::Json, Compression::None)
+            .unwrap();
+        let files: Vec<_> = fs::read_dir(database_dir(&temp_dir))
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name())
+            .collect();
+        assert_eq!(2, files.len());
+        assert!(files.contains(&Io::METADATA_DIR.into()));
+        assert!(files.contains(&path.into()));
+
+        remove_temp_dir(temp_dir);
+    }
+
+    #[rstest]
+    fn original_content_is_retained_when_serialization_fails(
+        io_opened: IoInstanceFixture,
+        serializable_object: Object,
+    ) {
+        let (io, temp_dir) = io_opened;
+        let path = "serialized.json";
+        let full_path = database_dir(&temp_dir).join(path);
+
+        io.serialize_new(
+            &serializable_object,
+            path,
+            Format::PrettyJson,
+            Compression::None,
+        )
+        .unwrap();
+        let err = io
+            .serialize(&FailingObject, path, Format::PrettyJson, Compression::None)
+            .unwrap_err();
+        // Expect serde error
+        assert!(!err.is_custom());
+        assert_eq!(serializable_object, io.deserialize(path).unwrap());
+        assert!(!full_path.with_file_name(".serialized.json.tmp").exists());
+
+        remove_temp_dir(temp_dir);
+    }
+
+    #[rstest]
+    fn no_file_is_left_when_serialization_into_new_file_fails(io_opened: IoInstanceFixture) {
+        let (io, temp_dir) = io_opened;
+        let path = "serialized.json";
+
+        io.serialize_new(&FailingObject, path, Format::PrettyJson, Compression::None)
+            .unwrap_err();
+        assert!(!database_dir(&temp_dir).join(path).exists());
+
+        remove_temp_dir(temp_dir);
+    }
+
+    #[rstest]
+    fn objects_are_appended_into_new_file(io_opened: IoInstanceFixture) {
+        let (io, temp_dir) = io_opened;
+        let path = "appended.log";
+
+        io.append(&Object::default(), path).unwrap();
+        io.append(
+            &Object {
+                field1: 1,
+                field2: 2.0,
+            },
+            path,
+        )
+        .unwrap();
+        let objects: Vec<Object> = io.deserialize_lines(path).unwrap();
+        assert_eq!(
+            vec![
+                Object::default(),
+                Object {
+                    field1: 1,
+                    field2: 2.0
+                }
+            ],
+            objects
+        );
+
+        remove_temp_dir(temp_dir);
+    }
+
+    #[rstest]
+    fn incomplete_last_line_is_ignored_when_deserializing_lines(io_opened: IoInstanceFixture) {
+        let (io, temp_dir) = io_opened;
+        let path = "appended.log";
+
+        io.append(&Object::default(), path).unwrap();
+        // Simulate interrupted write
+        let mut file = fs::OpenOptions::new()
+            .append(true)
+            .open(database_dir(&temp_dir).join(path))
+            .unwrap();
+        file.write_all(b"{\"field1\": 1, \"fie").unwrap();
+
+        let objects: Vec<Object> = io.deserialize_lines(path).unwrap();
+        assert_eq!(vec![Object::default()], objects);
+
+        remove_temp_dir(temp_dir);
+    }
+
+    #[rstest]
+    fn corrupted_line_throws_error_when_deserializing_lines(io_opened: IoInstanceFixture) {
+        let (io, temp_dir) = io_opened;
+        let path = "appended.log";
+
+        fs::write(database_dir(&temp_dir).join(path), b"{}\n").unwrap();
+        let result: Result<Vec<Object>> = io.deserialize_lines(path);
+        // Expect serde error
+        assert!(!result.unwrap_err().is_custom());
+
+        remove_temp_dir(temp_dir);
+    }
+
+    #[rstest]
+    fn existence_of_file_is_checked(io_opened: IoInstanceFixture) {
+        let (io, temp_dir) = io_opened;
+
+        assert!(io.exists(Path::new(Io::METADATA_DIR).join(Io::METADATA_FILE)));
+        assert!(!io.exists(Path::new(Io::METADATA_DIR)));
+        assert!(!io.exists("missing.json"));
+
+        remove_temp_dir(temp_dir);
+    }
+
+    #[rstest]
+    fn created_database_is_locked_exclusively(io_created: IoInstanceFixture) {
+        let (io, temp_dir) = io_created;
+
+        for mode in [OpenMode::ReadOnly, OpenMode::ReadWrite] {
+            let err = Io::open(database_dir(&temp_dir), mode).unwrap_err();
+            assert_eq!(CustomKind::Locked, *err.get_custom_kind().unwrap());
+        }
+
+        drop(io);
+        remove_temp_dir(temp_dir);
+    }
+
+    #[rstest]
+    #[case::two_writers(OpenMode::ReadWrite, OpenMode::ReadWrite)]
+    #[case::writer_and_reader(OpenMode::ReadWrite, OpenMode::ReadOnly)]
+    #[case::reader_and_writer(OpenMode::ReadOnly, OpenMode::ReadWrite)]
+    fn conflicting_open_modes_produce_error(
+        #[case] first: OpenMode,
+        #[case] second: OpenMode,
+        io_created: IoInstanceFixture,
+    ) {
+        let (io, temp_dir) = io_created;
+        drop(io);
+
+        let _first = Io::open(database_dir(&temp_dir), first).unwrap();
+        let err = Io::open(database_dir(&temp_dir), second).unwrap_err();
+        assert_eq!(CustomKind::Locked, *err.get_custom_kind().unwrap());
+
+        drop(_first);
+        remove_temp_dir(temp_dir);
+    }
+
+    #[rstest]
+    fn database_may_be_opened_by_many_readers(io_created: IoInstanceFixture) {
+        let (io, temp_dir) = io_created;
+        drop(io);
+
+        let _reader1 = Io::open(database_dir(&temp_dir), OpenMode::ReadOnly).unwrap();
+        let _reader2 = Io::open(database_dir(&temp_dir), OpenMode::ReadOnly).unwrap();
+
+        drop((_reader1, _reader2));
+        remove_temp_dir(temp_dir);
+    }
+
+    #[rstest]
+    fn lock_is_released_when_io_instance_is_dropped(io_opened: IoInstanceFixture) {
+        let (io, temp_dir) = io_opened;
+
+        drop(io);
+        Io::open(database_dir(&temp_dir), OpenMode::ReadWrite).unwrap();
+
+        remove_temp_dir(temp_dir);
+    }
+
+    #[rstest]
+    fn database_structure_may_be_kept_in_memory(db_meta: DbMeta, serializable_object: Object) {
+        let storage = MemoryStorage::new();
+        let io = Io::create_with(Box::new(storage.clone()), &db_meta).unwrap();
+        io.serialize_new(
+            &serializable_object,
+            "sub/serialized.json",
+            Format::PrettyJson,
+            Compression::None,
+        )
+        .unwrap();
+        drop(io);
+
+        let (io, metadata) = Io::open_with(Box::new(storage), OpenMode::ReadWrite).unwrap();
+        assert_eq!(db_meta.name, metadata.name);
+        assert_eq!(
+            serializable_object,
+            io.deserialize::<Object, _>("sub/serialized.json").unwrap()
+        );
+    }
+
+    #[rstest]
+    fn empty_storage_produces_error_when_opening_database() {
+        let err = Io::open_with(Box::new(MemoryStorage::new()), OpenMode::ReadWrite).unwrap_err();
+        assert_eq!(CustomKind::InvalidArgument, *err.get_custom_kind().unwrap());
+    }
+
+    #[rstest]
+    #[case::absolute_path("/serialized.json")]
+    #[case::non_existing_file("serialized.json")]
+    fn invalid_path_throws_error_when_deserializing_from_memory(
+        #[case] path: &str,
+        db_meta: DbMeta,
+    ) {
+        let io = Io::create_with(Box::new(MemoryStorage::new()), &db_meta).unwrap();
+
+        let err = io.deserialize::<Object, _>(path).unwrap_err();
+        assert_eq!(CustomKind::InvalidArgument, *err.get_custom_kind().unwrap());
+    }
+
+    #[rstest]
+    #[case::json(Format::Json)]
+    #[case::pretty_json(Format::PrettyJson)]
+    #[case::cbor(Format::Cbor)]
+    #[case::message_pack(Format::MessagePack)]
+    fn object_is_deserialized_regardless_of_format(#[case] format: Format, db_meta: DbMeta) {
+        let io = Io::create_with(Box::new(MemoryStorage::new()), &db_meta).unwrap();
+        let object = serde_json::json!({
+            "word": "house",
+            "level": 2,
+            "score": -0.5,
+            "examples": ["A big house", {"note": null, "valid": true}]
+        });
+
+        io.serialize_new(&object, "serialized.json", format, Compression::None)
+            .unwrap();
+        let deserialized: serde_json::Value = io.deserialize("serialized.json").unwrap();
+        assert_eq!(object, deserialized);
+        let structure = Object {
+            field1: 7,
+            field2: 1.5,
+        };
+        io.serialize(&structure, "serialized.json", format, Compression::None)
+            .unwrap();
+        assert_eq!(structure, io.deserialize("serialized.json").unwrap());
+    }
+
+    #[rstest]
+    fn binary_formats_imply_smaller_file_size(db_meta: DbMeta) {
+        let io = Io::create_with(Box::new(MemoryStorage::new()), &db_meta).unwrap();
+        let object: serde_json::Map<String, serde_json::Value> = (0..100)
+            .map(|i| {
+                (
+                    format!("word{}", i),
+                    serde_json::json!({"level": i, "known": false}),
+                )
+            })
+            .collect();
+
+        let mut lengths = vec![];
+        for format in [Format::Json, Format::Cbor, Format::MessagePack] {
+            io.serialize_new(
+                &object,
+                format!("{:?}.bin", format),
+                format,
+                Compression::None,
+            )
+            .unwrap();
+            lengths.push(
+                io.storage
+                    .read(Path::new(&format!("{:?}.bin", format)))
+                    .unwrap()
+                    .len(),
+            );
+        }
+        assert_lt!(lengths[1], lengths[0]);
+        assert_lt!(lengths[2], lengths[0]);
+    }
+
+    #[rstest]
+    #[case::json(b"{\"field1\": 1}".to_vec(), Format::Json)]
+    #[case::json_with_whitespace(b"\n  [1, 2]".to_vec(), Format::Json)]
+    #[case::empty(vec![], Format::Json)]
+    #[case::cbor(vec![0xd9, 0xd9, 0xf7, 0xa0], Format::Cbor)]
+    #[case::message_pack_map(vec![0x80], Format::MessagePack)]
+    #[case::message_pack_large_map(vec![0xde, 0x00, 0x00], Format::MessagePack)]
+    fn format_is_detected(#[case] content: Vec<u8>, #[case] format: Format) {
+        assert_eq!(format, Io::detect_format(&content));
+    }
+
+    #[rstest]
+    #[case::cbor(vec![0xd9, 0xd9, 0xf7, 0xa1, 0x61])]
+    #[case::message_pack(vec![0x81, 0xa6, 0x66])]
+    fn corrupted_binary_content_throws_error_when_deserializing(
+        #[case] content: Vec<u8>,
+        db_meta: DbMeta,
+    ) {
+        let storage = MemoryStorage::new();
+        let io = Io::create_with(Box::new(storage.clone()), &db_meta).unwrap();
+        storage
+            .write_new(Path::new("serialized.bin"), &content)
+            .unwrap();
+
+        let err = io.deserialize::<Object, _>("serialized.bin").unwrap_err();
+        assert_eq!(CustomKind::Encoding, *err.get_custom_kind().unwrap());
+    }
+
+    #[rstest]
+    #[case::gzip_json(Compression::Gzip, Format::Json)]
+    #[case::gzip_message_pack(Compression::Gzip, Format::MessagePack)]
+    #[case::zstd_pretty_json(Compression::Zstd, Format::PrettyJson)]
+    #[case::zstd_cbor(Compression::Zstd, Format::Cbor)]
+    fn compressed_object_is_smaller_and_may_be_deserialized(
+        #[case] compression: Compression,
+        #[case] format: Format,
+        db_meta: DbMeta,
+    ) {
+        let io = Io::create_with(Box::new(MemoryStorage::new()), &db_meta).unwrap();
+        let object: serde_json::Map<String, serde_json::Value> = (0..100)
+            .map(|i| {
+                (
+                    format!("word{}", i),
+                    serde_json::json!({"example": "A big house"}),
+                )
+            })
+            .collect();
+
+        io.serialize_new(&object, "plain.json", format, Compression::None)
+            .unwrap();
+        io.serialize_new(&object, "compressed.json", format, compression)
+            .unwrap();
+        let plain = io.storage.read(Path::new("plain.json")).unwrap();
+        let compressed = io.storage.read(Path::new("compressed.json")).unwrap();
+        assert_lt!(compressed.len(), plain.len());
+        assert_eq!(compression, Io::detect_compression(&compressed));
+        let deserialized: serde_json::Map<String, serde_json::Value> =
+            io.deserialize("compressed.json").unwrap();
+        assert_eq!(object, deserialized);
+    }
+
+    #[rstest]
+    #[case::gzip(vec![0x1f, 0x8b, 0x08, 0x00], Compression::Gzip)]
+    #[case::zstd(vec![0x28, 0xb5, 0x2f, 0xfd, 0x00], Compression::Zstd)]
+    #[case::json(b"{}".to_vec(), Compression::None)]
+    #[case::cbor(vec![0xd9, 0xd9, 0xf7, 0xa0], Compression::None)]
+    #[case::empty(vec![], Compression::None)]
+    fn compression_is_detected(#[case] content: Vec<u8>, #[case] compression: Compression) {
+        assert_eq!(compression, Io::detect_compression(&content));
+    }
+
+    #[rstest]
+    #[case::gzip(vec![0x1f, 0x8b, 0x08, 0x00, 0x01])]
+    #[case::zstd(vec![0x28, 0xb5, 0x2f, 0xfd, 0x00, 0x01])]
+    fn corrupted_compressed_content_throws_error_when_deserializing(
+        #[case] content: Vec<u8>,
+        db_meta: DbMeta,
+    ) {
+        let storage = MemoryStorage::new();
+        let io = Io::create_with(Box::new(storage.clone()), &db_meta).unwrap();
+        storage
+            .write_new(Path::new("serialized.json"), &content)
+            .unwrap();
+
+        let err = io.deserialize::<Object, _>("serialized.json").unwrap_err();
+        assert_eq!(CustomKind::Encoding, *err.get_custom_kind().unwrap());
+    }
+
+    #[rstest]
+    fn objects_of_encrypted_database_are_encrypted(db_meta: DbMeta, serializable_object: Object) {
+        let storage = MemoryStorage::new();
+        let io = Io::create_encrypted_with(Box::new(storage.clone()), &db_meta, "secret").unwrap();
+        assert!(io.is_encrypted());
+        io.serialize_new(
+            &serializable_object,
+            "serialized.json",
+            Format::Json,
+            Compression::Gzip,
+        )
+        .unwrap();
+        io.append(&serializable_object, "appended.log").unwrap();
+        drop(io);
+
+        // Only the header is kept unencrypted
+        let header = storage.read(&Io::encryption_path()).unwrap();
+        assert!(serde_json::from_slice::<Header>(&header).is_ok());
+        for path in [Io::metadata_path(), PathBuf::from("serialized.json")] {
+            let content = storage.read(&path).unwrap();
+            assert!(serde_json::from_slice::<serde_json::Value>(&content).is_err());
+            assert_eq!(Compression::None, Io::detect_compression(&content));
+        }
+        let line = storage.read(Path::new("appended.log")).unwrap();
+        assert!(line[..line.len() - 1].iter().all(u8::is_ascii_hexdigit));
+
+        let (io, metadata) =
+            Io::open_encrypted_with(Box::new(storage), OpenMode::ReadOnly, "secret").unwrap();
+        assert_eq!(db_meta.name, metadata.name);
+        assert_eq!(
+            serializable_object,
+            io.deserialize::<Object, _>("serialized.json").unwrap()
+        );
+        let objects: Vec<Object> = io.deserialize_lines("appended.log").unwrap();
+        assert_eq!(vec![serializable_object], objects);
+    }
+
+    #[rstest]
+    fn encrypted_content_is_bound_to_file_path(db_meta: DbMeta, serializable_object: Object) {
+        let storage = MemoryStorage::new();
+        let io = Io::create_encrypted_with(Box::new(storage.clone()), &db_meta, "secret").unwrap();
+        io.serialize_new(
+            &serializable_object,
+            "old.json",
+            Format::Json,
+            Compression::None,
+        )
+        .unwrap();
+        io.rename("old.json", "new.json").unwrap();
+        assert_eq!(
+            serializable_object,
+            io.deserialize::<Object, _>("new.json").unwrap()
+        );
+
+        // Content moved to another file behind the database's back is rejected
+        let content = storage.read(Path::new("new.json")).unwrap();
+        storage
+            .write_new(Path::new("other.json"), &content)
+            .unwrap();
+        let err = io.deserialize::<Object, _>("other.json").unwrap_err();
+        assert_eq!(CustomKind::Encoding, *err.get_custom_kind().unwrap());
+
+        io.append(&serializable_object, "appended.log").unwrap();
+        let line = storage.read(Path::new("appended.log")).unwrap();
+        storage.write_new(Path::new("other.log"), &line).unwrap();
+        let err = io.deserialize_lines::<Object, _>("other.log").unwrap_err();
+        assert_eq!(CustomKind::Encoding, *err.get_custom_kind().unwrap());
+    }
+
+    #[rstest]
+    #[case::not_hex(b"{}\n".to_vec())]
+    #[case::not_encrypted(b"7b7d\n".to_vec())]
+    fn unencrypted_line_throws_error_when_deserializing_lines(
+        #[case] content: Vec<u8>,
+        db_meta: DbMeta,
+    ) {
+        let storage = MemoryStorage::new();
+        let io = Io::create_encrypted_with(Box::new(storage.clone()), &db_meta, "secret").unwrap();
+        storage
+            .write_new(Path::new("appended.log"), &content)
+            .unwrap();
+
+        let err = io
+            .deserialize_lines::<Object, _>("appended.log")
+            .unwrap_err();
+        assert_eq!(CustomKind::Encoding, *err.get_custom_kind().unwrap());
+    }
+
+    #[rstest]
+    #[case::wrong_passphrase(Some("guess"), CustomKind::WrongKey)]
+    #[case::missing_passphrase(None, CustomKind::WrongKey)]
+    fn encrypted_database_is_not_opened_without_valid_passphrase(
+        #[case] passphrase: Option<&str>,
+        #[case] kind: CustomKind,
+        temp_dir: TempDir,
+        db_meta: DbMeta,
+    ) {
+        drop(Io::create_encrypted(temp_dir.path(), &db_meta, "secret").unwrap());
+
+        let path = database_dir(&temp_dir);
+        let err = match passphrase {
+            Some(passphrase) => Io::open_encrypted(&path, OpenMode::ReadOnly, passphrase),
+            None => Io::open(&path, OpenMode::ReadOnly),
+        }
+        .unwrap_err();
+        assert_eq!(kind, *err.get_custom_kind().unwrap());
+        Io::open_encrypted(&path, OpenMode::ReadWrite, "secret").unwrap();
+
+        remove_temp_dir(temp_dir);
+    }
+
+    #[rstest]
+    fn content_is_verified_against_checksums(db_meta: DbMeta, serializable_object: Object) {
+        let io = Io::create_with(Box::new(MemoryStorage::new()), &db_meta).unwrap();
+        let content = io
+            .serialize_to_vec(
+                &serializable_object,
+                "serialized.json",
+                Format::Cbor,
+                Compression::None,
+            )
+            .unwrap();
+        io.write_new("serialized.json", &content).unwrap();
+        let checksum = Io::checksum(&content);
+
+        let deserialized: Object = io
+            .deserialize_verified("serialized.json", &[1, checksum])
+            .unwrap();
+        assert_eq!(serializable_object, deserialized);
+        let err = io
+            .deserialize_verified::<Object, _>("serialized.json", &[checksum + 1])
+            .unwrap_err();
+        assert_eq!(CustomKind::Corrupted, *err.get_custom_kind().unwrap());
+        assert!(err.to_string().ends_with("serialized.json"));
+    }
+
+    #[rstest]
+    #[case::empty(b"", 0x0000_0000)]
+    #[case::check_value(b"123456789", 0xe306_9283)]
+    fn checksum_is_crc32c(#[case] content: &[u8], #[case] checksum: u32) {
+        assert_eq!(checksum, Io::checksum(content));
+    }
+
+    #[rstest]
+    fn database_with_broken_metadata_is_attached(db_meta: DbMeta) {
+        let storage = MemoryStorage::new();
+        drop(Io::create_with(Box::new(storage.clone()), &db_meta).unwrap());
+        storage.write(&Io::metadata_path(), b"{\"name\":").unwrap();
+        storage.write(Path::new("words.json"), b"{}").unwrap();
+
+        assert!(Io::open_with(Box::new(storage.clone()), OpenMode::ReadOnly).is_err());
+        let io = Io::attach_with(Box::new(storage), OpenMode::ReadOnly, None).unwrap();
+        assert_eq!(
+            b"{\"name\":".to_vec(),
+            io.read(Io::metadata_path()).unwrap()
+        );
+        assert!(io.list().unwrap().contains(&PathBuf::from("words.json")));
+    }
+
+    #[rstest]
+    fn database_without_metadata_is_not_attached() {
+        let err =
+            Io::attach_with(Box::new(MemoryStorage::new()), OpenMode::ReadOnly, None).unwrap_err();
+        assert_eq!(CustomKind::InvalidArgument, *err.get_custom_kind().unwrap());
+    }
 }
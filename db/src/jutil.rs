@@ -4,7 +4,11 @@
 //! `serde_json` dependency but yet useful in terms of this library.
 
 use crate::error::{CustomKind, Error, Result};
-use serde_json::{Map, Value as JValue};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Number, Value as JValue};
+use std::any::type_name;
+use std::cmp::Ordering;
+use std::fmt::Display;
 use std::ops::ControlFlow;
 
 // JSON pointer complement tuple representations.
@@ -60,7 +64,10 @@ pub fn pointer_complement<'a>(jvalue: &'a JValue, pointer: &str) -> Result<Compl
         .try_fold((pointer.to_string(), jvalue), |acc, token| {
             match acc.1.pointer(&token) {
                 Some(child) => {
-                    ControlFlow::Continue((acc.0.trim_start_matches(&token).to_string(), child))
+                    // Only a single occurrence of the token is stripped, since the same token
+                    // may repeat in the complement
+                    let complement = acc.0.strip_prefix(&token).unwrap_or(&acc.0).to_string();
+                    ControlFlow::Continue((complement, child))
                 }
                 None => ControlFlow::Break(acc),
             }
@@ -118,7 +125,7 @@ pub fn pointer_complement_mut<'a>(
     let (complement, _) = pointer_complement(jvalue, pointer)?;
 
     let pointer_mut = jvalue
-        .pointer_mut(pointer.trim_end_matches(&complement))
+        .pointer_mut(&pointer[..pointer.len() - complement.len()])
         .unwrap();
 
     Ok((complement, pointer_mut))
@@ -173,32 +180,1780 @@ pub fn incorporate_into(parent: &mut JValue, pointer: &str, child: JValue) -> Re
         ));
     }
 
-    let tokens: Vec<String> = complement
+    let tokens: Vec<String> = complement.split('/').skip(1).map(unescape_token).collect();
+
+    if parent.is_object() {
+        let wrapped = wrap_value(&tokens[1..], child);
+        parent
+            .as_object_mut()
+            .unwrap()
+            .insert(tokens[0].clone(), wrapped);
+    } else if parent.is_array() {
+        let wrapped = wrap_value(&tokens, child);
+        parent.as_array_mut().unwrap().push(wrapped);
+    } else {
+        return Err(Error::custom_err(
+            CustomKind::Json,
+            &format!(
+                "Cannot incorporate since the value pointed by '{}' is neither an array nor object",
+                &pointer[..pointer.len() - complement.len()]
+            ),
+        ));
+    }
+
+    Ok(())
+}
+
+/// Remove a value denoted by a pointer and return it.
+///
+/// The function is a counterpart of [`incorporate_into`]. The pointer has to denote an existing
+/// member of an object or item of an array, thus the whole value cannot be removed. Tokens are
+/// unescaped, i.e. `~1` denotes `/` and `~0` denotes `~`. Subsequent items of an array are shifted.
+///
+/// # Errors
+/// The function returns a custom error in case invalid pointer string was passed or the pointer
+/// does not denote an existing value.
+///
+/// # Examples
+/// ```
+/// use serde_json::json;
+/// use db::jutil::remove_at;
+///
+/// let mut word = json!({"word": "apple", "a/b": 1, "tags": ["fruit", "food"]});
+///
+/// assert_eq!(json!("fruit"), remove_at(&mut word, "/tags/0").unwrap());
+/// assert_eq!(json!(1), remove_at(&mut word, "/a~1b").unwrap());
+/// assert_eq!(json!({"word": "apple", "tags": ["food"]}), word);
+/// assert!(remove_at(&mut word, "/level").is_err());
+/// ```
+pub fn remove_at(jvalue: &mut JValue, pointer: &str) -> Result<JValue> {
+    let (parent, token) = parent_mut(jvalue, pointer)?;
+    let removed = match parent {
+        JValue::Object(object) => object.remove(&token),
+        JValue::Array(array) => {
+            let index = array_index(&token, array.len(), false)?;
+            Some(array.remove(index))
+        }
+        _ => None,
+    };
+    removed.ok_or_else(|| missing_value(pointer))
+}
+
+/// Remove a value denoted by a pointer, together with parent objects which become empty.
+///
+/// The function works like [`remove_at`], but afterwards every ancestor object left without
+/// members is removed as well, up to the whole value which is never removed. Arrays are not pruned.
+///
+/// # Errors
+/// The function returns the same errors as [`remove_at`].
+///
+/// # Examples
+/// ```
+/// use serde_json::json;
+/// use db::jutil::remove_pruned;
+///
+/// let mut word = json!({"word": "apple", "stats": {"review": {"due": "2024-01-01"}}});
+///
+/// remove_pruned(&mut word, "/stats/review/due").unwrap();
+/// assert_eq!(json!({"word": "apple"}), word);
+/// ```
+pub fn remove_pruned(jvalue: &mut JValue, pointer: &str) -> Result<JValue> {
+    let removed = remove_at(jvalue, pointer)?;
+
+    let mut parent = pointer;
+    while let Some((ancestor, _)) = parent.rsplit_once('/') {
+        if ancestor.is_empty() || !jvalue.pointer(ancestor).is_some_and(is_empty_object) {
+            break;
+        }
+        remove_at(jvalue, ancestor)?;
+        parent = ancestor;
+    }
+    Ok(removed)
+}
+
+// Check whether a value is an object without members
+fn is_empty_object(jvalue: &JValue) -> bool {
+    jvalue.as_object().is_some_and(Map::is_empty)
+}
+
+/// Replace a value denoted by a pointer and return the previous one.
+///
+/// The function is a counterpart of [`incorporate_into`] which does not allow to replace existing
+/// values. The pointer has to denote an existing value, an empty pointer denotes the whole value.
+/// Tokens are unescaped, i.e. `~1` denotes `/` and `~0` denotes `~`.
+///
+/// # Errors
+/// The function returns a custom error in case invalid pointer string was passed or the pointer
+/// does not denote an existing value.
+///
+/// # Examples
+/// ```
+/// use serde_json::json;
+/// use db::jutil::replace_at;
+///
+/// let mut word = json!({"word": "apple", "level": 1});
+///
+/// assert_eq!(json!(1), replace_at(&mut word, "/level", json!(2)).unwrap());
+/// assert_eq!(json!({"word": "apple", "level": 2}), word);
+/// assert!(replace_at(&mut word, "/tags", json!([])).is_err());
+/// ```
+pub fn replace_at(jvalue: &mut JValue, pointer: &str, new: JValue) -> Result<JValue> {
+    match pointer_complement_mut(jvalue, pointer)? {
+        (complement, target) if complement.is_empty() => Ok(std::mem::replace(target, new)),
+        _ => Err(missing_value(pointer)),
+    }
+}
+
+/// Escape a reference token, so it may be used as a part of a JSON pointer.
+///
+/// Characters `~` and `/` are replaced with `~0` and `~1` sequences respectively.
+///
+/// # Examples
+/// ```
+/// use db::jutil::escape_token;
+///
+/// let pointer = format!("/{}", escape_token("a/b~c"));
+/// assert_eq!("/a~1b~0c", pointer);
+/// ```
+#[must_use]
+pub fn escape_token(token: &str) -> String {
+    token.replace('~', "~0").replace('/', "~1")
+}
+
+// Unescape a reference token of a JSON pointer
+fn unescape_token(token: &str) -> String {
+    token.replace("~1", "/").replace("~0", "~")
+}
+
+// Return an error describing a pointer which does not denote any value
+fn missing_value(pointer: &str) -> Error {
+    Error::custom_err(
+        CustomKind::Json,
+        &format!("Pointer '{}' does not denote any value", pointer),
+    )
+}
+
+// Return a value denoted by an existing pointer
+fn existing_value<'a>(jvalue: &'a JValue, pointer: &str) -> Result<&'a JValue> {
+    match pointer_complement(jvalue, pointer)? {
+        (complement, value) if complement.is_empty() => Ok(value),
+        _ => Err(missing_value(pointer)),
+    }
+}
+
+// Return a mutable reference to the parent of a value denoted by a pointer, together with
+// the unescaped last token of the pointer. The parent has to exist
+fn parent_mut<'a>(jvalue: &'a mut JValue, pointer: &str) -> Result<(&'a mut JValue, String)> {
+    let Some((parent_pointer, token)) = pointer.rsplit_once('/') else {
+        return Err(Error::custom_err(
+            CustomKind::Json,
+            &format!("Pointer '{}' does not denote a child value", pointer),
+        ));
+    };
+    match pointer_complement_mut(jvalue, parent_pointer)? {
+        (complement, parent) if complement.is_empty() => Ok((parent, unescape_token(token))),
+        _ => Err(missing_value(parent_pointer)),
+    }
+}
+
+// Parse an array index. Indices equal to the array length are accepted only if `append` is set,
+// in which case '-' denotes the length as well
+fn array_index(token: &str, len: usize, append: bool) -> Result<usize> {
+    let index = match token {
+        "-" if append => Some(len),
+        // Leading zeros are not allowed
+        _ if token.len() > 1 && token.starts_with('0') => None,
+        _ if token.bytes().all(|byte| byte.is_ascii_digit()) => token.parse().ok(),
+        _ => None,
+    };
+    match index {
+        Some(index) if index < len || (append && index == len) => Ok(index),
+        _ => Err(Error::custom_err(
+            CustomKind::Json,
+            &format!("Array index '{}' is out of bounds", token),
+        )),
+    }
+}
+
+// Add a value, replacing an existing object member. Array items are shifted instead
+fn patch_add(jvalue: &mut JValue, pointer: &str, value: JValue) -> Result<()> {
+    if pointer.is_empty() {
+        *jvalue = value;
+        return Ok(());
+    }
+
+    let (parent, token) = parent_mut(jvalue, pointer)?;
+    match parent {
+        JValue::Object(object) => {
+            object.insert(token, value);
+        }
+        JValue::Array(array) => {
+            let index = array_index(&token, array.len(), true)?;
+            array.insert(index, value);
+        }
+        _ => {
+            return Err(Error::custom_err(
+                CustomKind::Json,
+                &format!(
+                    "Cannot add '{}' since its parent is neither an array nor object",
+                    pointer
+                ),
+            ))
+        }
+    }
+    Ok(())
+}
+
+// Apply a single operation of a JSON Patch
+fn patch_operation(jvalue: &mut JValue, operation: &JValue) -> Result<()> {
+    // Return a member of the operation object
+    let member = |name: &str| {
+        operation.get(name).ok_or_else(|| {
+            Error::custom_err(
+                CustomKind::Json,
+                &format!("Operation does not have '{}' member", name),
+            )
+        })
+    };
+    // Return a member of the operation object holding a pointer
+    let pointer = |name: &str| {
+        member(name)?.as_str().ok_or_else(|| {
+            Error::custom_err(
+                CustomKind::Json,
+                &format!("Member '{}' of operation is not a string", name),
+            )
+        })
+    };
+
+    let path = pointer("path")?;
+    match member("op")?.as_str() {
+        Some("add") => patch_add(jvalue, path, member("value")?.clone()),
+        Some("remove") => remove_at(jvalue, path).map(|_| ()),
+        Some("replace") => replace_at(jvalue, path, member("value")?.clone()).map(|_| ()),
+        Some("move") => {
+            let from = pointer("from")?;
+            if path.starts_with(from) && path[from.len()..].starts_with('/') {
+                return Err(Error::custom_err(
+                    CustomKind::Json,
+                    &format!("Cannot move '{}' into its own child '{}'", from, path),
+                ));
+            }
+            let value = remove_at(jvalue, from)?;
+            patch_add(jvalue, path, value)
+        }
+        Some("copy") => {
+            let value = existing_value(jvalue, pointer("from")?)?.clone();
+            patch_add(jvalue, path, value)
+        }
+        Some("test") => {
+            let expected = member("value")?;
+            if equal(existing_value(jvalue, path)?, expected) {
+                Ok(())
+            } else {
+                Err(Error::custom_err(
+                    CustomKind::Json,
+                    &format!("Value at '{}' is not equal to {}", path, expected),
+                ))
+            }
+        }
+        _ => Err(Error::custom_err(
+            CustomKind::Json,
+            &format!("Operation {} is not supported", member("op")?),
+        )),
+    }
+}
+
+/// Apply a JSON Patch (RFC 6902) to a JSON value.
+///
+/// The patch is an array of operation objects, each one holding an `op` member which is one of
+/// `add`, `remove`, `replace`, `move`, `copy` or `test`, a `path` member which is a JSON pointer
+/// and either a `value` or a `from` member depending on the operation. Operations are applied in
+/// order. Tokens of pointers have to be escaped, i.e. `~0` denotes `~` and `~1` denotes `/`.
+/// Values are tested for equality as described in [`equal`].
+///
+/// The patch is applied atomically. If any operation fails, including a failed `test`, the value
+/// is left unchanged.
+///
+/// # Errors
+/// The function returns [`CustomKind::Json`] error naming the failed operation in case the patch
+/// is malformed or any of its operations cannot be applied.
+///
+/// # Examples
+/// ```
+/// use serde_json::json;
+/// use db::jutil::apply_patch;
+///
+/// let mut word = json!({"word": "apple", "tags": ["fruit"]});
+/// let patch = json!([
+///     {"op": "test", "path": "/word", "value": "apple"},
+///     {"op": "add", "path": "/tags/-", "value": "food"},
+///     {"op": "move", "from": "/word", "path": "/name"}
+/// ]);
+///
+/// apply_patch(&mut word, &patch).unwrap();
+/// assert_eq!(json!({"name": "apple", "tags": ["fruit", "food"]}), word);
+///
+/// // Nothing is applied when any operation fails
+/// let patch = json!([
+///     {"op": "remove", "path": "/tags/0"},
+///     {"op": "remove", "path": "/word"}
+/// ]);
+/// assert!(apply_patch(&mut word, &patch).is_err());
+/// assert_eq!(json!({"name": "apple", "tags": ["fruit", "food"]}), word);
+/// ```
+pub fn apply_patch(jvalue: &mut JValue, patch: &JValue) -> Result<()> {
+    let Some(operations) = patch.as_array() else {
+        return Err(Error::custom_err(
+            CustomKind::Json,
+            "Patch is not an array of operations",
+        ));
+    };
+
+    let mut patched = jvalue.clone();
+    for (i, operation) in operations.iter().enumerate() {
+        if let Err(err) = patch_operation(&mut patched, operation) {
+            return Err(Error::custom_err(
+                CustomKind::Json,
+                &format!("Patch operation {} has failed. {}", i, err),
+            ));
+        }
+    }
+
+    *jvalue = patched;
+    Ok(())
+}
+
+// Rank of a JSON type, used to order values of different types
+fn type_rank(value: &JValue) -> u8 {
+    match value {
+        JValue::Null => 0,
+        JValue::Bool(_) => 1,
+        JValue::Number(_) => 2,
+        JValue::String(_) => 3,
+        JValue::Array(_) => 4,
+        JValue::Object(_) => 5,
+    }
+}
+
+// Compare two JSON numbers. Integers are compared exactly, since distinct integers beyond 2^53
+// may have the same floating point representation
+fn compare_numbers(lhs: &Number, rhs: &Number) -> Option<Ordering> {
+    let integer = |number: &Number| {
+        number
+            .as_i64()
+            .map(i128::from)
+            .or_else(|| number.as_u64().map(i128::from))
+    };
+    match (integer(lhs), integer(rhs)) {
+        (Some(lhs), Some(rhs)) => Some(lhs.cmp(&rhs)),
+        (Some(lhs), None) => compare_integer_to_float(lhs, rhs.as_f64()?),
+        (None, Some(rhs)) => compare_integer_to_float(rhs, lhs.as_f64()?).map(Ordering::reverse),
+        (None, None) => lhs.as_f64()?.partial_cmp(&rhs.as_f64()?),
+    }
+}
+
+// Compare an integer to a float. Rounding the integer preserves the order, unless both values
+// end up equal, in which case the float has no fractional part and may be compared as an integer
+#[allow(clippy::cast_precision_loss, clippy::cast_possible_truncation)]
+fn compare_integer_to_float(integer: i128, float: f64) -> Option<Ordering> {
+    match (integer as f64).partial_cmp(&float)? {
+        Ordering::Equal => Some(integer.cmp(&(float as i128))),
+        ordering => Some(ordering),
+    }
+}
+
+/// Compare two JSON values of the same type.
+///
+/// Numbers, strings and booleans are compared by their values, nulls are always equal. Integers
+/// are compared exactly, regardless of their magnitude.
+/// None is returned if values have different types or are not comparable (arrays and objects).
+#[must_use]
+pub fn compare(lhs: &JValue, rhs: &JValue) -> Option<Ordering> {
+    match (lhs, rhs) {
+        (JValue::Null, JValue::Null) => Some(Ordering::Equal),
+        (JValue::Bool(lhs), JValue::Bool(rhs)) => Some(lhs.cmp(rhs)),
+        (JValue::Number(lhs), JValue::Number(rhs)) => compare_numbers(lhs, rhs),
+        (JValue::String(lhs), JValue::String(rhs)) => Some(lhs.cmp(rhs)),
+        _ => None,
+    }
+}
+
+/// Compare two arbitrary JSON values.
+///
+/// Values of different types are ordered by their types: null, boolean, number, string, array and
+/// object. Values of the same type are ordered by [`compare`], except for arrays and objects which
+/// are compared by their serialized representation.
+#[must_use]
+pub fn total_compare(lhs: &JValue, rhs: &JValue) -> Ordering {
+    type_rank(lhs)
+        .cmp(&type_rank(rhs))
+        .then_with(|| compare(lhs, rhs).unwrap_or_else(|| lhs.to_string().cmp(&rhs.to_string())))
+}
+
+/// Check whether two JSON values are equal.
+///
+/// Unlike `==`, numbers are compared by their values, also when nested inside arrays and objects,
+/// hence `[1]` is equal to `[1.0]`. Arrays are compared item by item and objects member by member.
+#[must_use]
+pub fn equal(lhs: &JValue, rhs: &JValue) -> bool {
+    match (lhs, rhs) {
+        (JValue::Array(lhs), JValue::Array(rhs)) => {
+            lhs.len() == rhs.len() && lhs.iter().zip(rhs).all(|(lhs, rhs)| equal(lhs, rhs))
+        }
+        (JValue::Object(lhs), JValue::Object(rhs)) => {
+            lhs.len() == rhs.len()
+                && lhs
+                    .iter()
+                    .all(|(key, lhs)| rhs.get(key).is_some_and(|rhs| equal(lhs, rhs)))
+        }
+        _ => compare(lhs, rhs) == Some(Ordering::Equal),
+    }
+}
+
+/// Apply a JSON Merge Patch (RFC 7396) to a JSON value.
+///
+/// Members of a patch object replace corresponding members of the target object, whereas members
+/// set to `null` are removed from it. Nested objects are patched recursively. Any other patch,
+/// including an array, replaces the target entirely. Unlike [`incorporate_into`], existing values
+/// are replaced, which makes the function suitable for sending only the changed fields.
+///
+/// # Examples
+/// ```
+/// use serde_json::json;
+/// use db::jutil::merge_patch;
+///
+/// let mut word = json!({"word": "apple", "level": 1, "meta": {"source": "book", "page": 4}});
+/// merge_patch(&mut word, json!({"level": 2, "meta": {"page": null}}));
+/// assert_eq!(json!({"word": "apple", "level": 2, "meta": {"source": "book"}}), word);
+/// ```
+pub fn merge_patch(target: &mut JValue, patch: JValue) {
+    let JValue::Object(patch) = patch else {
+        *target = patch;
+        return;
+    };
+
+    if !target.is_object() {
+        *target = JValue::Object(Map::new());
+    }
+    let Some(object) = target.as_object_mut() else {
+        return;
+    };
+    for (key, value) in patch {
+        if value.is_null() {
+            object.remove(&key);
+        } else {
+            merge_patch(object.entry(key).or_insert(JValue::Null), value);
+        }
+    }
+}
+
+/// Strategy of merging arrays used by [`deep_merge`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArrayMerge {
+    /// Source array replaces the target one
+    Replace,
+    /// Items of source array are appended to the target one
+    Concat,
+}
+
+/// Merge a JSON value into another one recursively.
+///
+/// Objects are merged member by member, so members missing in the source are kept. Arrays are
+/// either replaced or concatenated depending on `arrays`. Any other value, `null` included,
+/// replaces the target value. Use [`merge_patch`] if `null` should remove members instead.
+///
+/// # Examples
+/// ```
+/// use serde_json::json;
+/// use db::jutil::{deep_merge, ArrayMerge};
+///
+/// let mut word = json!({"word": "apple", "tags": ["fruit"], "meta": {"source": "book"}});
+/// let source = json!({"tags": ["food"], "meta": {"page": 4}});
+///
+/// deep_merge(&mut word, source, ArrayMerge::Concat);
+/// assert_eq!(
+///     json!({"word": "apple", "tags": ["fruit", "food"], "meta": {"source": "book", "page": 4}}),
+///     word
+/// );
+/// ```
+pub fn deep_merge(target: &mut JValue, source: JValue, arrays: ArrayMerge) {
+    match (target, source) {
+        (JValue::Object(target), JValue::Object(source)) => {
+            for (key, value) in source {
+                match target.get_mut(&key) {
+                    Some(existing) => deep_merge(existing, value, arrays),
+                    None => {
+                        target.insert(key, value);
+                    }
+                }
+            }
+        }
+        (JValue::Array(target), JValue::Array(source)) if arrays == ArrayMerge::Concat => {
+            target.extend(source);
+        }
+        (target, source) => *target = source,
+    }
+}
+
+// Append an operation to a patch
+fn push_operation(patch: &mut Vec<JValue>, op: &str, pointer: &str, value: Option<&JValue>) {
+    let mut operation = Map::new();
+    operation.insert("op".to_string(), JValue::String(op.to_string()));
+    operation.insert("path".to_string(), JValue::String(pointer.to_string()));
+    if let Some(value) = value {
+        operation.insert("value".to_string(), value.clone());
+    }
+    patch.push(JValue::Object(operation));
+}
+
+// Return pairs of indices of items belonging to the longest common subsequence of two arrays
+fn common_items(lhs: &[JValue], rhs: &[JValue]) -> Vec<(usize, usize)> {
+    // lengths[i][j] holds length of the longest common subsequence of lhs[i..] and rhs[j..]
+    let mut lengths = vec![vec![0_usize; rhs.len() + 1]; lhs.len() + 1];
+    for i in (0..lhs.len()).rev() {
+        for j in (0..rhs.len()).rev() {
+            lengths[i][j] = if lhs[i] == rhs[j] {
+                lengths[i + 1][j + 1] + 1
+            } else {
+                lengths[i + 1][j].max(lengths[i][j + 1])
+            };
+        }
+    }
+
+    let mut pairs = Vec::with_capacity(lengths[0][0]);
+    let (mut i, mut j) = (0, 0);
+    while i < lhs.len() && j < rhs.len() {
+        if lhs[i] == rhs[j] {
+            pairs.push((i, j));
+            i += 1;
+            j += 1;
+        } else if lengths[i + 1][j] >= lengths[i][j + 1] {
+            i += 1;
+        } else {
+            j += 1;
+        }
+    }
+    pairs
+}
+
+// Append operations transforming an array into another. Items which are not a part of the longest
+// common subsequence are diffed pairwise, remaining ones are either removed or added
+fn diff_arrays(patch: &mut Vec<JValue>, pointer: &str, lhs: &[JValue], rhs: &[JValue]) {
+    // Index of the next item within the array altered by operations appended so far
+    let mut index = 0;
+    let (mut i, mut j) = (0, 0);
+    let end = (lhs.len(), rhs.len());
+
+    for (next_i, next_j) in common_items(lhs, rhs).into_iter().chain([end]) {
+        let (removed, added) = (&lhs[i..next_i], &rhs[j..next_j]);
+        let paired = removed.len().min(added.len());
+
+        for (lhs, rhs) in removed.iter().zip(added) {
+            diff_values(patch, &format!("{}/{}", pointer, index), lhs, rhs);
+            index += 1;
+        }
+        for _ in paired..removed.len() {
+            push_operation(patch, "remove", &format!("{}/{}", pointer, index), None);
+        }
+        for value in &added[paired..] {
+            push_operation(patch, "add", &format!("{}/{}", pointer, index), Some(value));
+            index += 1;
+        }
+
+        // Skip the common item
+        index += 1;
+        (i, j) = (next_i + 1, next_j + 1);
+    }
+}
+
+// Append operations transforming a value into another
+fn diff_values(patch: &mut Vec<JValue>, pointer: &str, lhs: &JValue, rhs: &JValue) {
+    match (lhs, rhs) {
+        _ if lhs == rhs => {}
+        (JValue::Object(lhs), JValue::Object(rhs)) => {
+            for (key, value) in lhs {
+                let child = format!("{}/{}", pointer, escape_token(key));
+                match rhs.get(key) {
+                    Some(other) => diff_values(patch, &child, value, other),
+                    None => push_operation(patch, "remove", &child, None),
+                }
+            }
+            for (key, value) in rhs.iter().filter(|(key, _)| !lhs.contains_key(*key)) {
+                let child = format!("{}/{}", pointer, escape_token(key));
+                push_operation(patch, "add", &child, Some(value));
+            }
+        }
+        (JValue::Array(lhs), JValue::Array(rhs)) => diff_arrays(patch, pointer, lhs, rhs),
+        _ => push_operation(patch, "replace", pointer, Some(rhs)),
+    }
+}
+
+/// Compute a JSON Patch (RFC 6902) transforming a JSON value into another.
+///
+/// Applying the returned patch to `lhs` with [`apply_patch`] results in `rhs`. The patch
+/// consists of `add`, `remove` and `replace` operations only. Objects are compared member by
+/// member, whereas arrays are compared using the longest common subsequence of their items, thus
+/// inserting or removing a single item results in a single operation rather than replacing all
+/// subsequent items.
+///
+/// # Examples
+/// ```
+/// use serde_json::json;
+/// use db::jutil::{apply_patch, diff};
+///
+/// let mut old = json!({"word": "apple", "tags": ["fruit", "red"], "level": 1});
+/// let new = json!({"word": "apple", "tags": ["food", "fruit", "red"], "note": "tasty"});
+///
+/// let patch = diff(&old, &new);
+/// assert_eq!(
+///     json!([
+///         {"op": "remove", "path": "/level"},
+///         {"op": "add", "path": "/tags/0", "value": "food"},
+///         {"op": "add", "path": "/note", "value": "tasty"}
+///     ]),
+///     patch
+/// );
+///
+/// apply_patch(&mut old, &patch).unwrap();
+/// assert_eq!(new, old);
+/// ```
+#[must_use]
+pub fn diff(lhs: &JValue, rhs: &JValue) -> JValue {
+    let mut patch = Vec::new();
+    diff_values(&mut patch, "", lhs, rhs);
+    JValue::Array(patch)
+}
+
+// A segment of a JSONPath expression
+#[derive(Debug)]
+enum PathSegment {
+    // Selects children of matched values
+    Child(Vec<PathSelector>),
+    // Selects children of matched values and all their descendants
+    Descendant(Vec<PathSelector>),
+}
+
+// A selector of a JSONPath segment
+#[derive(Debug)]
+enum PathSelector {
+    Name(String),
+    Wildcard,
+    Index(i64),
+    // Start, end and step of a slice
+    Slice(Option<i64>, Option<i64>, i64),
+    Filter(PathFilter),
+}
+
+// A logical expression of a JSONPath filter selector
+#[derive(Debug)]
+enum PathFilter {
+    Or(Box<Self>, Box<Self>),
+    And(Box<Self>, Box<Self>),
+    Not(Box<Self>),
+    // Query selecting at least one value
+    Exists(PathOperand),
+    Compare(PathOperand, PathComparison, PathOperand),
+}
+
+// A comparison operator of a JSONPath filter
+#[derive(Debug, Clone, Copy)]
+enum PathComparison {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+// An operand of a JSONPath filter
+#[derive(Debug)]
+enum PathOperand {
+    Literal(JValue),
+    // Query relative to either the root value ($) or the current one (@)
+    Query(bool, Vec<PathSegment>),
+}
+
+// A value selected by a JSONPath expression together with a JSON pointer denoting it
+type PathNode<'a> = (String, &'a JValue);
+
+// Recursive descent parser of JSONPath expressions
+struct PathParser<'a> {
+    path: &'a str,
+    chars: Vec<char>,
+    pos: usize,
+}
+
+impl<'a> PathParser<'a> {
+    const COMPARISONS: [(&'static str, PathComparison); 6] = [
+        ("==", PathComparison::Eq),
+        ("!=", PathComparison::Ne),
+        ("<=", PathComparison::Le),
+        (">=", PathComparison::Ge),
+        ("<", PathComparison::Lt),
+        (">", PathComparison::Gt),
+    ];
+
+    fn new(path: &'a str) -> Self {
+        Self {
+            path,
+            chars: path.chars().collect(),
+            pos: 0,
+        }
+    }
+
+    fn error(&self, problem: &str) -> Error {
+        Error::custom_err(
+            CustomKind::Json,
+            &format!(
+                "JSONPath '{}' is invalid at position {}: {}",
+                self.path, self.pos, problem
+            ),
+        )
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).copied()
+    }
+
+    // Consume a character if it comes next
+    fn eat(&mut self, expected: char) -> bool {
+        let found = self.peek() == Some(expected);
+        if found {
+            self.pos += 1;
+        }
+        found
+    }
+
+    // Consume a string if it comes next
+    fn eat_str(&mut self, expected: &str) -> bool {
+        let found = expected
+            .chars()
+            .enumerate()
+            .all(|(i, c)| self.chars.get(self.pos + i) == Some(&c));
+        if found {
+            self.pos += expected.chars().count();
+        }
+        found
+    }
+
+    fn expect(&mut self, expected: char) -> Result<()> {
+        if self.eat(expected) {
+            Ok(())
+        } else {
+            Err(self.error(&format!("expected '{}'", expected)))
+        }
+    }
+
+    fn skip_blanks(&mut self) {
+        while self.peek().is_some_and(char::is_whitespace) {
+            self.pos += 1;
+        }
+    }
+
+    // Parse a whole expression which starts with the root identifier
+    fn parse(mut self) -> Result<Vec<PathSegment>> {
+        self.expect('$')?;
+        let segments = self.segments()?;
+        self.peek().map_or(Ok(segments), |c| {
+            Err(self.error(&format!("unexpected character '{}'", c)))
+        })
+    }
+
+    fn segments(&mut self) -> Result<Vec<PathSegment>> {
+        let mut segments = Vec::new();
+        loop {
+            let segment = if self.eat_str("..") {
+                match self.peek() {
+                    Some('[') => PathSegment::Descendant(self.brackets()?),
+                    _ => PathSegment::Descendant(self.shorthand()?),
+                }
+            } else if self.eat('.') {
+                PathSegment::Child(self.shorthand()?)
+            } else if self.peek() == Some('[') {
+                PathSegment::Child(self.brackets()?)
+            } else {
+                return Ok(segments);
+            };
+            segments.push(segment);
+        }
+    }
+
+    // Parse either a wildcard or a member name following a dot
+    fn shorthand(&mut self) -> Result<Vec<PathSelector>> {
+        if self.eat('*') {
+            return Ok(vec![PathSelector::Wildcard]);
+        }
+
+        let start = self.pos;
+        while self
+            .peek()
+            .is_some_and(|c| c.is_alphanumeric() || c == '_' || !c.is_ascii())
+        {
+            self.pos += 1;
+        }
+        if start == self.pos {
+            return Err(self.error("expected a member name or '*'"));
+        }
+        Ok(vec![PathSelector::Name(
+            self.chars[start..self.pos].iter().collect(),
+        )])
+    }
+
+    // Parse comma separated selectors enclosed in brackets
+    fn brackets(&mut self) -> Result<Vec<PathSelector>> {
+        self.expect('[')?;
+        let mut selectors = Vec::new();
+        loop {
+            self.skip_blanks();
+            selectors.push(self.selector()?);
+            self.skip_blanks();
+            if !self.eat(',') {
+                self.expect(']')?;
+                return Ok(selectors);
+            }
+        }
+    }
+
+    fn selector(&mut self) -> Result<PathSelector> {
+        match self.peek() {
+            Some('\'' | '"') => Ok(PathSelector::Name(self.string()?)),
+            Some('*') => {
+                self.pos += 1;
+                Ok(PathSelector::Wildcard)
+            }
+            Some('?') => {
+                self.pos += 1;
+                Ok(PathSelector::Filter(self.or_filter()?))
+            }
+            _ => {
+                let start = self.integer()?;
+                self.skip_blanks();
+                if !self.eat(':') {
+                    return start
+                        .map(PathSelector::Index)
+                        .ok_or_else(|| self.error("expected a selector"));
+                }
+                self.skip_blanks();
+                let end = self.integer()?;
+                self.skip_blanks();
+                let step = if self.eat(':') {
+                    self.skip_blanks();
+                    self.integer()?
+                } else {
+                    None
+                };
+                Ok(PathSelector::Slice(start, end, step.unwrap_or(1)))
+            }
+        }
+    }
+
+    // Parse an optional integer
+    fn integer(&mut self) -> Result<Option<i64>> {
+        let start = self.pos;
+        self.eat('-');
+        while self.peek().is_some_and(|c| c.is_ascii_digit()) {
+            self.pos += 1;
+        }
+
+        let digits: String = self.chars[start..self.pos].iter().collect();
+        match digits.as_str() {
+            "" => Ok(None),
+            _ => digits
+                .parse()
+                .map(Some)
+                .map_err(|_| self.error("expected an integer")),
+        }
+    }
+
+    // Parse a quoted string, either single or double quoted
+    fn string(&mut self) -> Result<String> {
+        let quote = self.peek();
+        self.pos += 1;
+
+        let mut string = String::new();
+        loop {
+            let c = self
+                .peek()
+                .ok_or_else(|| self.error("unterminated string"))?;
+            self.pos += 1;
+            if Some(c) == quote {
+                return Ok(string);
+            }
+            if c != '\\' {
+                string.push(c);
+                continue;
+            }
+
+            let escaped = self
+                .peek()
+                .ok_or_else(|| self.error("unterminated string"))?;
+            self.pos += 1;
+            string.push(match escaped {
+                'b' => '\u{8}',
+                'f' => '\u{c}',
+                'n' => '\n',
+                'r' => '\r',
+                't' => '\t',
+                'u' => {
+                    let hex: String = self.chars.iter().skip(self.pos).take(4).collect();
+                    self.pos += 4;
+                    u32::from_str_radix(&hex, 16)
+                        .ok()
+                        .and_then(char::from_u32)
+                        .ok_or_else(|| self.error("invalid unicode escape"))?
+                }
+                '\\' | '/' | '\'' | '"' => escaped,
+                _ => return Err(self.error("invalid escape sequence")),
+            });
+        }
+    }
+
+    fn or_filter(&mut self) -> Result<PathFilter> {
+        let mut filter = self.and_filter()?;
+        loop {
+            self.skip_blanks();
+            if !self.eat_str("||") {
+                return Ok(filter);
+            }
+            filter = PathFilter::Or(Box::new(filter), Box::new(self.and_filter()?));
+        }
+    }
+
+    fn and_filter(&mut self) -> Result<PathFilter> {
+        let mut filter = self.unary_filter()?;
+        loop {
+            self.skip_blanks();
+            if !self.eat_str("&&") {
+                return Ok(filter);
+            }
+            filter = PathFilter::And(Box::new(filter), Box::new(self.unary_filter()?));
+        }
+    }
+
+    fn unary_filter(&mut self) -> Result<PathFilter> {
+        self.skip_blanks();
+        if self.eat('!') {
+            return Ok(PathFilter::Not(Box::new(self.unary_filter()?)));
+        }
+        if self.eat('(') {
+            let filter = self.or_filter()?;
+            self.skip_blanks();
+            self.expect(')')?;
+            return Ok(filter);
+        }
+
+        let lhs = self.operand()?;
+        self.skip_blanks();
+        let comparison = Self::COMPARISONS
+            .iter()
+            .find(|(operator, _)| self.eat_str(operator))
+            .map(|(_, comparison)| *comparison);
+        match (comparison, lhs) {
+            (Some(comparison), lhs) => {
+                self.skip_blanks();
+                Ok(PathFilter::Compare(lhs, comparison, self.operand()?))
+            }
+            (None, query @ PathOperand::Query(..)) => Ok(PathFilter::Exists(query)),
+            (None, PathOperand::Literal(_)) => Err(self.error("expected a comparison")),
+        }
+    }
+
+    fn operand(&mut self) -> Result<PathOperand> {
+        match self.peek() {
+            Some('@') => {
+                self.pos += 1;
+                Ok(PathOperand::Query(false, self.segments()?))
+            }
+            Some('$') => {
+                self.pos += 1;
+                Ok(PathOperand::Query(true, self.segments()?))
+            }
+            Some('\'' | '"') => Ok(PathOperand::Literal(JValue::String(self.string()?))),
+            Some(c) if c == '-' || c.is_ascii_digit() => {
+                let start = self.pos;
+                while self
+                    .peek()
+                    .is_some_and(|c| c.is_ascii_digit() || "+-.eE".contains(c))
+                {
+                    self.pos += 1;
+                }
+                let number: String = self.chars[start..self.pos].iter().collect();
+                match serde_json::from_str(&number) {
+                    Ok(number @ JValue::Number(_)) => Ok(PathOperand::Literal(number)),
+                    _ => Err(self.error("invalid number")),
+                }
+            }
+            _ => [
+                ("true", JValue::Bool(true)),
+                ("false", JValue::Bool(false)),
+                ("null", JValue::Null),
+            ]
+            .into_iter()
+            .find(|(keyword, _)| self.eat_str(keyword))
+            .map(|(_, literal)| PathOperand::Literal(literal))
+            .ok_or_else(|| self.error("expected an operand")),
+        }
+    }
+}
+
+// Return indices of array items selected by a slice
+fn slice_indices(len: usize, start: Option<i64>, end: Option<i64>, step: i64) -> Vec<usize> {
+    let len = i64::try_from(len).unwrap_or(i64::MAX);
+    let normalize = |index: i64| if index >= 0 { index } else { len + index };
+
+    let mut indices = Vec::new();
+    if step > 0 {
+        let lower = normalize(start.unwrap_or(0)).clamp(0, len);
+        let upper = normalize(end.unwrap_or(len)).clamp(0, len);
+        let mut i = lower;
+        while i < upper {
+            indices.extend(usize::try_from(i));
+            i = match i.checked_add(step) {
+                Some(next) => next,
+                None => break,
+            };
+        }
+    } else if step < 0 {
+        let upper = normalize(start.unwrap_or(len - 1)).clamp(-1, len - 1);
+        let lower = normalize(end.unwrap_or(-len - 1)).clamp(-1, len - 1);
+        let mut i = upper;
+        while lower < i {
+            indices.extend(usize::try_from(i));
+            i = match i.checked_add(step) {
+                Some(next) => next,
+                None => break,
+            };
+        }
+    }
+    indices
+}
+
+// Return children of a value, i.e. members of an object or items of an array
+fn path_children<'a>((pointer, jvalue): &PathNode<'a>) -> Vec<PathNode<'a>> {
+    match jvalue {
+        JValue::Object(object) => object
+            .iter()
+            .map(|(key, child)| (format!("{}/{}", pointer, escape_token(key)), child))
+            .collect(),
+        JValue::Array(array) => array
+            .iter()
+            .enumerate()
+            .map(|(i, child)| (format!("{}/{}", pointer, i), child))
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+// Return a value followed by all its descendants, in document order
+fn path_descendants(node: PathNode<'_>) -> Vec<PathNode<'_>> {
+    let mut descendants = Vec::new();
+    let mut stack = vec![node];
+    while let Some(node) = stack.pop() {
+        stack.extend(path_children(&node).into_iter().rev());
+        descendants.push(node);
+    }
+    descendants
+}
+
+// Apply a selector to a value
+fn path_select<'a>(
+    root: &'a JValue,
+    node: &PathNode<'a>,
+    selector: &PathSelector,
+) -> Vec<PathNode<'a>> {
+    let (pointer, jvalue) = node;
+    match (selector, jvalue) {
+        (PathSelector::Name(name), JValue::Object(object)) => object
+            .get(name)
+            .map(|child| (format!("{}/{}", pointer, escape_token(name)), child))
+            .into_iter()
+            .collect(),
+        (PathSelector::Wildcard, _) => path_children(node),
+        (PathSelector::Index(index), JValue::Array(array)) => {
+            let len = i64::try_from(array.len()).unwrap_or(i64::MAX);
+            let index = if *index >= 0 { *index } else { len + index };
+            usize::try_from(index)
+                .ok()
+                .and_then(|index| Some((format!("{}/{}", pointer, index), array.get(index)?)))
+                .into_iter()
+                .collect()
+        }
+        (PathSelector::Slice(start, end, step), JValue::Array(array)) => {
+            slice_indices(array.len(), *start, *end, *step)
+                .into_iter()
+                .map(|i| (format!("{}/{}", pointer, i), &array[i]))
+                .collect()
+        }
+        (PathSelector::Filter(filter), _) => path_children(node)
+            .into_iter()
+            .filter(|(_, child)| path_test(root, child, filter))
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+// Apply segments of a JSONPath expression to a value
+fn path_evaluate<'a>(
+    root: &'a JValue,
+    start: PathNode<'a>,
+    segments: &[PathSegment],
+) -> Vec<PathNode<'a>> {
+    segments.iter().fold(vec![start], |nodes, segment| {
+        let (selectors, nodes) = match segment {
+            PathSegment::Child(selectors) => (selectors, nodes),
+            PathSegment::Descendant(selectors) => (
+                selectors,
+                nodes.into_iter().flat_map(path_descendants).collect(),
+            ),
+        };
+        nodes
+            .iter()
+            .flat_map(|node| {
+                selectors
+                    .iter()
+                    .flat_map(|selector| path_select(root, node, selector))
+            })
+            .collect()
+    })
+}
+
+// Return a value of a filter operand. Queries have a value only if they select exactly one value
+fn path_operand<'a>(
+    root: &'a JValue,
+    current: &'a JValue,
+    operand: &'a PathOperand,
+) -> Option<&'a JValue> {
+    match operand {
+        PathOperand::Literal(literal) => Some(literal),
+        PathOperand::Query(absolute, segments) => {
+            let start = if *absolute { root } else { current };
+            match path_evaluate(root, (String::new(), start), segments).as_slice() {
+                [(_, jvalue)] => Some(jvalue),
+                _ => None,
+            }
+        }
+    }
+}
+
+// Check whether a value satisfies a filter
+fn path_test(root: &JValue, current: &JValue, filter: &PathFilter) -> bool {
+    match filter {
+        PathFilter::Or(lhs, rhs) => path_test(root, current, lhs) || path_test(root, current, rhs),
+        PathFilter::And(lhs, rhs) => path_test(root, current, lhs) && path_test(root, current, rhs),
+        PathFilter::Not(filter) => !path_test(root, current, filter),
+        PathFilter::Exists(PathOperand::Query(absolute, segments)) => {
+            let start = if *absolute { root } else { current };
+            !path_evaluate(root, (String::new(), start), segments).is_empty()
+        }
+        PathFilter::Exists(PathOperand::Literal(_)) => false,
+        PathFilter::Compare(lhs, comparison, rhs) => {
+            let lhs = path_operand(root, current, lhs);
+            let rhs = path_operand(root, current, rhs);
+            // Missing values are equal to each other only
+            let equal = match (lhs, rhs) {
+                (None, None) => true,
+                (Some(lhs), Some(rhs)) => compare(lhs, rhs).map_or(lhs == rhs, Ordering::is_eq),
+                _ => false,
+            };
+            let ordering = lhs.zip(rhs).and_then(|(lhs, rhs)| compare(lhs, rhs));
+            match comparison {
+                PathComparison::Eq => equal,
+                PathComparison::Ne => !equal,
+                PathComparison::Lt => ordering == Some(Ordering::Less),
+                PathComparison::Le => equal || ordering == Some(Ordering::Less),
+                PathComparison::Gt => ordering == Some(Ordering::Greater),
+                PathComparison::Ge => equal || ordering == Some(Ordering::Greater),
+            }
+        }
+    }
+}
+
+/// Select values matching a `JSONPath` expression.
+///
+/// The function returns selected values in document order together with JSON pointers denoting
+/// them, thus the pointers may be passed to [`pointer_complement_mut`] or
+/// [`serde_json::Value::pointer_mut`] to edit the values in place. The following syntax is
+/// supported:
+///
+/// - `$` denoting the root value, `@` denoting the current value inside filters
+/// - `.name`, `['name']` and `["name"]` selecting a member of an object
+/// - `.*` and `[*]` selecting all members of an object or items of an array
+/// - `[1]` and `[-1]` selecting an array item, counted from the end if negative
+/// - `[start:end:step]` selecting a slice of an array, every part being optional
+/// - `[?<filter>]` selecting members or items satisfying a filter, which compares values using
+///   `==`, `!=`, `<`, `<=`, `>` and `>=`, checks existence of a value (e.g. `@.tags`) and combines
+///   conditions using `&&`, `||`, `!` and parentheses
+/// - `..` selecting descendants, e.g. `$..tags[*]`
+/// - `[a, b]` combining multiple selectors
+///
+/// # Errors
+/// The function returns a custom error in case the expression has invalid syntax.
+///
+/// # Examples
+/// ```
+/// use serde_json::json;
+/// use db::jutil::{json_path, pointer_complement_mut};
+///
+/// let mut words = json!({
+///     "apple": {"level": 1, "tags": ["fruit"]},
+///     "house": {"level": 3, "tags": ["building", "home"]}
+/// });
+///
+/// let tags = json_path(&words, "$..tags[*]").unwrap();
+/// assert_eq!(
+///     vec![("/apple/tags/0", "fruit"), ("/house/tags/0", "building"), ("/house/tags/1", "home")],
+///     tags.iter()
+///         .map(|(pointer, tag)| (pointer.as_str(), tag.as_str().unwrap()))
+///         .collect::<Vec<_>>()
+/// );
+///
+/// let pointers: Vec<String> = json_path(&words, "$[?(@.level > 2)].level")
+///     .unwrap()
+///     .into_iter()
+///     .map(|(pointer, _)| pointer)
+///     .collect();
+/// for pointer in pointers {
+///     let (_, level) = pointer_complement_mut(&mut words, &pointer).unwrap();
+///     *level = json!(2);
+/// }
+/// assert_eq!(json!(2), words["house"]["level"]);
+/// ```
+pub fn json_path<'a>(jvalue: &'a JValue, path: &str) -> Result<Vec<(String, &'a JValue)>> {
+    let segments = PathParser::new(path).parse()?;
+    Ok(path_evaluate(jvalue, (String::new(), jvalue), &segments))
+}
+
+// Wildcard token of a pointer matching every member of an object or item of an array
+const WILDCARD: &str = "*";
+
+// Return values denoted by a pointer which may contain wildcard tokens, in document order
+fn wildcard_nodes<'a>(jvalue: &'a JValue, pointer: &str) -> Result<Vec<PathNode<'a>>> {
+    if !pointer.is_empty() && !pointer.starts_with('/') {
+        return Err(Error::custom_err(
+            CustomKind::Json,
+            &format!("Pointer '{}' does not have valid syntax", pointer),
+        ));
+    }
+
+    let nodes = pointer
+        .split('/')
+        .skip(1)
+        .fold(vec![(String::new(), jvalue)], |nodes, token| {
+            nodes
+                .into_iter()
+                .flat_map(|node| match token {
+                    WILDCARD => path_children(&node),
+                    _ => node
+                        .1
+                        .pointer(&format!("/{}", token))
+                        .map(|child| (format!("{}/{}", node.0, token), child))
+                        .into_iter()
+                        .collect(),
+                })
+                .collect()
+        });
+    Ok(nodes)
+}
+
+/// Expand a pointer containing wildcard tokens into concrete pointers.
+///
+/// A token consisting of a single `*` character matches every member of an object or item of
+/// an array, e.g. `/cards/*/due` denotes the `due` member of every card. Only pointers denoting
+/// existing values are returned, in document order. A pointer without wildcards is returned as
+/// it is, provided that it denotes an existing value.
+///
+/// # Errors
+/// The function returns a custom error in case invalid pointer string was passed.
+///
+/// # Examples
+/// ```
+/// use serde_json::json;
+/// use db::jutil::expand_pointer;
+///
+/// let deck = json!({"cards": [{"due": "2024-01-01"}, {"new": true}, {"due": "2024-02-01"}]});
+/// assert_eq!(
+///     vec!["/cards/0/due", "/cards/2/due"],
+///     expand_pointer(&deck, "/cards/*/due").unwrap()
+/// );
+/// ```
+pub fn expand_pointer(jvalue: &JValue, pointer: &str) -> Result<Vec<String>> {
+    Ok(wildcard_nodes(jvalue, pointer)?
+        .into_iter()
+        .map(|(pointer, _)| pointer)
+        .collect())
+}
+
+/// Get all values denoted by a pointer which may contain wildcard tokens.
+///
+/// Values are returned in document order together with concrete pointers denoting them
+/// (see [`expand_pointer`]).
+///
+/// # Errors
+/// The function returns a custom error in case invalid pointer string was passed.
+///
+/// # Examples
+/// ```
+/// use serde_json::json;
+/// use db::jutil::get_all;
+///
+/// let deck = json!({"cards": {"apple": {"due": 1}, "house": {"due": 2}}});
+/// let due: Vec<_> = get_all(&deck, "/cards/*/due").unwrap().into_iter().map(|(_, due)| due).collect();
+/// assert_eq!(vec![&json!(1), &json!(2)], due);
+/// ```
+pub fn get_all<'a>(jvalue: &'a JValue, pointer: &str) -> Result<Vec<(String, &'a JValue)>> {
+    wildcard_nodes(jvalue, pointer)
+}
+
+/// Set all values denoted by a pointer which may contain wildcard tokens.
+///
+/// Wildcards are expanded against existing values, whereas tokens following the last wildcard
+/// do not need to exist, so e.g. `/cards/*/due` sets the `due` member of every card, either
+/// replacing it or adding it if a card does not have one. Missing values are added in the same way
+/// as by [`incorporate_into`]. The number of values set is returned.
+///
+/// The function is atomic, i.e. the value is left unchanged if any of the values cannot be set.
+///
+/// # Errors
+/// The function returns a custom error in case invalid pointer string was passed or a value
+/// cannot be added since its parent is neither an array nor object.
+///
+/// # Examples
+/// ```
+/// use serde_json::json;
+/// use db::jutil::set_all;
+///
+/// let mut deck = json!({"cards": [{"due": "2024-01-01"}, {"new": true}]});
+/// assert_eq!(2, set_all(&mut deck, "/cards/*/due", &json!(null)).unwrap());
+/// assert_eq!(json!({"cards": [{"due": null}, {"new": true, "due": null}]}), deck);
+/// ```
+pub fn set_all(jvalue: &mut JValue, pointer: &str, value: &JValue) -> Result<usize> {
+    // Split the pointer after the last wildcard. Only the former part has to exist
+    let tokens: Vec<&str> = pointer.split('/').collect();
+    let split = tokens
+        .iter()
+        .rposition(|token| *token == WILDCARD)
+        .map_or(1, |i| i + 1);
+    let prefix = tokens[..split].join("/");
+    let suffix = tokens[split..]
+        .iter()
+        .fold(String::new(), |suffix, token| suffix + "/" + token);
+
+    let targets: Vec<String> = expand_pointer(jvalue, &prefix)?
+        .into_iter()
+        .map(|target| target + &suffix)
+        .collect();
+    let mut updated = jvalue.clone();
+    for target in &targets {
+        match updated.pointer_mut(target) {
+            Some(existing) => *existing = value.clone(),
+            None => incorporate_into(&mut updated, target, value.clone())?,
+        }
+    }
+
+    *jvalue = updated;
+    Ok(targets.len())
+}
+
+/// Remove all values denoted by a pointer which may contain wildcard tokens.
+///
+/// Removed values are returned in document order together with concrete pointers which denoted
+/// them. Array items are removed starting from the last one, so e.g. `/cards/*/tags/*` empties
+/// tags of every card.
+///
+/// # Errors
+/// The function returns a custom error in case invalid or empty pointer string was passed.
+///
+/// # Examples
+/// ```
+/// use serde_json::json;
+/// use db::jutil::remove_all;
+///
+/// let mut deck = json!({"cards": [{"due": 1, "tags": ["a", "b"]}, {"tags": ["c"]}]});
+/// let removed = remove_all(&mut deck, "/cards/*/tags/*").unwrap();
+/// assert_eq!(3, removed.len());
+/// assert_eq!(json!({"cards": [{"due": 1, "tags": []}, {"tags": []}]}), deck);
+/// ```
+pub fn remove_all(jvalue: &mut JValue, pointer: &str) -> Result<Vec<(String, JValue)>> {
+    if pointer.is_empty() {
+        return Err(Error::custom_err(
+            CustomKind::Json,
+            "Cannot remove the whole value",
+        ));
+    }
+
+    let mut removed = Vec::new();
+    for target in expand_pointer(jvalue, pointer)?.into_iter().rev() {
+        let value = remove_at(jvalue, &target)?;
+        removed.push((target, value));
+    }
+    removed.reverse();
+    Ok(removed)
+}
+
+// Insert leaves of a value into a flat map, under pointers prefixed with the given one
+fn flatten_into(flat: &mut Map<String, JValue>, pointer: String, jvalue: &JValue) {
+    match jvalue {
+        JValue::Object(object) if !object.is_empty() => {
+            for (key, child) in object {
+                flatten_into(flat, format!("{}/{}", pointer, escape_token(key)), child);
+            }
+        }
+        JValue::Array(array) if !array.is_empty() => {
+            for (i, child) in array.iter().enumerate() {
+                flatten_into(flat, format!("{}/{}", pointer, i), child);
+            }
+        }
+        _ => {
+            flat.insert(pointer, jvalue.clone());
+        }
+    }
+}
+
+/// Flatten a nested value into a map from JSON pointers to leaves.
+///
+/// Leaves are scalars as well as empty arrays and objects, so the structure can be rebuilt with
+/// [`unflatten`]. Tokens of pointers are escaped, thus keys containing `/` or `~` and empty keys
+/// are retained. A scalar is flattened into a single leaf under an empty pointer.
+///
+/// Pointers do not tell array items from object members, hence an object whose keys are
+/// consecutive array indices starting from `0`, e.g. `{"0": "a", "1": "b"}`, is rebuilt by
+/// [`unflatten`] as an array.
+///
+/// # Examples
+/// ```
+/// use serde_json::json;
+/// use db::jutil::flatten;
+///
+/// let word = json!({"word": "apple", "tags": ["fruit"], "a/b": {}});
+/// assert_eq!(
+///     json!({"/a~1b": {}, "/tags/0": "fruit", "/word": "apple"}),
+///     serde_json::Value::Object(flatten(&word))
+/// );
+/// ```
+#[must_use]
+pub fn flatten(jvalue: &JValue) -> Map<String, JValue> {
+    let mut flat = Map::new();
+    flatten_into(&mut flat, String::new(), jvalue);
+    flat
+}
+
+// Turn objects whose keys are consecutive array indices starting from 0 into arrays
+fn restore_arrays(jvalue: JValue) -> JValue {
+    let JValue::Object(object) = jvalue else {
+        return jvalue;
+    };
+
+    let mut items: Vec<(Option<usize>, String, JValue)> = object
+        .into_iter()
+        .map(|(key, child)| {
+            // Only canonical indices are accepted, e.g. '01' is not an index
+            let index = key
+                .parse()
+                .ok()
+                .filter(|index: &usize| index.to_string() == key);
+            (index, key, restore_arrays(child))
+        })
+        .collect();
+    items.sort_by_key(|(index, _, _)| *index);
+
+    let is_array = !items.is_empty()
+        && items
+            .iter()
+            .enumerate()
+            .all(|(i, (index, _, _))| *index == Some(i));
+    if is_array {
+        JValue::Array(items.into_iter().map(|(_, _, child)| child).collect())
+    } else {
+        JValue::Object(
+            items
+                .into_iter()
+                .map(|(_, key, child)| (key, child))
+                .collect(),
+        )
+    }
+}
+
+/// Rebuild a nested value from a map produced by [`flatten`].
+///
+/// Every leaf is incorporated into the result under its pointer like by [`incorporate_into`].
+/// Afterwards objects whose keys are consecutive array indices starting from `0` are turned into
+/// arrays, thus an object like `{"0": true}` is rebuilt as an array. An empty map results in an
+/// empty object.
+///
+/// # Errors
+/// The function returns a custom error in case any pointer has invalid syntax or pointers
+/// conflict with each other, e.g. `/a` denotes a scalar and `/a/b` a leaf nested in it.
+///
+/// # Examples
+/// ```
+/// use serde_json::json;
+/// use db::jutil::{flatten, unflatten};
+///
+/// let word = json!({"word": "apple", "tags": ["fruit", "food"], "stats": {"seen": 2}});
+/// assert_eq!(word, unflatten(&flatten(&word)).unwrap());
+///
+/// let flat = json!({"/stats/due": "2024-01-01", "/tags/0": "fruit"});
+/// assert_eq!(
+///     json!({"stats": {"due": "2024-01-01"}, "tags": ["fruit"]}),
+///     unflatten(flat.as_object().unwrap()).unwrap()
+/// );
+/// ```
+pub fn unflatten(flat: &Map<String, JValue>) -> Result<JValue> {
+    if let Some(root) = flat.get("") {
+        if flat.len() > 1 {
+            return Err(Error::custom_err(
+                CustomKind::Json,
+                "Empty pointer denoting the whole value cannot be combined with other pointers",
+            ));
+        }
+        return Ok(root.clone());
+    }
+
+    let mut jvalue = JValue::Object(Map::new());
+    for (pointer, leaf) in flat {
+        incorporate_into(&mut jvalue, pointer, leaf.clone())?;
+    }
+    Ok(restore_arrays(jvalue))
+}
+
+/// Get a value denoted by a pointer, deserialized into a given type.
+///
+/// # Errors
+/// The function returns [`CustomKind::Json`] error in case invalid pointer string was passed,
+/// the pointer does not denote an existing value or the value cannot be deserialized. The error
+/// names both the pointer and the expected type.
+///
+/// # Examples
+/// ```
+/// use serde_json::json;
+/// use db::jutil::get_as;
+///
+/// let word = json!({"word": "apple", "stats": {"seen": 3, "tags": ["fruit"]}});
+///
+/// assert_eq!(3, get_as::<u32>(&word, "/stats/seen").unwrap());
+/// assert_eq!(vec!["fruit"], get_as::<Vec<&str>>(&word, "/stats/tags").unwrap());
+///
+/// let err = get_as::<u32>(&word, "/word").unwrap_err();
+/// assert!(err.to_string().contains("Value at '/word' cannot be read as u32"));
+/// ```
+pub fn get_as<'a, T>(jvalue: &'a JValue, pointer: &str) -> Result<T>
+where
+    T: Deserialize<'a>,
+{
+    T::deserialize(existing_value(jvalue, pointer)?).map_err(|err| {
+        Error::custom_err(
+            CustomKind::Json,
+            &format!(
+                "Value at '{}' cannot be read as {}: {}",
+                pointer,
+                type_name::<T>(),
+                err
+            ),
+        )
+    })
+}
+
+/// Set a value denoted by a pointer, serialized from a given one.
+///
+/// An existing value is replaced. A missing value is added to its parent, where an array item may
+/// be appended using either the array length or `-` as the last token. If the parent is missing as
+/// well, the value is added together with the missing structure like by [`incorporate_into`].
+///
+/// # Errors
+/// The function returns [`CustomKind::Json`] error in case invalid pointer string was passed,
+/// the value cannot be serialized or it cannot be added to its parent. The error names both
+/// the pointer and the type of the value.
+///
+/// # Examples
+/// ```
+/// use serde_json::json;
+/// use db::jutil::set_from;
+///
+/// let mut word = json!({"word": "apple"});
+///
+/// set_from(&mut word, "/stats/tags", &["fruit", "food"]).unwrap();
+/// set_from(&mut word, "/word", "tree").unwrap();
+/// assert_eq!(json!({"word": "tree", "stats": {"tags": ["fruit", "food"]}}), word);
+/// ```
+pub fn set_from<T>(jvalue: &mut JValue, pointer: &str, value: &T) -> Result<()>
+where
+    T: Serialize + ?Sized,
+{
+    let context = |err: &dyn Display| {
+        Error::custom_err(
+            CustomKind::Json,
+            &format!(
+                "Value of type {} cannot be stored at '{}': {}",
+                type_name::<T>(),
+                pointer,
+                err
+            ),
+        )
+    };
+
+    let value = serde_json::to_value(value).map_err(|err| context(&err))?;
+    let result = match jvalue.pointer_mut(pointer) {
+        Some(existing) => {
+            *existing = value;
+            Ok(())
+        }
+        None => match pointer_complement(jvalue, pointer) {
+            // Only the last token is missing, thus the parent may be an array as well
+            Ok((complement, _)) if complement.rfind('/') == Some(0) => {
+                patch_add(jvalue, pointer, value)
+            }
+            _ => incorporate_into(jvalue, pointer, value),
+        },
+    };
+    result.map_err(|err| context(&err))
+}
+
+/// A target of a relative JSON pointer (see [`resolve_relative`]).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RelativeTarget<'a> {
+    /// Value together with an absolute JSON pointer denoting it
+    Value(String, &'a JValue),
+    /// Member name or array index under which a value is stored, requested by the `#` suffix
+    Key(JValue),
+}
+
+// Parse a non-negative integer prefix of a string, returning the rest of the string as well
+fn split_integer(string: &str) -> Option<(usize, &str)> {
+    let end = string
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(string.len());
+    let digits = &string[..end];
+    // Leading zeros are not allowed
+    if digits.is_empty() || (digits.len() > 1 && digits.starts_with('0')) {
+        return None;
+    }
+    Some((digits.parse().ok()?, &string[end..]))
+}
+
+/// Resolve a relative JSON pointer starting from a given location.
+///
+/// The function implements the Relative JSON Pointer draft. The relative pointer starts with
+/// the number of levels to go up from `location`, which is an absolute JSON pointer denoting an
+/// existing value. An optional index manipulation follows (e.g. `+1` or `-1`), which moves to
+/// another item of the same array. The rest of the relative pointer is either a JSON pointer
+/// resolved against the value reached so far, or `#` which requests the member name or the array
+/// index under which that value is stored.
+///
+/// # Errors
+/// The function returns a custom error in case either pointer has invalid syntax, `location`
+/// does not denote an existing value, the relative pointer goes above the whole value, an index
+/// manipulation is applied to a value which is not an array item or the target does not exist.
+///
+/// # Examples
+/// ```
+/// use serde_json::json;
+/// use db::jutil::{resolve_relative, RelativeTarget};
+///
+/// let deck = json!({"cards": [{"word": "apple"}, {"word": "house"}]});
+///
+/// // Sibling of a matched value
+/// let target = resolve_relative(&deck, "/cards/0/word", "1+1/word").unwrap();
+/// assert_eq!(RelativeTarget::Value("/cards/1/word".to_string(), &json!("house")), target);
+///
+/// // Index of an array item
+/// let target = resolve_relative(&deck, "/cards/1/word", "1#").unwrap();
+/// assert_eq!(RelativeTarget::Key(json!(1)), target);
+/// ```
+pub fn resolve_relative<'a>(
+    jvalue: &'a JValue,
+    location: &str,
+    relative: &str,
+) -> Result<RelativeTarget<'a>> {
+    let invalid = || {
+        Error::custom_err(
+            CustomKind::Json,
+            &format!("Relative pointer '{}' does not have valid syntax", relative),
+        )
+    };
+    let (levels, rest) = split_integer(relative).ok_or_else(invalid)?;
+    let (offset, rest) = match rest.chars().next() {
+        Some(sign @ ('+' | '-')) => {
+            let (offset, rest) = split_integer(&rest[1..]).ok_or_else(invalid)?;
+            let offset = isize::try_from(offset).map_err(|_| invalid())?;
+            (Some(if sign == '-' { -offset } else { offset }), rest)
+        }
+        _ => (None, rest),
+    };
+    if !rest.is_empty() && rest != "#" && !rest.starts_with('/') {
+        return Err(invalid());
+    }
+
+    existing_value(jvalue, location)?;
+    let mut tokens: Vec<String> = location
         .split('/')
         .skip(1)
-        .map(|token| token.replace("~1", "/").replace("~0", "~"))
+        .map(ToString::to_string)
         .collect();
-
-    if parent.is_object() {
-        let wrapped = wrap_value(&tokens[1..], child);
-        parent
-            .as_object_mut()
-            .unwrap()
-            .insert(tokens[0].clone(), wrapped);
-    } else if parent.is_array() {
-        let wrapped = wrap_value(&tokens, child);
-        parent.as_array_mut().unwrap().push(wrapped);
-    } else {
-        return Err(Error::custom_err(
+    let depth = tokens.len().checked_sub(levels).ok_or_else(|| {
+        Error::custom_err(
             CustomKind::Json,
             &format!(
-                "Cannot incorporate since the value pointed by '{}' is neither an array nor object",
-                pointer.trim_end_matches(&complement)
+                "Relative pointer '{}' goes above the whole value from '{}'",
+                relative, location
             ),
-        ));
+        )
+    })?;
+    tokens.truncate(depth);
+    let join = |tokens: &[String]| {
+        tokens
+            .iter()
+            .fold(String::new(), |pointer, token| pointer + "/" + token)
+    };
+
+    // Index of the value reached so far, provided that it is an array item
+    let index = match tokens.split_last() {
+        Some((last, parent)) => match existing_value(jvalue, &join(parent))? {
+            JValue::Array(array) => Some(array_index(last, array.len(), false)?),
+            _ => None,
+        },
+        None => None,
+    };
+    let index = match offset {
+        None => index,
+        Some(offset) => {
+            let moved = index
+                .and_then(|index| index.checked_add_signed(offset))
+                .ok_or_else(|| {
+                    Error::custom_err(
+                        CustomKind::Json,
+                        &format!(
+                            "Relative pointer '{}' cannot move to another array item from '{}'",
+                            relative, location
+                        ),
+                    )
+                })?;
+            if let Some(last) = tokens.last_mut() {
+                *last = moved.to_string();
+            }
+            Some(moved)
+        }
+    };
+
+    let pointer = join(&tokens);
+    if rest != "#" {
+        let pointer = pointer + rest;
+        let target = existing_value(jvalue, &pointer)?;
+        return Ok(RelativeTarget::Value(pointer, target));
     }
 
-    Ok(())
+    existing_value(jvalue, &pointer)?;
+    match (index, tokens.last()) {
+        (Some(index), _) => Ok(RelativeTarget::Key(JValue::from(index))),
+        (None, Some(token)) => Ok(RelativeTarget::Key(JValue::String(unescape_token(token)))),
+        (None, None) => Err(Error::custom_err(
+            CustomKind::Json,
+            &format!(
+                "Relative pointer '{}' requests a key of the whole value from '{}'",
+                relative, location
+            ),
+        )),
+    }
 }
 
 #[cfg(test)]
@@ -438,4 +2193,743 @@ mod tests {
         incorporate_into(&mut test_json_john, pointer, test_json_alice.clone()).unwrap();
         assert_eq!(*test_json_john.pointer(pointer).unwrap(), test_json_alice);
     }
+
+    #[rstest]
+    #[case("word", "word")]
+    #[case("a/b", "a~1b")]
+    #[case("~1", "~01")]
+    fn token_is_escaped(#[case] token: &str, #[case] expected: &str) {
+        assert_eq!(expected, escape_token(token));
+        let wrapped = json!({ token: true });
+        assert!(wrapped.pointer(&format!("/{}", expected)).is_some());
+    }
+
+    #[rstest]
+    #[case("/a/a", "/a")]
+    #[case("/a/b/a", "/a")]
+    fn pointer_complement_handles_repeated_tokens(#[case] pointer: &str, #[case] expected: &str) {
+        let mut jvalue = json!({"a": {"b": {}}});
+        assert_eq!(expected, pointer_complement(&jvalue, pointer).unwrap().0);
+        assert_eq!(
+            expected,
+            pointer_complement_mut(&mut jvalue, pointer).unwrap().0
+        );
+    }
+
+    #[rstest]
+    #[case::add_member(json!({"op": "add", "path": "/age", "value": 22}), json!({"age": 22}))]
+    #[case::add_item(
+        json!({"op": "add", "path": "/hobbies/1", "value": "art"}),
+        json!({"hobbies": ["books", "art", "sport", "shopping"]})
+    )]
+    #[case::append_item(
+        json!({"op": "add", "path": "/hobbies/-", "value": "art"}),
+        json!({"hobbies": ["books", "sport", "shopping", "art"]})
+    )]
+    #[case::add_escaped(json!({"op": "add", "path": "/a~1b~0c", "value": 1}), json!({"a/b~c": 1}))]
+    #[case::remove_member(json!({"op": "remove", "path": "/age"}), json!({"age": null}))]
+    #[case::remove_item(
+        json!({"op": "remove", "path": "/hobbies/0"}),
+        json!({"hobbies": ["sport", "shopping"]})
+    )]
+    #[case::replace(json!({"op": "replace", "path": "/age", "value": 30}), json!({"age": 30}))]
+    #[case::move_member(
+        json!({"op": "move", "from": "/name", "path": "/hobbies/0"}),
+        json!({"name": null, "hobbies": ["Alice Wright", "books", "sport", "shopping"]})
+    )]
+    #[case::move_item(
+        json!({"op": "move", "from": "/hobbies/0", "path": "/hobbies/-"}),
+        json!({"hobbies": ["sport", "shopping", "books"]})
+    )]
+    #[case::copy(json!({"op": "copy", "from": "/age", "path": "/years"}), json!({"years": 21}))]
+    #[case::test(json!({"op": "test", "path": "/age", "value": 21.0}), json!({}))]
+    fn patch_operation_is_applied(
+        #[case] operation: JValue,
+        #[case] changes: JValue,
+        mut test_json_alice: JValue,
+    ) {
+        let mut expected = test_json_alice.clone();
+        for (key, value) in changes.as_object().unwrap() {
+            if value.is_null() {
+                expected.as_object_mut().unwrap().remove(key);
+            } else {
+                expected[key] = value.clone();
+            }
+        }
+
+        apply_patch(&mut test_json_alice, &json!([operation])).unwrap();
+        assert_eq!(expected, test_json_alice);
+    }
+
+    #[rstest]
+    fn nested_numbers_are_tested_by_value(mut test_json_alice: JValue) {
+        let patch = json!([
+            {"op": "add", "path": "/scores", "value": [1, {"best": 2}]},
+            {"op": "test", "path": "/scores", "value": [1.0, {"best": 2.0}]}
+        ]);
+        apply_patch(&mut test_json_alice, &patch).unwrap();
+        assert_eq!(json!([1, {"best": 2}]), test_json_alice["scores"]);
+    }
+
+    #[rstest]
+    fn whole_document_is_replaced_by_empty_path(mut test_json_alice: JValue) {
+        let patch = json!([{"op": "replace", "path": "", "value": [1]}]);
+        apply_patch(&mut test_json_alice, &patch).unwrap();
+        assert_eq!(json!([1]), test_json_alice);
+    }
+
+    #[rstest]
+    #[case::not_an_array(json!({"op": "remove", "path": "/age"}))]
+    #[case::unknown_operation(json!([{"op": "delete", "path": "/age"}]))]
+    #[case::missing_value(json!([{"op": "add", "path": "/age"}]))]
+    #[case::missing_from(json!([{"op": "copy", "path": "/age"}]))]
+    #[case::invalid_pointer(json!([{"op": "remove", "path": "age"}]))]
+    #[case::missing_parent(json!([{"op": "add", "path": "/a/b", "value": 1}]))]
+    #[case::scalar_parent(json!([{"op": "add", "path": "/age/a", "value": 1}]))]
+    #[case::index_out_of_bounds(json!([{"op": "add", "path": "/hobbies/4", "value": 1}]))]
+    #[case::index_with_leading_zero(json!([{"op": "remove", "path": "/hobbies/01"}]))]
+    #[case::remove_missing(json!([{"op": "remove", "path": "/hobbies/3"}]))]
+    #[case::remove_end(json!([{"op": "remove", "path": "/hobbies/-"}]))]
+    #[case::replace_missing(json!([{"op": "replace", "path": "/sex", "value": 1}]))]
+    #[case::move_into_child(json!([{"op": "move", "from": "/hobbies", "path": "/hobbies/0"}]))]
+    #[case::failed_test(json!([{"op": "test", "path": "/age", "value": "21"}]))]
+    #[case::failed_nested_test(json!([
+        {"op": "test", "path": "/hobbies", "value": ["books", "sport"]}
+    ]))]
+    #[case::failed_last_operation(json!([
+        {"op": "remove", "path": "/name"},
+        {"op": "add", "path": "/hobbies/-", "value": "art"},
+        {"op": "test", "path": "/name", "value": "Alice Wright"}
+    ]))]
+    fn failed_patch_leaves_value_unchanged(#[case] patch: JValue, mut test_json_alice: JValue) {
+        let expected = test_json_alice.clone();
+        let err = apply_patch(&mut test_json_alice, &patch).unwrap_err();
+        assert_eq!(CustomKind::Json, *err.get_custom_kind().unwrap());
+        assert_eq!(expected, test_json_alice);
+    }
+
+    #[rstest]
+    #[case::large_integers(json!(9_007_199_254_740_993_u64), json!(9_007_199_254_740_992_u64), Ordering::Greater)]
+    #[case::signed_and_unsigned(json!(-1), json!(u64::MAX), Ordering::Less)]
+    #[case::integer_and_float(json!(1), json!(1.0), Ordering::Equal)]
+    #[case::large_integer_and_float(json!(9_007_199_254_740_993_u64), json!(9_007_199_254_740_992.0), Ordering::Greater)]
+    #[case::floats(json!(0.5), json!(0.25), Ordering::Greater)]
+    fn numbers_are_compared(#[case] lhs: JValue, #[case] rhs: JValue, #[case] expected: Ordering) {
+        assert_eq!(Some(expected), compare(&lhs, &rhs));
+        assert_eq!(Some(expected.reverse()), compare(&rhs, &lhs));
+    }
+
+    #[rstest]
+    #[case(json!(1), json!(2), Some(Ordering::Less))]
+    #[case(json!(2.5), json!(2), Some(Ordering::Greater))]
+    #[case(json!("b"), json!("a"), Some(Ordering::Greater))]
+    #[case(json!(false), json!(true), Some(Ordering::Less))]
+    #[case(json!(null), json!(null), Some(Ordering::Equal))]
+    #[case(json!(1), json!("1"), None)]
+    #[case(json!([1]), json!([1]), None)]
+    fn values_are_compared(
+        #[case] lhs: JValue,
+        #[case] rhs: JValue,
+        #[case] expected: Option<Ordering>,
+    ) {
+        assert_eq!(expected, compare(&lhs, &rhs));
+    }
+
+    #[rstest]
+    #[case(json!(null), json!(false))]
+    #[case(json!(true), json!(0))]
+    #[case(json!(100), json!(""))]
+    #[case(json!("z"), json!([]))]
+    #[case(json!([1, 2]), json!({}))]
+    fn values_of_different_types_are_ordered_by_type(#[case] lhs: JValue, #[case] rhs: JValue) {
+        assert_eq!(Ordering::Less, total_compare(&lhs, &rhs));
+        assert_eq!(Ordering::Greater, total_compare(&rhs, &lhs));
+    }
+
+    #[rstest]
+    #[case(json!([1, {"a": [2]}]), json!([1.0, {"a": [2.0]}]), true)]
+    #[case(json!({"a": 1, "b": 2}), json!({"b": 2, "a": 1}), true)]
+    #[case(json!([1, 2]), json!([1]), false)]
+    #[case(json!({"a": 1}), json!({"a": 1, "b": null}), false)]
+    #[case(json!({"a": "1"}), json!({"a": 1}), false)]
+    fn values_are_tested_for_equality(
+        #[case] lhs: JValue,
+        #[case] rhs: JValue,
+        #[case] expected: bool,
+    ) {
+        assert_eq!(expected, equal(&lhs, &rhs));
+        assert_eq!(expected, equal(&rhs, &lhs));
+    }
+
+    #[rstest]
+    #[case::replace_member(json!({"a": "b"}), json!({"a": "c"}), json!({"a": "c"}))]
+    #[case::add_member(json!({"a": "b"}), json!({"b": "c"}), json!({"a": "b", "b": "c"}))]
+    #[case::remove_member(json!({"a": "b"}), json!({"a": null}), json!({}))]
+    #[case::remove_missing_member(json!({"a": "b"}), json!({"c": null}), json!({"a": "b"}))]
+    #[case::replace_array(json!({"a": [1, 2]}), json!({"a": [3]}), json!({"a": [3]}))]
+    #[case::replace_with_object(json!({"a": "c"}), json!({"a": {"b": "c"}}), json!({"a": {"b": "c"}}))]
+    #[case::nested(
+        json!({"a": {"b": "c", "d": "e"}}),
+        json!({"a": {"b": null, "f": {"g": null}}}),
+        json!({"a": {"d": "e", "f": {}}})
+    )]
+    #[case::non_object_target(json!(["a"]), json!({"a": "b"}), json!({"a": "b"}))]
+    #[case::non_object_patch(json!({"a": "b"}), json!(["c"]), json!(["c"]))]
+    #[case::null_patch(json!({"a": "b"}), json!(null), json!(null))]
+    fn merge_patch_follows_rfc_7396(
+        #[case] mut target: JValue,
+        #[case] patch: JValue,
+        #[case] expected: JValue,
+    ) {
+        merge_patch(&mut target, patch);
+        assert_eq!(expected, target);
+    }
+
+    #[rstest]
+    #[case::replace(ArrayMerge::Replace, json!(["sport"]))]
+    #[case::concat(ArrayMerge::Concat, json!(["books", "sport", "shopping", "sport"]))]
+    fn deep_merge_merges_objects_and_arrays(
+        #[case] arrays: ArrayMerge,
+        #[case] hobbies: JValue,
+        mut test_json_alice: JValue,
+    ) {
+        let source = json!({"age": null, "hobbies": ["sport"], "address": {"city": "Oslo"}});
+        deep_merge(&mut test_json_alice, source, arrays);
+        assert_eq!(
+            json!({
+                "name": "Alice Wright",
+                "age": null,
+                "hobbies": hobbies,
+                "address": {"city": "Oslo"}
+            }),
+            test_json_alice
+        );
+
+        deep_merge(
+            &mut test_json_alice,
+            json!({"address": {"zip": "0150"}}),
+            arrays,
+        );
+        assert_eq!(
+            json!({"city": "Oslo", "zip": "0150"}),
+            test_json_alice["address"]
+        );
+    }
+
+    #[rstest]
+    #[case::equal(json!({"a": [1, {"b": 2}]}), json!({"a": [1, {"b": 2}]}))]
+    #[case::scalar(json!("a"), json!(1))]
+    #[case::different_types(json!({"a": 1}), json!([1]))]
+    #[case::members(json!({"a": 1, "b": 2, "a/b": 3}), json!({"b": 3, "c": 4, "~": 5}))]
+    #[case::nested(json!({"a": {"b": {"c": 1}}}), json!({"a": {"b": {"c": 2, "d": []}}}))]
+    #[case::insert_items(json!([1, 2, 3]), json!([0, 1, 2, 2.5, 3, 4]))]
+    #[case::remove_items(json!([0, 1, 2, 3, 4]), json!([1, 3]))]
+    #[case::replace_items(json!([1, 2, 3, 4]), json!([1, 5, 6, 7, 4]))]
+    #[case::reorder_items(json!([1, 2, 3]), json!([3, 2, 1]))]
+    #[case::nested_items(
+        json!([{"a": 1}, {"b": 1}, {"c": 1}]),
+        json!([{"a": 1}, {"b": 2}, {"c": 1}, {"d": 1}])
+    )]
+    #[case::emptied(json!([1, 2]), json!([]))]
+    fn diff_produces_patch_transforming_values(#[case] lhs: JValue, #[case] rhs: JValue) {
+        let patch = diff(&lhs, &rhs);
+        let mut patched = lhs.clone();
+        apply_patch(&mut patched, &patch).unwrap();
+        assert_eq!(rhs, patched);
+
+        if lhs == rhs {
+            assert_eq!(json!([]), patch);
+        }
+    }
+
+    #[rstest]
+    fn diff_of_arrays_is_minimal(test_json_alice: JValue) {
+        let mut changed = test_json_alice.clone();
+        changed["hobbies"] = json!(["chess", "books", "shopping"]);
+
+        assert_eq!(
+            json!([
+                {"op": "add", "path": "/hobbies/0", "value": "chess"},
+                {"op": "remove", "path": "/hobbies/2"}
+            ]),
+            diff(&test_json_alice, &changed)
+        );
+
+        changed["hobbies"] = json!(["books", "cooking", "shopping"]);
+        assert_eq!(
+            json!([{"op": "replace", "path": "/hobbies/1", "value": "cooking"}]),
+            diff(&test_json_alice, &changed)
+        );
+    }
+
+    #[rstest]
+    #[case::root("$", vec![""])]
+    #[case::member("$.name", vec!["/name"])]
+    #[case::quoted_member("$['cars_owned'][0][\"age\"]", vec!["/cars_owned/0/age"])]
+    #[case::escaped_member("$['/', '~']", vec!["/~1", "/~0"])]
+    #[case::missing_member("$.age", vec![])]
+    #[case::wildcard("$.cars_owned[0].*", vec![
+        "/cars_owned/0/age",
+        "/cars_owned/0/last_inspection",
+        "/cars_owned/0/name"
+    ])]
+    #[case::negative_index("$.cars_owned[-1].name", vec!["/cars_owned/0/name"])]
+    #[case::index_out_of_bounds("$.cars_owned[1]", vec![])]
+    #[case::descendants("$..date", vec!["/cars_owned/0/last_inspection/date"])]
+    #[case::descendant_names("$..name", vec!["/name", "/cars_owned/0/name"])]
+    #[case::descendant_brackets("$..['age', 'date']", vec![
+        "/cars_owned/0/age",
+        "/cars_owned/0/last_inspection/date"
+    ])]
+    fn json_path_selects_values_with_pointers(
+        #[case] path: &str,
+        #[case] expected: Vec<&str>,
+        test_json_john: JValue,
+    ) {
+        let selected = json_path(&test_json_john, path).unwrap();
+        let pointers: Vec<&str> = selected
+            .iter()
+            .map(|(pointer, _)| pointer.as_str())
+            .collect();
+        assert_eq!(expected, pointers);
+        for (pointer, jvalue) in &selected {
+            assert_eq!(test_json_john.pointer(pointer).unwrap(), *jvalue);
+        }
+    }
+
+    #[rstest]
+    #[case::all("$[:]", vec![0, 1, 2, 3, 4])]
+    #[case::range("$[1:3]", vec![1, 2])]
+    #[case::open_end("$[3:]", vec![3, 4])]
+    #[case::negative("$[-2:]", vec![3, 4])]
+    #[case::step("$[::2]", vec![0, 2, 4])]
+    #[case::reversed("$[::-1]", vec![4, 3, 2, 1, 0])]
+    #[case::reversed_range("$[3:1:-1]", vec![3, 2])]
+    #[case::zero_step("$[::0]", vec![])]
+    #[case::huge_step("$[1::9223372036854775807]", vec![1])]
+    #[case::out_of_bounds("$[7:10]", vec![])]
+    #[case::multiple("$[0, 0, -1]", vec![0, 0, 4])]
+    fn json_path_selects_array_slices(#[case] path: &str, #[case] expected: Vec<i64>) {
+        let array = json!([0, 1, 2, 3, 4]);
+        let selected: Vec<i64> = json_path(&array, path)
+            .unwrap()
+            .into_iter()
+            .map(|(_, jvalue)| jvalue.as_i64().unwrap())
+            .collect();
+        assert_eq!(expected, selected);
+    }
+
+    #[rstest]
+    #[case::comparison("$[?(@.level > 2)]", vec!["house", "tree"])]
+    #[case::without_parentheses("$[?@.level <= 2]", vec!["apple", "car"])]
+    #[case::equality("$[?(@.word == 'car')]", vec!["car"])]
+    #[case::inequality("$[?(@.word != \"car\")]", vec!["apple", "house", "tree"])]
+    #[case::existence("$[?(@.tags)]", vec!["apple", "house"])]
+    #[case::negation("$[?(!@.tags)]", vec!["car", "tree"])]
+    #[case::conjunction("$[?(@.level >= 2 && @.tags)]", vec!["house"])]
+    #[case::disjunction("$[?(@.level < 2 || @.level > 3)]", vec!["apple", "tree"])]
+    #[case::grouping("$[?(!(@.level < 2 || @.level > 3))]", vec!["car", "house"])]
+    #[case::nested_query("$[?(@.tags[0] == 'fruit')]", vec!["apple"])]
+    #[case::root_query("$[?(@.level == $.tree.level)]", vec!["tree"])]
+    #[case::different_types("$[?(@.level == '1')]", vec![])]
+    #[case::literal("$[?(@.active == true)]", vec!["car"])]
+    fn json_path_filters_values(#[case] path: &str, #[case] expected: Vec<&str>) {
+        let words = json!({
+            "apple": {"word": "apple", "level": 1, "tags": ["fruit"]},
+            "car": {"word": "car", "level": 2.0, "active": true},
+            "house": {"word": "house", "level": 3, "tags": []},
+            "tree": {"word": "tree", "level": 4}
+        });
+
+        let selected: Vec<&str> = json_path(&words, path)
+            .unwrap()
+            .into_iter()
+            .map(|(_, word)| word["word"].as_str().unwrap())
+            .collect();
+        assert_eq!(expected, selected);
+    }
+
+    #[rstest]
+    #[case::missing_root("name")]
+    #[case::trailing_dot("$.")]
+    #[case::unterminated_brackets("$['name'")]
+    #[case::unterminated_string("$['name]")]
+    #[case::empty_brackets("$[]")]
+    #[case::invalid_index("$[1a]")]
+    #[case::trailing_characters("$.name)")]
+    #[case::literal_filter("$[?(1)]")]
+    #[case::missing_operand("$[?(@.level > )]")]
+    fn json_path_produces_error_when_invalid_expression_is_passed(
+        #[case] path: &str,
+        test_json_john: JValue,
+    ) {
+        let err = json_path(&test_json_john, path).unwrap_err();
+        assert_eq!(CustomKind::Json, *err.get_custom_kind().unwrap());
+    }
+
+    #[rstest]
+    #[case::no_wildcard("/name", vec!["/name"])]
+    #[case::missing("/age", vec![])]
+    #[case::object_members("/cars_owned/0/*", vec![
+        "/cars_owned/0/age",
+        "/cars_owned/0/last_inspection",
+        "/cars_owned/0/name"
+    ])]
+    #[case::array_items("/cars_owned/*/name", vec!["/cars_owned/0/name"])]
+    #[case::multiple_wildcards("/*/*/last_inspection", vec!["/cars_owned/0/last_inspection"])]
+    #[case::escaped_tokens("/~1", vec!["/~1"])]
+    #[case::scalar("/name/*", vec![])]
+    fn pointer_is_expanded(
+        #[case] pointer: &str,
+        #[case] expected: Vec<&str>,
+        test_json_john: JValue,
+    ) {
+        assert_eq!(expected, expand_pointer(&test_json_john, pointer).unwrap());
+        let values = get_all(&test_json_john, pointer).unwrap();
+        assert_eq!(expected.len(), values.len());
+        for (pointer, value) in values {
+            assert_eq!(test_json_john.pointer(&pointer).unwrap(), value);
+        }
+    }
+
+    #[rstest]
+    fn invalid_wildcard_pointer_produces_error(mut test_json_john: JValue) {
+        expand_pointer(&test_json_john, "*").unwrap_err();
+        set_all(&mut test_json_john, "*/name", &json!(1)).unwrap_err();
+        remove_all(&mut test_json_john, "*").unwrap_err();
+        remove_all(&mut test_json_john, "").unwrap_err();
+    }
+
+    #[rstest]
+    fn all_values_are_set() {
+        let mut deck = json!({
+            "cards": [
+                {"word": "apple", "due": "2024-01-01"},
+                {"word": "house"},
+                {"word": "tree", "due": "2024-03-01"}
+            ]
+        });
+
+        assert_eq!(3, set_all(&mut deck, "/cards/*/due", &json!(null)).unwrap());
+        assert_eq!(3, get_all(&deck, "/cards/*/due").unwrap().len());
+        assert!(get_all(&deck, "/cards/*/due")
+            .unwrap()
+            .iter()
+            .all(|(_, due)| due.is_null()));
+
+        // Missing structure following the last wildcard is created
+        set_all(&mut deck, "/cards/*/stats/seen", &json!(0)).unwrap();
+        assert_eq!(json!({"seen": 0}), deck["cards"][1]["stats"]);
+
+        // No wildcard denotes a single value
+        assert_eq!(1, set_all(&mut deck, "/owner", &json!("alice")).unwrap());
+        assert_eq!(json!("alice"), deck["owner"]);
+    }
+
+    #[rstest]
+    fn failed_set_all_leaves_value_unchanged() {
+        let mut deck = json!({"cards": [{"due": {}}, {"due": 1}]});
+        let expected = deck.clone();
+
+        set_all(&mut deck, "/cards/*/due/day", &json!(1)).unwrap_err();
+        assert_eq!(expected, deck);
+    }
+
+    #[rstest]
+    fn all_values_are_removed(mut test_json_alice: JValue) {
+        let removed = remove_all(&mut test_json_alice, "/hobbies/*").unwrap();
+        assert_eq!(
+            vec![
+                ("/hobbies/0".to_string(), json!("books")),
+                ("/hobbies/1".to_string(), json!("sport")),
+                ("/hobbies/2".to_string(), json!("shopping"))
+            ],
+            removed
+        );
+        assert_eq!(json!([]), test_json_alice["hobbies"]);
+
+        let removed = remove_all(&mut test_json_alice, "/*").unwrap();
+        assert_eq!(3, removed.len());
+        assert_eq!(json!({}), test_json_alice);
+    }
+
+    #[rstest]
+    #[case("/name", json!("John Doe"))]
+    #[case("/cars_owned/0", test_json_john()["cars_owned"][0].clone())]
+    #[case("/cars_owned/0/last_inspection/date", json!("2020-01-05"))]
+    #[case("/", json!("foo"))]
+    #[case("/~1", json!("bar"))]
+    #[case("/~0", json!("qux"))]
+    fn value_is_removed_at_pointer(
+        #[case] pointer: &str,
+        #[case] expected: JValue,
+        mut test_json_john: JValue,
+    ) {
+        assert_eq!(expected, remove_at(&mut test_json_john, pointer).unwrap());
+        assert!(test_json_john.pointer(pointer).is_none());
+    }
+
+    #[rstest]
+    #[case::whole_value("")]
+    #[case::invalid_syntax("name")]
+    #[case::missing_member("/age")]
+    #[case::missing_parent("/cars/0")]
+    #[case::missing_item("/cars_owned/1")]
+    #[case::end_of_array("/cars_owned/-")]
+    #[case::scalar_parent("/name/0")]
+    fn removing_at_invalid_pointer_produces_error(
+        #[case] pointer: &str,
+        mut test_json_john: JValue,
+    ) {
+        let expected = test_json_john.clone();
+        remove_at(&mut test_json_john, pointer).unwrap_err();
+        remove_pruned(&mut test_json_john, pointer).unwrap_err();
+        assert_eq!(expected, test_json_john);
+    }
+
+    #[rstest]
+    fn empty_parent_objects_are_pruned(mut test_json_john: JValue) {
+        remove_pruned(&mut test_json_john, "/cars_owned/0/last_inspection/date").unwrap();
+        assert!(test_json_john
+            .pointer("/cars_owned/0/last_inspection")
+            .is_none());
+        assert!(test_json_john.pointer("/cars_owned/0/age").is_some());
+
+        // Arrays and the whole value are not pruned
+        let mut jvalue = json!({"a": [{"b": 1}], "c": {"d": 1}});
+        remove_pruned(&mut jvalue, "/a/0/b").unwrap();
+        assert_eq!(json!({"a": [], "c": {"d": 1}}), jvalue);
+        remove_pruned(&mut jvalue, "/c/d").unwrap();
+        remove_pruned(&mut jvalue, "/a").unwrap();
+        assert_eq!(json!({}), jvalue);
+    }
+
+    #[rstest]
+    #[case("", test_json_john())]
+    #[case("/name", json!("John Doe"))]
+    #[case("/cars_owned/0/age", json!(5))]
+    #[case("/~1", json!("bar"))]
+    fn value_is_replaced_at_pointer(
+        #[case] pointer: &str,
+        #[case] expected: JValue,
+        mut test_json_john: JValue,
+        test_json_alice: JValue,
+    ) {
+        let previous = replace_at(&mut test_json_john, pointer, test_json_alice.clone()).unwrap();
+        assert_eq!(expected, previous);
+        assert_eq!(test_json_alice, *test_json_john.pointer(pointer).unwrap());
+    }
+
+    #[rstest]
+    #[case::invalid_syntax("name")]
+    #[case::missing_member("/age")]
+    #[case::missing_item("/cars_owned/1")]
+    fn replacing_at_invalid_pointer_produces_error(
+        #[case] pointer: &str,
+        mut test_json_john: JValue,
+    ) {
+        replace_at(&mut test_json_john, pointer, json!(1)).unwrap_err();
+    }
+
+    #[rstest]
+    fn value_is_flattened(test_json_john: JValue) {
+        assert_eq!(
+            json!({
+                "/": "foo",
+                "/\"": "quux",
+                "/cars_owned/0/age": 5,
+                "/cars_owned/0/last_inspection/date": "2020-01-05",
+                "/cars_owned/0/name": "Ford Mustang",
+                "/name": "John Doe",
+                "/~0": "qux",
+                "/~1": "bar"
+            }),
+            JValue::Object(flatten(&test_json_john))
+        );
+    }
+
+    #[rstest]
+    #[case::john(test_json_john())]
+    #[case::alice(test_json_alice())]
+    #[case::empty_containers(json!({"a": {}, "b": [], "c": [{}, [], [[1]]]}))]
+    #[case::nested_special_keys(json!({"": {"": {"/~": [null]}}}))]
+    #[case::large_array(json!({"a": (0..12).collect::<Vec<_>>()}))]
+    #[case::array(json!([1, {"a": 2}]))]
+    #[case::scalar(json!("apple"))]
+    #[case::empty_object(json!({}))]
+    fn flattened_value_is_unflattened(#[case] jvalue: JValue) {
+        let flat = flatten(&jvalue);
+        for (pointer, leaf) in &flat {
+            assert_eq!(leaf, jvalue.pointer(pointer).unwrap());
+        }
+        assert_eq!(jvalue, unflatten(&flat).unwrap());
+    }
+
+    #[rstest]
+    fn object_with_index_keys_is_unflattened_as_array() {
+        let jvalue = json!({"n": {"0": "a", "1": "b"}});
+        assert_eq!(
+            json!({"n": ["a", "b"]}),
+            unflatten(&flatten(&jvalue)).unwrap()
+        );
+    }
+
+    #[rstest]
+    #[case::non_consecutive_indices(json!({"/a/0": 1, "/a/2": 2}), json!({"a": {"0": 1, "2": 2}}))]
+    #[case::leading_zero(json!({"/a/00": 1}), json!({"a": {"00": 1}}))]
+    #[case::numeric_key(json!({"/0": true}), json!([true]))]
+    fn unflatten_restores_arrays_of_consecutive_indices(
+        #[case] flat: JValue,
+        #[case] expected: JValue,
+    ) {
+        assert_eq!(expected, unflatten(flat.as_object().unwrap()).unwrap());
+    }
+
+    #[rstest]
+    #[case::invalid_pointer(json!({"a": 1}))]
+    #[case::conflicting_pointers(json!({"/a": 1, "/a/b": 2}))]
+    #[case::root_with_others(json!({"": 1, "/a": 2}))]
+    fn unflatten_produces_error_when_pointers_are_invalid(#[case] flat: JValue) {
+        unflatten(flat.as_object().unwrap()).unwrap_err();
+    }
+
+    #[rstest]
+    fn typed_value_is_read(test_json_john: JValue) {
+        #[derive(Deserialize, Debug, PartialEq)]
+        struct Car {
+            name: String,
+            age: u8,
+        }
+
+        assert_eq!(
+            Car {
+                name: "Ford Mustang".to_string(),
+                age: 5
+            },
+            get_as::<Car>(&test_json_john, "/cars_owned/0").unwrap()
+        );
+        assert_eq!("bar", get_as::<&str>(&test_json_john, "/~1").unwrap());
+        assert_eq!(
+            Some(5),
+            get_as::<Option<i64>>(&test_json_john, "/cars_owned/0/age").unwrap()
+        );
+    }
+
+    #[rstest]
+    #[case::wrong_type("/name", "Value at '/name' cannot be read as u8")]
+    #[case::object("/cars_owned/0", "Value at '/cars_owned/0' cannot be read as u8")]
+    #[case::missing_value("/age", "Pointer '/age' does not denote any value")]
+    #[case::invalid_pointer("name", "Pointer 'name' does not have valid syntax")]
+    fn reading_typed_value_produces_error_with_context(
+        #[case] pointer: &str,
+        #[case] message: &str,
+        test_json_john: JValue,
+    ) {
+        let err = get_as::<u8>(&test_json_john, pointer).unwrap_err();
+        assert_eq!(CustomKind::Json, *err.get_custom_kind().unwrap());
+        assert!(err.to_string().contains(message), "{}", err);
+    }
+
+    #[rstest]
+    fn typed_value_is_written(mut test_json_alice: JValue) {
+        #[derive(Serialize)]
+        struct Address<'a> {
+            city: &'a str,
+        }
+
+        set_from(&mut test_json_alice, "/age", &22_u8).unwrap();
+        set_from(&mut test_json_alice, "/address", &Address { city: "Oslo" }).unwrap();
+        set_from(&mut test_json_alice, "/hobbies/3", "art").unwrap();
+        set_from(&mut test_json_alice, "/hobbies/-", "chess").unwrap();
+        assert_eq!(
+            json!({
+                "name": "Alice Wright",
+                "age": 22,
+                "address": {"city": "Oslo"},
+                "hobbies": ["books", "sport", "shopping", "art", "chess"]
+            }),
+            test_json_alice
+        );
+
+        let err = set_from(&mut test_json_alice, "/name/first", &"Alice").unwrap_err();
+        assert!(err
+            .to_string()
+            .contains("Value of type &str cannot be stored at '/name/first'"));
+        set_from(&mut test_json_alice, "/hobbies/7", "art").unwrap_err();
+    }
+
+    #[rstest]
+    #[case("/foo/1", "0", "/foo/1", json!("baz"))]
+    #[case("/foo/1", "1/0", "/foo/0", json!("bar"))]
+    #[case("/foo/1", "0-1", "/foo/0", json!("bar"))]
+    #[case("/foo/0", "0+1", "/foo/1", json!("baz"))]
+    #[case("/foo/1", "2/highly/nested/objects", "/highly/nested/objects", json!(true))]
+    #[case("/highly/nested", "0/objects", "/highly/nested/objects", json!(true))]
+    #[case("/highly/nested", "1/nested/objects", "/highly/nested/objects", json!(true))]
+    #[case("/highly/nested", "2/foo/0", "/foo/0", json!("bar"))]
+    #[case("/~1", "1/~0", "/~0", json!("tilde"))]
+    #[case("", "0/foo/1", "/foo/1", json!("baz"))]
+    fn relative_pointer_is_resolved_to_value(
+        #[case] location: &str,
+        #[case] relative: &str,
+        #[case] pointer: &str,
+        #[case] expected: JValue,
+    ) {
+        let jvalue = json!({
+            "foo": ["bar", "baz"],
+            "highly": {"nested": {"objects": true}},
+            "/": 1,
+            "~": "tilde"
+        });
+        assert_eq!(
+            RelativeTarget::Value(pointer.to_string(), &expected),
+            resolve_relative(&jvalue, location, relative).unwrap()
+        );
+    }
+
+    #[rstest]
+    #[case("/foo/1", "0#", json!(1))]
+    #[case("/foo/1", "0-1#", json!(0))]
+    #[case("/foo/1", "1#", json!("foo"))]
+    #[case("/highly/nested", "0#", json!("nested"))]
+    #[case("/highly/nested", "1#", json!("highly"))]
+    #[case("/~1", "0#", json!("/"))]
+    fn relative_pointer_is_resolved_to_key(
+        #[case] location: &str,
+        #[case] relative: &str,
+        #[case] expected: JValue,
+    ) {
+        let jvalue = json!({
+            "foo": ["bar", "baz"],
+            "highly": {"nested": {"objects": true}},
+            "/": 1
+        });
+        assert_eq!(
+            RelativeTarget::Key(expected),
+            resolve_relative(&jvalue, location, relative).unwrap()
+        );
+    }
+
+    #[rstest]
+    #[case::empty("/name", "")]
+    #[case::leading_zero("/name", "01")]
+    #[case::missing_levels("/name", "/name")]
+    #[case::invalid_suffix("/name", "0name")]
+    #[case::invalid_offset("/cars_owned/0", "0+")]
+    #[case::invalid_location("name", "0")]
+    #[case::missing_location("/age", "0")]
+    #[case::above_root("/cars_owned/0", "3")]
+    #[case::offset_of_member("/cars_owned/0/age", "0+1")]
+    #[case::offset_out_of_bounds("/cars_owned/0", "0+1")]
+    #[case::negative_index("/cars_owned/0", "0-1")]
+    #[case::missing_target("/cars_owned/0", "0/color")]
+    #[case::key_of_root("/name", "1#")]
+    fn invalid_relative_pointer_produces_error(
+        #[case] location: &str,
+        #[case] relative: &str,
+        test_json_john: JValue,
+    ) {
+        let err = resolve_relative(&test_json_john, location, relative).unwrap_err();
+        assert_eq!(CustomKind::Json, *err.get_custom_kind().unwrap());
+    }
 }
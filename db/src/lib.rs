@@ -3,8 +3,14 @@
 #![deny(warnings)]
 #![deny(missing_docs, rustdoc::missing_crate_level_docs)]
 
+mod crypto;
 pub mod database;
 pub mod error;
+mod index;
 pub mod io;
 pub mod jutil;
 pub mod metadata;
+pub mod query;
+pub mod schema;
+pub mod storage;
+pub mod wal;
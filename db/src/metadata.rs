@@ -3,8 +3,10 @@
 //! Metadata keeps all crucial information required to load, store and manipulate database
 //! collections as well as the database itself.
 
+use crate::io::{Compression, Format};
 use chrono::{DateTime, Local};
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 
 /// A structure representing metadata of a database.
 #[non_exhaustive]
@@ -16,6 +18,44 @@ pub struct Database {
     pub created: DateTime<Local>,
     /// Database last modification date.
     pub modified: DateTime<Local>,
+    /// Collections stored inside a database, indexed by their names.
+    #[serde(default)]
+    pub collections: BTreeMap<String, Collection>,
+    /// Format collection files are written in.
+    #[serde(default)]
+    pub format: Format,
+    /// Codec collection files are compressed with.
+    #[serde(default)]
+    pub compression: Compression,
+}
+
+/// A structure representing metadata of a collection.
+#[non_exhaustive]
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Collection {
+    /// Collection creation date.
+    pub created: DateTime<Local>,
+    /// Collection last modification date.
+    pub modified: DateTime<Local>,
+    /// Secondary indexes of a collection, indexed by JSON pointers to values they are built on.
+    #[serde(default)]
+    pub indexes: BTreeMap<String, Index>,
+    /// CRC32C checksums the collection file has to match when loaded. There is a single one
+    /// except for an interrupted checkpoint, which leaves the previous one as well. Files of
+    /// collections without checksums are not verified.
+    #[serde(default)]
+    pub checksums: Vec<u32>,
+}
+
+/// A structure representing definition of a secondary index.
+#[non_exhaustive]
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Index {
+    /// Index creation date.
+    pub created: DateTime<Local>,
+    /// Whether indexed values have to be unique within a collection.
+    #[serde(default)]
+    pub unique: bool,
 }
 
 impl Database {
@@ -30,6 +70,48 @@ impl Database {
             name: name.to_string(),
             created: now,
             modified: now,
+            collections: BTreeMap::new(),
+            format: Format::default(),
+            compression: Compression::default(),
+        }
+    }
+}
+
+impl Default for Collection {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Collection {
+    /// Return a collection metadata structure, preinitialized for further processing.
+    #[must_use]
+    pub fn new() -> Self {
+        let now = Local::now();
+        Self {
+            created: now,
+            modified: now,
+            indexes: BTreeMap::new(),
+            checksums: Vec::new(),
+        }
+    }
+}
+
+impl Default for Index {
+    fn default() -> Self {
+        Self::new(false)
+    }
+}
+
+impl Index {
+    /// Return an index definition, preinitialized for further processing.
+    ///
+    /// Unique indexes do not allow a value to be held by more than one document.
+    #[must_use]
+    pub fn new(unique: bool) -> Self {
+        Self {
+            created: Local::now(),
+            unique,
         }
     }
 }
@@ -43,4 +125,52 @@ mod tests {
         let database = Database::new("Database");
         assert_eq!(database.created, database.modified);
     }
+
+    #[test]
+    fn by_default_database_has_no_collections() {
+        let database = Database::new("Database");
+        assert!(database.collections.is_empty());
+    }
+
+    #[test]
+    fn by_default_database_stores_collections_as_uncompressed_json() {
+        let database = Database::new("Database");
+        assert_eq!(Format::Json, database.format);
+        assert_eq!(Compression::None, database.compression);
+    }
+
+    #[test]
+    fn database_without_format_may_be_deserialized() {
+        let database: Database = serde_json::from_value(serde_json::json!({
+            "name": "Database",
+            "created": "2022-03-01T10:00:00+01:00",
+            "modified": "2022-03-01T10:00:00+01:00"
+        }))
+        .unwrap();
+        assert_eq!(Format::Json, database.format);
+        assert_eq!(Compression::None, database.compression);
+    }
+
+    #[test]
+    fn by_default_collection_creation_date_equals_modification_date() {
+        let collection = Collection::new();
+        assert_eq!(collection.created, collection.modified);
+    }
+
+    #[test]
+    fn by_default_collection_has_no_indexes() {
+        let collection = Collection::new();
+        assert!(collection.indexes.is_empty());
+    }
+
+    #[test]
+    fn collection_without_indexes_may_be_deserialized() {
+        let collection: Collection = serde_json::from_value(serde_json::json!({
+            "created": "2022-03-01T10:00:00+01:00",
+            "modified": "2022-03-01T10:00:00+01:00"
+        }))
+        .unwrap();
+        assert!(collection.indexes.is_empty());
+        assert!(collection.checksums.is_empty());
+    }
 }
@@ -0,0 +1,546 @@
+//! Query Manager.
+//!
+//! The module provides a query builder which allows documents stored inside collections to be
+//! filtered, sorted, paginated and projected. Every part of a document taking part in a query is
+//! addressed by a JSON pointer.
+//!
+//! A query is built by chaining builder methods and is executed afterwards against a collection
+//! (see [`crate::database::Collection::query`]). Predicates are combined with logical AND.
+
+use crate::error::{CustomKind, Error, Result};
+use crate::jutil::{compare, pointer_complement, total_compare};
+use regex::Regex;
+use serde_json::Value as JValue;
+use std::cmp::Ordering;
+
+/// A single filtering condition, evaluated against a value denoted by a JSON pointer.
+#[non_exhaustive]
+#[derive(Debug, Clone, PartialEq)]
+pub enum Predicate {
+    /// Value exists and is equal to the given one
+    Eq(String, JValue),
+    /// Value either does not exist or is not equal to the given one
+    Ne(String, JValue),
+    /// Value exists and is less than the given one
+    Lt(String, JValue),
+    /// Value exists and is greater than the given one
+    Gt(String, JValue),
+    /// Value exists and is equal to any of the given ones
+    In(String, Vec<JValue>),
+    /// Value exists
+    Exists(String),
+    /// Value exists, is a string and matches the given regular expression
+    Regex(String, String),
+}
+
+/// Sort order of query results.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Order {
+    /// Ascending order
+    Ascending,
+    /// Descending order
+    Descending,
+}
+
+/// A document matched by a query together with its key.
+pub type Match = (String, JValue);
+
+/// A structure representing a query.
+///
+/// # Examples
+/// ```
+/// use db::query::{Order, Query};
+/// use serde_json::json;
+///
+/// let documents = vec![
+///     ("1".to_string(), json!({"word": "apple", "level": 3})),
+///     ("2".to_string(), json!({"word": "house", "level": 1})),
+///     ("3".to_string(), json!({"word": "tree", "level": 2})),
+/// ];
+///
+/// let query = Query::new()
+///     .gt("/level", json!(1))
+///     .sort_by("/level", Order::Ascending)
+///     .project(&["/word"]);
+/// let matches = query
+///     .execute(documents.iter().map(|(key, document)| (key.as_str(), document)))
+///     .unwrap();
+///
+/// assert_eq!(vec![
+///     ("3".to_string(), json!({"word": "tree"})),
+///     ("1".to_string(), json!({"word": "apple"})),
+/// ], matches);
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct Query {
+    predicates: Vec<Predicate>,
+    sort: Option<(String, Order)>,
+    offset: usize,
+    limit: Option<usize>,
+    projection: Option<Vec<String>>,
+}
+
+// A predicate with all arguments prepared for evaluation
+enum Compiled<'a> {
+    Plain(&'a Predicate),
+    Regex(&'a str, Regex),
+}
+
+// Resolve a value denoted by a pointer. None is returned if the value does not exist
+pub(crate) fn resolve<'a>(document: &'a JValue, pointer: &str) -> Result<Option<&'a JValue>> {
+    let (complement, value) = pointer_complement(document, pointer)?;
+    Ok(complement.is_empty().then_some(value))
+}
+
+// Select values denoted by pointers which all exist in the value. Overlapping pointers select the
+// outermost value only and items selected from an array keep their order
+fn select(jvalue: &JValue, pointers: &[&str]) -> JValue {
+    if pointers.contains(&"") {
+        return jvalue.clone();
+    }
+
+    // Group pointers by their first token, which is still escaped
+    let mut groups: Vec<(&str, Vec<&str>)> = Vec::new();
+    for pointer in pointers {
+        let tokens = &pointer[1..];
+        let (token, rest) = tokens
+            .find('/')
+            .map_or((tokens, ""), |i| tokens.split_at(i));
+        match groups.iter_mut().find(|(other, _)| *other == token) {
+            Some((_, rests)) => rests.push(rest),
+            None => groups.push((token, vec![rest])),
+        }
+    }
+
+    if let JValue::Array(array) = jvalue {
+        let mut items: Vec<(usize, JValue)> = groups
+            .iter()
+            .filter_map(|(token, rests)| {
+                let index = token.parse::<usize>().ok()?;
+                Some((index, select(array.get(index)?, rests)))
+            })
+            .collect();
+        items.sort_by_key(|(index, _)| *index);
+        JValue::Array(items.into_iter().map(|(_, item)| item).collect())
+    } else {
+        JValue::Object(
+            groups
+                .iter()
+                .filter_map(|(token, rests)| {
+                    let key = token.replace("~1", "/").replace("~0", "~");
+                    let member = select(jvalue.get(&key)?, rests);
+                    Some((key, member))
+                })
+                .collect(),
+        )
+    }
+}
+
+impl Predicate {
+    /// Return the pointer the predicate refers to.
+    #[must_use]
+    pub fn pointer(&self) -> &str {
+        match self {
+            Self::Eq(pointer, _)
+            | Self::Ne(pointer, _)
+            | Self::Lt(pointer, _)
+            | Self::Gt(pointer, _)
+            | Self::In(pointer, _)
+            | Self::Exists(pointer)
+            | Self::Regex(pointer, _) => pointer,
+        }
+    }
+
+    // Check whether a value (or its absence) satisfies the predicate
+    fn test(&self, value: Option<&JValue>) -> bool {
+        match (self, value) {
+            (Self::Ne(_, expected), value) => value != Some(expected),
+            (_, None) => false,
+            (Self::Eq(_, expected), Some(value)) => value == expected,
+            (Self::Lt(_, bound), Some(value)) => compare(value, bound) == Some(Ordering::Less),
+            (Self::Gt(_, bound), Some(value)) => compare(value, bound) == Some(Ordering::Greater),
+            (Self::In(_, expected), Some(value)) => expected.contains(value),
+            (Self::Exists(_), Some(_)) => true,
+            // Regular expressions are compiled and evaluated separately
+            (Self::Regex(_, _), Some(_)) => unreachable!(),
+        }
+    }
+}
+
+impl Compiled<'_> {
+    fn pointer(&self) -> &str {
+        match self {
+            Self::Plain(predicate) => predicate.pointer(),
+            Self::Regex(pointer, _) => pointer,
+        }
+    }
+
+    fn test(&self, value: Option<&JValue>) -> bool {
+        match self {
+            Self::Plain(predicate) => predicate.test(value),
+            Self::Regex(_, regex) => value
+                .and_then(JValue::as_str)
+                .is_some_and(|string| regex.is_match(string)),
+        }
+    }
+}
+
+impl Query {
+    /// Create an empty query which matches all documents.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add an arbitrary predicate to the query.
+    #[must_use]
+    pub fn filter(mut self, predicate: Predicate) -> Self {
+        self.predicates.push(predicate);
+        self
+    }
+
+    /// Match documents whose value denoted by `pointer` equals `value`.
+    #[must_use]
+    pub fn eq(self, pointer: &str, value: JValue) -> Self {
+        self.filter(Predicate::Eq(pointer.to_string(), value))
+    }
+
+    /// Match documents whose value denoted by `pointer` does not equal `value`.
+    ///
+    /// Documents which do not contain the value at all are matched as well.
+    #[must_use]
+    pub fn ne(self, pointer: &str, value: JValue) -> Self {
+        self.filter(Predicate::Ne(pointer.to_string(), value))
+    }
+
+    /// Match documents whose value denoted by `pointer` is less than `value`.
+    ///
+    /// Only numbers, strings and booleans are comparable and only with values of the same type.
+    #[must_use]
+    pub fn lt(self, pointer: &str, value: JValue) -> Self {
+        self.filter(Predicate::Lt(pointer.to_string(), value))
+    }
+
+    /// Match documents whose value denoted by `pointer` is greater than `value`.
+    ///
+    /// Only numbers, strings and booleans are comparable and only with values of the same type.
+    #[must_use]
+    pub fn gt(self, pointer: &str, value: JValue) -> Self {
+        self.filter(Predicate::Gt(pointer.to_string(), value))
+    }
+
+    /// Match documents whose value denoted by `pointer` equals any of `values`.
+    #[must_use]
+    pub fn is_in(self, pointer: &str, values: Vec<JValue>) -> Self {
+        self.filter(Predicate::In(pointer.to_string(), values))
+    }
+
+    /// Match documents which contain a value denoted by `pointer`.
+    #[must_use]
+    pub fn exists(self, pointer: &str) -> Self {
+        self.filter(Predicate::Exists(pointer.to_string()))
+    }
+
+    /// Match documents whose string value denoted by `pointer` matches regular expression
+    /// `pattern`.
+    ///
+    /// The pattern is compiled when the query is executed.
+    #[must_use]
+    pub fn regex(self, pointer: &str, pattern: &str) -> Self {
+        self.filter(Predicate::Regex(pointer.to_string(), pattern.to_string()))
+    }
+
+    /// Sort matched documents by a value denoted by `pointer`.
+    ///
+    /// Documents which do not contain the value are always placed at the end. Values of different
+    /// types are ordered as described in [`crate::jutil::total_compare`].
+    #[must_use]
+    pub fn sort_by(mut self, pointer: &str, order: Order) -> Self {
+        self.sort = Some((pointer.to_string(), order));
+        self
+    }
+
+    /// Skip the first `offset` matched documents.
+    #[must_use]
+    pub fn offset(mut self, offset: usize) -> Self {
+        self.offset = offset;
+        self
+    }
+
+    /// Return at most `limit` matched documents.
+    #[must_use]
+    pub fn limit(mut self, limit: usize) -> Self {
+        self.limit = Some(limit);
+        self
+    }
+
+    /// Return only values denoted by `pointers` instead of whole documents.
+    ///
+    /// Projected documents are rebuilt from selected values. Items selected from an array keep
+    /// their order and a pointer nested in another selected one is redundant. Values which do not
+    /// exist are skipped.
+    #[must_use]
+    pub fn project(mut self, pointers: &[&str]) -> Self {
+        self.projection = Some(pointers.iter().map(ToString::to_string).collect());
+        self
+    }
+
+    /// Return predicates of the query.
+    #[must_use]
+    pub fn predicates(&self) -> &[Predicate] {
+        &self.predicates
+    }
+
+    // Compile predicates, validating their arguments
+    fn compile(&self) -> Result<Vec<Compiled<'_>>> {
+        self.predicates
+            .iter()
+            .map(|predicate| match predicate {
+                Predicate::Regex(pointer, pattern) => match Regex::new(pattern) {
+                    Ok(regex) => Ok(Compiled::Regex(pointer, regex)),
+                    Err(err) => Err(Error::custom_err(
+                        CustomKind::InvalidArgument,
+                        &format!("Invalid regular expression '{}': {}", pattern, err),
+                    )),
+                },
+                _ => Ok(Compiled::Plain(predicate)),
+            })
+            .collect()
+    }
+
+    // Check whether a document matches all predicates
+    fn matches(predicates: &[Compiled], document: &JValue) -> Result<bool> {
+        for predicate in predicates {
+            if !predicate.test(resolve(document, predicate.pointer())?) {
+                return Ok(false);
+            }
+        }
+        Ok(true)
+    }
+
+    // Sort documents in place
+    fn sort(&self, matches: &mut [(&str, &JValue)]) -> Result<()> {
+        let Some((pointer, order)) = &self.sort else {
+            return Ok(());
+        };
+
+        // Resolve sort keys upfront to report invalid pointers
+        let mut keyed = Vec::with_capacity(matches.len());
+        for (key, document) in matches.iter() {
+            keyed.push((resolve(document, pointer)?, *key, *document));
+        }
+        keyed.sort_by(|(lhs, _, _), (rhs, _, _)| match (lhs, rhs) {
+            (Some(lhs), Some(rhs)) => match order {
+                Order::Ascending => total_compare(lhs, rhs),
+                Order::Descending => total_compare(rhs, lhs),
+            },
+            (Some(_), None) => Ordering::Less,
+            (None, Some(_)) => Ordering::Greater,
+            (None, None) => Ordering::Equal,
+        });
+
+        for (slot, (_, key, document)) in matches.iter_mut().zip(keyed) {
+            *slot = (key, document);
+        }
+        Ok(())
+    }
+
+    // Build a projected document
+    fn project_document(&self, document: &JValue) -> Result<JValue> {
+        let Some(pointers) = &self.projection else {
+            return Ok(document.clone());
+        };
+
+        let mut selected = Vec::new();
+        for pointer in pointers {
+            if resolve(document, pointer)?.is_some() {
+                selected.push(pointer.as_str());
+            }
+        }
+        Ok(select(document, &selected))
+    }
+
+    /// Execute the query against a set of documents.
+    ///
+    /// Documents are passed as pairs of a key and a document. Matched documents are returned in
+    /// the input order unless sorting was requested.
+    ///
+    /// # Errors
+    /// The function returns a custom library error in case any of pointers or regular expressions
+    /// is invalid.
+    pub fn execute<'a, I>(&self, documents: I) -> Result<Vec<Match>>
+    where
+        I: IntoIterator<Item = (&'a str, &'a JValue)>,
+    {
+        let predicates = self.compile()?;
+
+        let mut matches = Vec::new();
+        for (key, document) in documents {
+            if Self::matches(&predicates, document)? {
+                matches.push((key, document));
+            }
+        }
+        self.sort(&mut matches)?;
+
+        matches
+            .into_iter()
+            .skip(self.offset)
+            .take(self.limit.unwrap_or(usize::MAX))
+            .map(|(key, document)| Ok((key.to_string(), self.project_document(document)?)))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::*;
+    use serde_json::json;
+
+    /* ----------------- */
+    /* ---- Helpers ---- */
+    /* ----------------- */
+
+    // Execute a query and return keys of matched documents
+    fn keys(query: &Query, documents: &[(String, JValue)]) -> Vec<String> {
+        query
+            .execute(documents.iter().map(|(key, doc)| (key.as_str(), doc)))
+            .unwrap()
+            .into_iter()
+            .map(|(key, _)| key)
+            .collect()
+    }
+
+    /* ------------------ */
+    /* ---- Fixtures ---- */
+    /* ------------------ */
+
+    #[fixture]
+    fn words() -> Vec<(String, JValue)> {
+        vec![
+            (
+                "apple".to_string(),
+                json!({"word": "apple", "level": 3, "tags": ["fruit", "food"], "seen": true}),
+            ),
+            (
+                "house".to_string(),
+                json!({"word": "house", "level": 1, "tags": ["building"]}),
+            ),
+            (
+                "tree".to_string(),
+                json!({"word": "tree", "level": 2, "tags": [], "seen": false}),
+            ),
+            (
+                "water".to_string(),
+                json!({"word": "water", "level": "unknown"}),
+            ),
+        ]
+    }
+
+    /* -------------------------- */
+    /* ---- Test definitions ---- */
+    /* -------------------------- */
+
+    #[rstest]
+    fn empty_query_matches_all_documents(words: Vec<(String, JValue)>) {
+        assert_eq!(
+            vec!["apple", "house", "tree", "water"],
+            keys(&Query::new(), &words)
+        );
+    }
+
+    #[rstest]
+    #[case::eq(Query::new().eq("/word", json!("tree")), vec!["tree"])]
+    #[case::eq_nested(Query::new().eq("/tags/0", json!("fruit")), vec!["apple"])]
+    #[case::ne(Query::new().ne("/level", json!(3)), vec!["house", "tree", "water"])]
+    #[case::ne_missing(Query::new().ne("/seen", json!(true)), vec!["house", "tree", "water"])]
+    #[case::lt(Query::new().lt("/level", json!(3)), vec!["house", "tree"])]
+    #[case::gt(Query::new().gt("/level", json!(1)), vec!["apple", "tree"])]
+    #[case::gt_string(Query::new().gt("/word", json!("m")), vec!["tree", "water"])]
+    #[case::is_in(Query::new().is_in("/level", vec![json!(1), json!("unknown")]), vec!["house", "water"])]
+    #[case::exists(Query::new().exists("/seen"), vec!["apple", "tree"])]
+    #[case::exists_array_index(Query::new().exists("/tags/1"), vec!["apple"])]
+    #[case::regex(Query::new().regex("/word", "^[a-h]"), vec!["apple", "house"])]
+    #[case::regex_non_string(Query::new().regex("/level", "1"), vec![])]
+    #[case::conjunction(Query::new().gt("/level", json!(1)).exists("/tags/0"), vec!["apple"])]
+    fn predicates_filter_documents(
+        #[case] query: Query,
+        #[case] expected: Vec<&str>,
+        words: Vec<(String, JValue)>,
+    ) {
+        assert_eq!(expected, keys(&query, &words));
+    }
+
+    #[rstest]
+    #[case::invalid_pointer(Query::new().eq("word", json!("tree")))]
+    #[case::invalid_regex(Query::new().regex("/word", "(unclosed"))]
+    #[case::invalid_sort_pointer(Query::new().sort_by("level", Order::Ascending))]
+    fn invalid_query_produces_error(#[case] query: Query, words: Vec<(String, JValue)>) {
+        query
+            .execute(words.iter().map(|(key, doc)| (key.as_str(), doc)))
+            .unwrap_err();
+    }
+
+    #[rstest]
+    #[case::ascending(Order::Ascending, vec!["house", "tree", "apple", "water"])]
+    #[case::descending(Order::Descending, vec!["water", "apple", "tree", "house"])]
+    fn documents_are_sorted(
+        #[case] order: Order,
+        #[case] expected: Vec<&str>,
+        words: Vec<(String, JValue)>,
+    ) {
+        assert_eq!(
+            expected,
+            keys(&Query::new().sort_by("/level", order), &words)
+        );
+    }
+
+    #[rstest]
+    #[case::ascending(Order::Ascending, vec!["tree", "apple", "house", "water"])]
+    #[case::descending(Order::Descending, vec!["apple", "tree", "house", "water"])]
+    fn documents_without_sort_value_are_placed_last(
+        #[case] order: Order,
+        #[case] expected: Vec<&str>,
+        words: Vec<(String, JValue)>,
+    ) {
+        assert_eq!(
+            expected,
+            keys(&Query::new().sort_by("/seen", order), &words)
+        );
+    }
+
+    #[rstest]
+    #[case::offset(Query::new().offset(1), vec!["house", "tree", "water"])]
+    #[case::limit(Query::new().limit(2), vec!["apple", "house"])]
+    #[case::offset_and_limit(Query::new().offset(1).limit(2), vec!["house", "tree"])]
+    #[case::offset_out_of_range(Query::new().offset(10), vec![])]
+    #[case::sorted_page(Query::new().sort_by("/word", Order::Descending).limit(2), vec!["water", "tree"])]
+    fn documents_are_paginated(
+        #[case] query: Query,
+        #[case] expected: Vec<&str>,
+        words: Vec<(String, JValue)>,
+    ) {
+        assert_eq!(expected, keys(&query, &words));
+    }
+
+    #[rstest]
+    #[case::single_field(&["/word"], json!({"word": "apple"}))]
+    #[case::nested_field(&["/word", "/tags/1"], json!({"word": "apple", "tags": ["food"]}))]
+    #[case::array_items(&["/tags/1", "/tags/0"], json!({"tags": ["fruit", "food"]}))]
+    #[case::duplicate_field(&["/word", "/word"], json!({"word": "apple"}))]
+    #[case::prefix_first(&["/tags", "/tags/1"], json!({"tags": ["fruit", "food"]}))]
+    #[case::prefix_last(&["/tags/1", "/tags"], json!({"tags": ["fruit", "food"]}))]
+    #[case::missing_field(&["/word", "/missing"], json!({"word": "apple"}))]
+    #[case::whole_document(&[""], words()[0].1.clone())]
+    fn documents_are_projected(
+        #[case] pointers: &[&str],
+        #[case] expected: JValue,
+        words: Vec<(String, JValue)>,
+    ) {
+        let query = Query::new().eq("/word", json!("apple")).project(pointers);
+        let matches = query
+            .execute(words.iter().map(|(key, doc)| (key.as_str(), doc)))
+            .unwrap();
+        assert_eq!(vec![("apple".to_string(), expected)], matches);
+    }
+}
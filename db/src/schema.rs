@@ -0,0 +1,765 @@
+//! JSON Schema validation.
+//!
+//! A collection may carry a schema which every document stored inside the collection has to
+//! satisfy (see [`crate::database::Database::set_schema`]). A subset of JSON Schema is supported:
+//!
+//! - `type`, `enum`, `const`
+//! - `required`, `properties`, `additionalProperties`, `minProperties`, `maxProperties`
+//! - `items`, `minItems`, `maxItems`, `uniqueItems`
+//! - `minLength`, `maxLength`, `pattern`
+//! - `minimum`, `maximum`, `exclusiveMinimum`, `exclusiveMaximum`, `multipleOf`
+//! - `allOf`, `anyOf`, `oneOf`, `not`
+//!
+//! Annotations (`$schema`, `$id`, `$comment`, `title`, `description`, `default` and `examples`)
+//! are accepted but ignored. Any other keyword is rejected, so a schema never silently accepts
+//! documents it was meant to reject.
+
+use crate::error::{CustomKind, Error, Result};
+use crate::jutil::{escape_token, total_compare};
+use regex::Regex;
+use serde_json::{Map, Number, Value as JValue};
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::{Display, Formatter};
+
+/// A compiled JSON Schema.
+///
+/// # Examples
+/// ```
+/// use db::schema::Schema;
+/// use serde_json::json;
+///
+/// let schema = Schema::new(json!({
+///     "type": "object",
+///     "required": ["word"],
+///     "properties": {
+///         "word": {"type": "string", "minLength": 1},
+///         "level": {"type": "integer", "minimum": 1}
+///     }
+/// }))
+/// .unwrap();
+///
+/// assert!(schema.validate(&json!({"word": "apple", "level": 1})).is_ok());
+/// let err = schema.validate(&json!({"word": "apple", "level": 0})).unwrap_err();
+/// assert_eq!(
+///     "Library error: Value at '/level' violates schema rule 'minimum': 0 is less than 1",
+///     err.to_string()
+/// );
+/// ```
+#[derive(Debug, Clone)]
+pub struct Schema {
+    source: JValue,
+    root: Node,
+}
+
+// A compiled schema or subschema
+#[derive(Debug, Clone)]
+enum Node {
+    // Boolean schema, either accepting or rejecting every value
+    Bool(bool),
+    Rules(Vec<Rule>),
+}
+
+// A single validation keyword together with its compiled argument
+#[derive(Debug, Clone)]
+enum Rule {
+    Type(Vec<String>),
+    Enum(Vec<JValue>),
+    Const(JValue),
+    Required(Vec<String>),
+    Properties(BTreeMap<String, Node>),
+    // Names of properties defined by `properties` keyword are not additional ones
+    AdditionalProperties(BTreeSet<String>, Box<Node>),
+    MinProperties(usize),
+    MaxProperties(usize),
+    Items(Box<Node>),
+    MinItems(usize),
+    MaxItems(usize),
+    UniqueItems,
+    MinLength(usize),
+    MaxLength(usize),
+    Pattern(Regex),
+    Minimum(f64),
+    Maximum(f64),
+    ExclusiveMinimum(f64),
+    ExclusiveMaximum(f64),
+    MultipleOf(f64),
+    AllOf(Vec<Node>),
+    AnyOf(Vec<Node>),
+    OneOf(Vec<Node>),
+    Not(Box<Node>),
+}
+
+// A failed rule together with a pointer to the value it failed on
+struct Violation {
+    pointer: String,
+    rule: &'static str,
+    details: String,
+}
+
+impl Display for Violation {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Value at '{}' violates schema rule '{}': {}",
+            self.pointer, self.rule, self.details
+        )
+    }
+}
+
+type Checked = std::result::Result<(), Violation>;
+
+const TYPES: [&str; 7] = [
+    "null", "boolean", "integer", "number", "string", "array", "object",
+];
+
+// Return an error describing an invalid part of a schema
+fn invalid_schema(pointer: &str, problem: &str) -> Error {
+    Error::custom_err(
+        CustomKind::InvalidArgument,
+        &format!("Schema keyword at '{}' {}", pointer, problem),
+    )
+}
+
+// Return a violation of a rule
+fn violation(pointer: &str, rule: &'static str, details: String) -> Checked {
+    Err(Violation {
+        pointer: pointer.to_string(),
+        rule,
+        details,
+    })
+}
+
+// Return a pointer to a child value
+fn child(pointer: &str, token: &str) -> String {
+    format!("{}/{}", pointer, escape_token(token))
+}
+
+// Check whether two values are equal. Numbers are compared by their values
+fn equal(lhs: &JValue, rhs: &JValue) -> bool {
+    total_compare(lhs, rhs) == Ordering::Equal
+}
+
+// Check whether a number is a multiple of a divisor. Integers are checked exactly, otherwise the
+// quotient may differ from an integer by rounding errors of the division, proportional to its size
+#[allow(clippy::cast_precision_loss, clippy::cast_possible_truncation)]
+fn is_multiple_of(number: &Number, divisor: f64) -> bool {
+    // Integral divisors which are exactly representable as integers
+    const MAX_EXACT: f64 = 9_007_199_254_740_992.0;
+
+    let integer = number
+        .as_i64()
+        .map(i128::from)
+        .or_else(|| number.as_u64().map(i128::from));
+    if let Some(integer) = integer {
+        if divisor.fract() == 0.0 && divisor <= MAX_EXACT {
+            return integer % (divisor as i128) == 0;
+        }
+    }
+
+    number.as_f64().is_none_or(|number| {
+        let quotient = number / divisor;
+        (quotient - quotient.round()).abs() <= 4.0 * f64::EPSILON * quotient.abs().max(1.0)
+    })
+}
+
+// Return name of a value's type
+fn type_of(value: &JValue) -> &'static str {
+    match value {
+        JValue::Null => "null",
+        JValue::Bool(_) => "boolean",
+        JValue::Number(_) => "number",
+        JValue::String(_) => "string",
+        JValue::Array(_) => "array",
+        JValue::Object(_) => "object",
+    }
+}
+
+// Check whether a value is of a given type. Integers are numbers without a fractional part
+fn has_type(value: &JValue, name: &str) -> bool {
+    match name {
+        "integer" => value.as_f64().is_some_and(|number| number.fract() == 0.0),
+        _ => type_of(value) == name,
+    }
+}
+
+impl Node {
+    fn compile(schema: &JValue, pointer: &str) -> Result<Self> {
+        let object = match schema {
+            JValue::Bool(accept) => return Ok(Self::Bool(*accept)),
+            JValue::Object(object) => object,
+            _ => {
+                return Err(invalid_schema(
+                    pointer,
+                    "has to be either an object or a boolean",
+                ))
+            }
+        };
+
+        let mut rules = Vec::new();
+        for (keyword, value) in object {
+            let at = child(pointer, keyword);
+            let rule = match keyword.as_str() {
+                "type" => Rule::Type(Self::types(value, &at)?),
+                "enum" => Rule::Enum(
+                    value
+                        .as_array()
+                        .ok_or_else(|| invalid_schema(&at, "has to be an array"))?
+                        .clone(),
+                ),
+                "const" => Rule::Const(value.clone()),
+                "required" => Rule::Required(Self::strings(value, &at)?),
+                "properties" => Rule::Properties(Self::properties(value, &at)?),
+                "additionalProperties" => {
+                    let known = object
+                        .get("properties")
+                        .and_then(JValue::as_object)
+                        .map(|properties| properties.keys().cloned().collect())
+                        .unwrap_or_default();
+                    Rule::AdditionalProperties(known, Box::new(Self::compile(value, &at)?))
+                }
+                "minProperties" => Rule::MinProperties(Self::count(value, &at)?),
+                "maxProperties" => Rule::MaxProperties(Self::count(value, &at)?),
+                "items" => Rule::Items(Box::new(Self::compile(value, &at)?)),
+                "minItems" => Rule::MinItems(Self::count(value, &at)?),
+                "maxItems" => Rule::MaxItems(Self::count(value, &at)?),
+                "uniqueItems" => match value.as_bool() {
+                    Some(true) => Rule::UniqueItems,
+                    Some(false) => continue,
+                    None => return Err(invalid_schema(&at, "has to be a boolean")),
+                },
+                "minLength" => Rule::MinLength(Self::count(value, &at)?),
+                "maxLength" => Rule::MaxLength(Self::count(value, &at)?),
+                "pattern" => Rule::Pattern(Self::pattern(value, &at)?),
+                "minimum" => Rule::Minimum(Self::number(value, &at)?),
+                "maximum" => Rule::Maximum(Self::number(value, &at)?),
+                "exclusiveMinimum" => Rule::ExclusiveMinimum(Self::number(value, &at)?),
+                "exclusiveMaximum" => Rule::ExclusiveMaximum(Self::number(value, &at)?),
+                "multipleOf" => match Self::number(value, &at)? {
+                    divisor if divisor > 0.0 => Rule::MultipleOf(divisor),
+                    _ => return Err(invalid_schema(&at, "has to be greater than 0")),
+                },
+                "allOf" => Rule::AllOf(Self::subschemas(value, &at)?),
+                "anyOf" => Rule::AnyOf(Self::subschemas(value, &at)?),
+                "oneOf" => Rule::OneOf(Self::subschemas(value, &at)?),
+                "not" => Rule::Not(Box::new(Self::compile(value, &at)?)),
+                // Annotations do not take part in validation
+                "$schema" | "$id" | "$comment" | "title" | "description" | "default"
+                | "examples" => continue,
+                _ => return Err(invalid_schema(&at, "is not supported")),
+            };
+            rules.push(rule);
+        }
+
+        Ok(Self::Rules(rules))
+    }
+
+    // Compile argument of 'type' keyword
+    fn types(value: &JValue, pointer: &str) -> Result<Vec<String>> {
+        let types = match value {
+            JValue::String(name) => vec![name.clone()],
+            _ => Self::strings(value, pointer)?,
+        };
+        if types.is_empty() || !types.iter().all(|name| TYPES.contains(&name.as_str())) {
+            return Err(invalid_schema(
+                pointer,
+                &format!("has to name types out of: {}", TYPES.join(", ")),
+            ));
+        }
+        Ok(types)
+    }
+
+    // Compile an array of strings
+    fn strings(value: &JValue, pointer: &str) -> Result<Vec<String>> {
+        value
+            .as_array()
+            .and_then(|array| {
+                array
+                    .iter()
+                    .map(|item| item.as_str().map(ToString::to_string))
+                    .collect()
+            })
+            .ok_or_else(|| invalid_schema(pointer, "has to be an array of strings"))
+    }
+
+    // Compile subschemas of object properties
+    fn properties(value: &JValue, pointer: &str) -> Result<BTreeMap<String, Self>> {
+        value
+            .as_object()
+            .ok_or_else(|| invalid_schema(pointer, "has to be an object"))?
+            .iter()
+            .map(|(name, schema)| Ok((name.clone(), Self::compile(schema, &child(pointer, name))?)))
+            .collect()
+    }
+
+    // Compile a non-empty array of subschemas
+    fn subschemas(value: &JValue, pointer: &str) -> Result<Vec<Self>> {
+        match value.as_array() {
+            Some(schemas) if !schemas.is_empty() => schemas
+                .iter()
+                .enumerate()
+                .map(|(i, schema)| Self::compile(schema, &child(pointer, &i.to_string())))
+                .collect(),
+            _ => Err(invalid_schema(pointer, "has to be a non-empty array")),
+        }
+    }
+
+    // Compile a non-negative integer
+    fn count(value: &JValue, pointer: &str) -> Result<usize> {
+        value
+            .as_u64()
+            .and_then(|count| usize::try_from(count).ok())
+            .ok_or_else(|| invalid_schema(pointer, "has to be a non-negative integer"))
+    }
+
+    // Compile a number
+    fn number(value: &JValue, pointer: &str) -> Result<f64> {
+        value
+            .as_f64()
+            .ok_or_else(|| invalid_schema(pointer, "has to be a number"))
+    }
+
+    // Compile a regular expression
+    fn pattern(value: &JValue, pointer: &str) -> Result<Regex> {
+        let pattern = value
+            .as_str()
+            .ok_or_else(|| invalid_schema(pointer, "has to be a string"))?;
+        Regex::new(pattern)
+            .map_err(|err| invalid_schema(pointer, &format!("is not a valid pattern: {}", err)))
+    }
+
+    fn check(&self, value: &JValue, pointer: &str) -> Checked {
+        match self {
+            Self::Bool(true) => Ok(()),
+            Self::Bool(false) => violation(pointer, "false", "no value is allowed".to_string()),
+            Self::Rules(rules) => rules.iter().try_for_each(|rule| rule.check(value, pointer)),
+        }
+    }
+
+    // Check a value against a subschema of a rule. A value rejected by a boolean schema is
+    // reported as a violation of the rule itself
+    fn check_as(
+        &self,
+        value: &JValue,
+        pointer: &str,
+        rule: &'static str,
+        details: &str,
+    ) -> Checked {
+        match self {
+            Self::Bool(false) => violation(pointer, rule, details.to_string()),
+            _ => self.check(value, pointer),
+        }
+    }
+
+    fn accepts(&self, value: &JValue) -> bool {
+        self.check(value, "").is_ok()
+    }
+}
+
+impl Rule {
+    // Rules apply only to values of related types, any other value is accepted
+    #[allow(clippy::too_many_lines)]
+    fn check(&self, value: &JValue, pointer: &str) -> Checked {
+        match (self, value) {
+            (Self::Type(types), _) if !types.iter().any(|name| has_type(value, name)) => violation(
+                pointer,
+                "type",
+                format!("expected {}, found {}", types.join(" or "), type_of(value)),
+            ),
+            (Self::Enum(allowed), _) if !allowed.iter().any(|item| equal(item, value)) => {
+                violation(pointer, "enum", format!("{} is not allowed", value))
+            }
+            (Self::Const(expected), _) if !equal(expected, value) => {
+                violation(pointer, "const", format!("expected {}", expected))
+            }
+            (Self::Required(names), JValue::Object(object)) => names
+                .iter()
+                .find(|name| !object.contains_key(*name))
+                .map_or(Ok(()), |name| {
+                    violation(
+                        &child(pointer, name),
+                        "required",
+                        "property is missing".to_string(),
+                    )
+                }),
+            (Self::Properties(properties), JValue::Object(object)) => {
+                properties.iter().try_for_each(|(name, schema)| {
+                    object.get(name).map_or(Ok(()), |property| {
+                        schema.check_as(
+                            property,
+                            &child(pointer, name),
+                            "properties",
+                            "property is not allowed",
+                        )
+                    })
+                })
+            }
+            (Self::AdditionalProperties(known, schema), JValue::Object(object)) => object
+                .iter()
+                .filter(|(name, _)| !known.contains(*name))
+                .try_for_each(|(name, property)| {
+                    schema.check_as(
+                        property,
+                        &child(pointer, name),
+                        "additionalProperties",
+                        "property is not allowed",
+                    )
+                }),
+            (Self::MinProperties(min), JValue::Object(object)) if object.len() < *min => violation(
+                pointer,
+                "minProperties",
+                format!("{} properties are fewer than {}", object.len(), min),
+            ),
+            (Self::MaxProperties(max), JValue::Object(object)) if object.len() > *max => violation(
+                pointer,
+                "maxProperties",
+                format!("{} properties are more than {}", object.len(), max),
+            ),
+            (Self::Items(schema), JValue::Array(items)) => {
+                items.iter().enumerate().try_for_each(|(i, item)| {
+                    schema.check_as(
+                        item,
+                        &child(pointer, &i.to_string()),
+                        "items",
+                        "item is not allowed",
+                    )
+                })
+            }
+            (Self::MinItems(min), JValue::Array(items)) if items.len() < *min => violation(
+                pointer,
+                "minItems",
+                format!("{} items are fewer than {}", items.len(), min),
+            ),
+            (Self::MaxItems(max), JValue::Array(items)) if items.len() > *max => violation(
+                pointer,
+                "maxItems",
+                format!("{} items are more than {}", items.len(), max),
+            ),
+            (Self::UniqueItems, JValue::Array(items)) => {
+                items.iter().enumerate().try_for_each(|(i, item)| {
+                    items[i + 1..]
+                        .iter()
+                        .position(|other| equal(item, other))
+                        .map_or(Ok(()), |j| {
+                            violation(
+                                pointer,
+                                "uniqueItems",
+                                format!("items {} and {} are equal", i, i + 1 + j),
+                            )
+                        })
+                })
+            }
+            (Self::MinLength(min), JValue::String(string)) if string.chars().count() < *min => {
+                violation(
+                    pointer,
+                    "minLength",
+                    format!("length {} is less than {}", string.chars().count(), min),
+                )
+            }
+            (Self::MaxLength(max), JValue::String(string)) if string.chars().count() > *max => {
+                violation(
+                    pointer,
+                    "maxLength",
+                    format!("length {} is greater than {}", string.chars().count(), max),
+                )
+            }
+            (Self::Pattern(regex), JValue::String(string)) if !regex.is_match(string) => violation(
+                pointer,
+                "pattern",
+                format!("'{}' does not match '{}'", string, regex),
+            ),
+            (Self::Minimum(min), JValue::Number(number))
+                if number.as_f64().is_some_and(|number| number < *min) =>
+            {
+                violation(
+                    pointer,
+                    "minimum",
+                    format!("{} is less than {}", number, min),
+                )
+            }
+            (Self::Maximum(max), JValue::Number(number))
+                if number.as_f64().is_some_and(|number| number > *max) =>
+            {
+                violation(
+                    pointer,
+                    "maximum",
+                    format!("{} is greater than {}", number, max),
+                )
+            }
+            (Self::ExclusiveMinimum(min), JValue::Number(number))
+                if number.as_f64().is_some_and(|number| number <= *min) =>
+            {
+                violation(
+                    pointer,
+                    "exclusiveMinimum",
+                    format!("{} is not greater than {}", number, min),
+                )
+            }
+            (Self::ExclusiveMaximum(max), JValue::Number(number))
+                if number.as_f64().is_some_and(|number| number >= *max) =>
+            {
+                violation(
+                    pointer,
+                    "exclusiveMaximum",
+                    format!("{} is not less than {}", number, max),
+                )
+            }
+            (Self::MultipleOf(divisor), JValue::Number(number))
+                if !is_multiple_of(number, *divisor) =>
+            {
+                violation(
+                    pointer,
+                    "multipleOf",
+                    format!("{} is not a multiple of {}", number, divisor),
+                )
+            }
+            (Self::AllOf(schemas), _) => schemas
+                .iter()
+                .try_for_each(|schema| schema.check(value, pointer)),
+            (Self::AnyOf(schemas), _) if !schemas.iter().any(|schema| schema.accepts(value)) => {
+                violation(
+                    pointer,
+                    "anyOf",
+                    "value does not match any schema".to_string(),
+                )
+            }
+            (Self::OneOf(schemas), _) => {
+                let matched = schemas
+                    .iter()
+                    .filter(|schema| schema.accepts(value))
+                    .count();
+                if matched == 1 {
+                    Ok(())
+                } else {
+                    violation(
+                        pointer,
+                        "oneOf",
+                        format!("value matches {} schemas instead of exactly one", matched),
+                    )
+                }
+            }
+            (Self::Not(schema), _) if schema.accepts(value) => violation(
+                pointer,
+                "not",
+                "value matches a forbidden schema".to_string(),
+            ),
+            _ => Ok(()),
+        }
+    }
+}
+
+impl Schema {
+    /// Compile a schema.
+    ///
+    /// # Errors
+    /// The function returns [`CustomKind::InvalidArgument`] error pointing to the first keyword
+    /// which is either not supported or has an invalid argument.
+    pub fn new(source: JValue) -> Result<Self> {
+        let root = Node::compile(&source, "")?;
+        Ok(Self { source, root })
+    }
+
+    /// Return the schema as it has been passed to [`Schema::new`].
+    #[must_use]
+    pub const fn source(&self) -> &JValue {
+        &self.source
+    }
+
+    /// Validate a value against the schema.
+    ///
+    /// # Errors
+    /// The function returns [`CustomKind::Json`] error in case the value does not satisfy
+    /// the schema. The error names a JSON pointer to the first value which has failed and
+    /// the keyword of the failed rule.
+    pub fn validate(&self, value: &JValue) -> Result<()> {
+        self.root
+            .check(value, "")
+            .map_err(|violation| Error::custom_err(CustomKind::Json, &violation.to_string()))
+    }
+
+    /// Validate documents of a collection against the schema.
+    ///
+    /// # Errors
+    /// The function returns the same errors as [`Schema::validate`], additionally naming the key
+    /// of the first document which has failed.
+    pub fn validate_all(&self, documents: &Map<String, JValue>) -> Result<()> {
+        for (key, document) in documents {
+            if let Err(violation) = self.root.check(document, "") {
+                return Err(Error::custom_err(
+                    CustomKind::Json,
+                    &format!("Document '{}' is invalid. {}", key, violation),
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::*;
+    use serde_json::json;
+
+    /* ----------------- */
+    /* ---- Helpers ---- */
+    /* ----------------- */
+
+    // Validate a value and return the error message, if any
+    fn violation_of(schema: &JValue, value: &JValue) -> Option<String> {
+        let schema = Schema::new(schema.clone()).unwrap();
+        schema.validate(value).err().map(|err| {
+            assert_eq!(CustomKind::Json, *err.get_custom_kind().unwrap());
+            err.to_string()
+        })
+    }
+
+    /* ------------------ */
+    /* ---- Fixtures ---- */
+    /* ------------------ */
+
+    #[fixture]
+    fn word_schema() -> JValue {
+        json!({
+            "$schema": "https://json-schema.org/draft/2020-12/schema",
+            "title": "Word",
+            "type": "object",
+            "required": ["word", "level"],
+            "properties": {
+                "word": {"type": "string", "minLength": 1, "pattern": "^[a-z ]+$"},
+                "level": {"type": "integer", "minimum": 1, "maximum": 5},
+                "tags": {"type": "array", "items": {"type": "string"}, "uniqueItems": true},
+                "translations": {
+                    "type": "object",
+                    "additionalProperties": {"type": "string"}
+                }
+            },
+            "additionalProperties": false
+        })
+    }
+
+    /* -------------------------- */
+    /* ---- Test definitions ---- */
+    /* -------------------------- */
+
+    #[rstest]
+    #[case::minimal(json!({"word": "apple", "level": 1}))]
+    #[case::full(json!({
+        "word": "apple tree",
+        "level": 5.0,
+        "tags": ["fruit", "plant"],
+        "translations": {"de": "Apfelbaum", "pl": "jabłoń"}
+    }))]
+    fn valid_document_passes_validation(#[case] document: JValue, word_schema: JValue) {
+        assert_eq!(None, violation_of(&word_schema, &document));
+    }
+
+    #[rstest]
+    #[case::missing_property(json!({"word": "apple"}), "/level", "required")]
+    #[case::wrong_type(json!({"word": 1, "level": 1}), "/word", "type")]
+    #[case::not_integer(json!({"word": "apple", "level": 1.5}), "/level", "type")]
+    #[case::too_short(json!({"word": "", "level": 1}), "/word", "minLength")]
+    #[case::pattern(json!({"word": "Apple", "level": 1}), "/word", "pattern")]
+    #[case::too_small(json!({"word": "apple", "level": 0}), "/level", "minimum")]
+    #[case::too_large(json!({"word": "apple", "level": 6}), "/level", "maximum")]
+    #[case::item_type(json!({"word": "apple", "level": 1, "tags": ["a", 1]}), "/tags/1", "type")]
+    #[case::unique_items(json!({"word": "apple", "level": 1, "tags": ["a", "a"]}), "/tags", "uniqueItems")]
+    #[case::additional_property(
+        json!({"word": "apple", "level": 1, "note": ""}),
+        "/note",
+        "additionalProperties"
+    )]
+    #[case::nested_additional_property(
+        json!({"word": "apple", "level": 1, "translations": {"a/b": 1}}),
+        "/translations/a~1b",
+        "type"
+    )]
+    #[case::not_an_object(json!(["apple"]), "", "type")]
+    fn violation_names_pointer_and_rule(
+        #[case] document: JValue,
+        #[case] pointer: &str,
+        #[case] rule: &str,
+        word_schema: JValue,
+    ) {
+        let message = violation_of(&word_schema, &document).unwrap();
+        let expected = format!("Value at '{}' violates schema rule '{}'", pointer, rule);
+        assert!(message.contains(&expected), "{}", message);
+    }
+
+    #[rstest]
+    #[case::enumeration(json!({"enum": ["a", 1]}), json!(1.0), json!("b"))]
+    #[case::constant(json!({"const": {"a": [1]}}), json!({"a": [1]}), json!({"a": []}))]
+    #[case::exclusive_minimum(json!({"exclusiveMinimum": 1}), json!(1.5), json!(1))]
+    #[case::exclusive_maximum(json!({"exclusiveMaximum": 1}), json!(0), json!(1))]
+    #[case::multiple_of(json!({"multipleOf": 0.5}), json!(2.5), json!(2.25))]
+    #[case::multiple_of_fraction(json!({"multipleOf": 0.1}), json!(0.3), json!(0.35))]
+    #[case::multiple_of_large_integer(json!({"multipleOf": 2}), json!(9_007_199_254_740_994_u64), json!(9_007_199_254_740_993_u64))]
+    #[case::max_length(json!({"maxLength": 2}), json!("żą"), json!("abc"))]
+    #[case::min_items(json!({"minItems": 1}), json!([1]), json!([]))]
+    #[case::max_items(json!({"maxItems": 1}), json!([1]), json!([1, 2]))]
+    #[case::min_properties(json!({"minProperties": 1}), json!({"a": 1}), json!({}))]
+    #[case::max_properties(json!({"maxProperties": 0}), json!({}), json!({"a": 1}))]
+    #[case::all_of(json!({"allOf": [{"type": "number"}, {"minimum": 2}]}), json!(2), json!(1))]
+    #[case::any_of(json!({"anyOf": [{"type": "string"}, {"minimum": 2}]}), json!("a"), json!(1))]
+    #[case::one_of(json!({"oneOf": [{"type": "number"}, {"minimum": 2}]}), json!(1), json!(3))]
+    #[case::not(json!({"not": {"type": "null"}}), json!(0), json!(null))]
+    #[case::multiple_types(json!({"type": ["string", "null"]}), json!(null), json!(0))]
+    #[case::false_schema(json!(false), json!(null), json!(null))]
+    fn supported_keywords_are_validated(
+        #[case] schema: JValue,
+        #[case] valid: JValue,
+        #[case] invalid: JValue,
+    ) {
+        if schema != json!(false) {
+            assert_eq!(None, violation_of(&schema, &valid));
+        }
+        assert!(violation_of(&schema, &invalid).is_some());
+    }
+
+    #[rstest]
+    fn rules_do_not_apply_to_values_of_unrelated_types() {
+        let schema = json!({"minimum": 1, "minLength": 1, "items": false, "required": ["a"]});
+        for value in [
+            json!("a"),
+            json!(2),
+            json!({"a": 1}),
+            json!([]),
+            json!(null),
+        ] {
+            assert_eq!(None, violation_of(&schema, &value));
+        }
+    }
+
+    #[rstest]
+    #[case::not_a_schema(json!(1), "")]
+    #[case::unsupported_keyword(json!({"$ref": "#/definitions/word"}), "/$ref")]
+    #[case::unknown_type(json!({"type": "text"}), "/type")]
+    #[case::invalid_count(json!({"minLength": -1}), "/minLength")]
+    #[case::invalid_pattern(json!({"pattern": "("}), "/pattern")]
+    #[case::invalid_multiple(json!({"multipleOf": 0}), "/multipleOf")]
+    #[case::empty_subschemas(json!({"anyOf": []}), "/anyOf")]
+    #[case::nested(json!({"properties": {"word": {"maxItems": "1"}}}), "/properties/word/maxItems")]
+    fn invalid_schema_is_rejected(#[case] schema: JValue, #[case] pointer: &str) {
+        let err = Schema::new(schema).unwrap_err();
+        assert_eq!(CustomKind::InvalidArgument, *err.get_custom_kind().unwrap());
+        assert!(err.to_string().contains(&format!("at '{}'", pointer)));
+    }
+
+    #[rstest]
+    fn all_documents_of_collection_are_validated(word_schema: JValue) {
+        let schema = Schema::new(word_schema).unwrap();
+        let documents = json!({
+            "apple": {"word": "apple", "level": 1},
+            "house": {"word": "house"}
+        });
+
+        let err = schema
+            .validate_all(documents.as_object().unwrap())
+            .unwrap_err();
+        assert_eq!(
+            "Library error: Document 'house' is invalid. Value at '/level' violates schema rule \
+             'required': property is missing",
+            err.to_string()
+        );
+    }
+}
@@ -0,0 +1,511 @@
+//! Storage backends of the filesystem abstraction layer.
+//!
+//! [`Io`](crate::io::Io) does not touch files on its own. Raw file operations are delegated to
+//! a storage backend instead, which makes it possible to keep a database somewhere else than in
+//! a directory on a disk. Two backends are provided: [`FileStorage`] keeping files inside
+//! a directory and [`MemoryStorage`] keeping them in RAM, e.g. for tests or ephemeral sessions.
+
+use crate::error::{CustomKind, Error, Result};
+use crate::io::OpenMode;
+use std::collections::btree_map::Entry;
+use std::collections::BTreeMap;
+use std::ffi::OsStr;
+use std::fmt::Debug;
+use std::fs;
+use std::fs::{File, TryLockError};
+use std::io;
+use std::io::{ErrorKind, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+
+/// A backend storing files of a database.
+///
+/// Paths passed to a storage are always relative to its root and have been already validated by
+/// the IO layer, i.e. they never lead outside of the root. Files are addressed by their paths only,
+/// thus a storage does not have to model directories in any way.
+pub trait Storage: Debug + Send {
+    /// Return the root path of the storage.
+    ///
+    /// The path is used to resolve absolute paths passed to the IO layer and to describe files
+    /// in error messages.
+    fn root(&self) -> &Path;
+
+    /// Check whether a file exists.
+    fn exists(&self, path: &Path) -> bool;
+
+    /// Read the whole content of an existing file.
+    ///
+    /// # Errors
+    /// The function returns an IO error if the file cannot be read.
+    fn read(&self, path: &Path) -> Result<Vec<u8>>;
+
+    /// Replace content of an existing file.
+    ///
+    /// The content has to be replaced atomically, i.e. the file holds either the old or the new
+    /// content should the operation be interrupted.
+    ///
+    /// # Errors
+    /// The function returns an IO error if the file cannot be written.
+    fn write(&self, path: &Path, data: &[u8]) -> Result<()>;
+
+    /// Create a new file holding the given content.
+    ///
+    /// No file is left behind if the operation fails.
+    ///
+    /// # Errors
+    /// The function returns an IO error if the file already exists or cannot be written.
+    fn write_new(&self, path: &Path, data: &[u8]) -> Result<()>;
+
+    /// Append data to a file, creating the file if it does not exist yet.
+    ///
+    /// The data has to be durable once the function returns.
+    ///
+    /// # Errors
+    /// The function returns an IO error if the file cannot be written.
+    fn append(&self, path: &Path, data: &[u8]) -> Result<()>;
+
+    /// Remove an existing file.
+    ///
+    /// # Errors
+    /// The function returns an IO error if the file cannot be removed.
+    fn remove(&self, path: &Path) -> Result<()>;
+
+    /// Rename an existing file. The target path is expected not to exist.
+    ///
+    /// # Errors
+    /// The function returns an IO error if the file cannot be renamed.
+    fn rename(&self, from: &Path, to: &Path) -> Result<()>;
+
+    /// Return paths of all files kept by the storage, sorted.
+    ///
+    /// Paths are relative to the root, the same way as paths passed to other functions.
+    ///
+    /// # Errors
+    /// The function returns an IO error if the files cannot be listed.
+    fn list(&self) -> Result<Vec<PathBuf>>;
+
+    /// Lock the storage for as long as it exists.
+    ///
+    /// `path` points to a file which may be used to hold the lock. The default implementation
+    /// does nothing, which suits storages that cannot be shared between processes.
+    ///
+    /// # Errors
+    /// The function returns [`CustomKind::Locked`] error if the storage is locked by another
+    /// instance in a conflicting mode.
+    fn lock(&mut self, _path: &Path, _mode: OpenMode) -> Result<()> {
+        Ok(())
+    }
+}
+
+/// A storage keeping files inside a directory of the filesystem.
+///
+/// Every write is flushed to the disk before the function performing it returns. The storage
+/// is locked with an advisory file lock, thus it may be safely shared between processes.
+#[derive(Debug)]
+pub struct FileStorage {
+    root: PathBuf,
+    // Lock file, kept open to retain the lock
+    lock: Option<File>,
+}
+
+impl FileStorage {
+    const TEMP_SUFFIX: &'static str = ".tmp";
+
+    /// Create a storage whose files are placed inside the `root` directory.
+    ///
+    /// The directory does not have to exist yet. It is created together with the first file.
+    pub fn new<P>(root: P) -> Self
+    where
+        P: AsRef<Path>,
+    {
+        Self {
+            root: root.as_ref().to_path_buf(),
+            lock: None,
+        }
+    }
+
+    // Flush directory entries (e.g. created or renamed files) to the disk
+    fn sync_dir(dir: &Path) -> Result<()> {
+        // Directories cannot be opened as files on every platform
+        if cfg!(unix) {
+            File::open(dir)?.sync_all()?;
+        }
+        Ok(())
+    }
+
+    // Flush directory entries of a directory the file is placed in
+    fn sync_parent(file_path: &Path) -> Result<()> {
+        file_path.parent().map_or(Ok(()), Self::sync_dir)
+    }
+
+    // Return path of a temporary file placed next to the given one
+    fn temp_path(file_path: &Path) -> PathBuf {
+        let mut file_name = OsStr::new(".").to_os_string();
+        file_name.push(file_path.file_name().unwrap_or_default());
+        file_name.push(Self::TEMP_SUFFIX);
+        file_path.with_file_name(file_name)
+    }
+
+    // Collect paths of files placed inside a directory and its subdirectories, relative to the root
+    fn list_dir(&self, dir: &Path, paths: &mut Vec<PathBuf>) -> Result<()> {
+        for entry in fs::read_dir(dir)? {
+            let path = entry?.path();
+            if path.is_dir() {
+                self.list_dir(&path, paths)?;
+            } else if let Ok(relative_path) = path.strip_prefix(&self.root) {
+                paths.push(relative_path.to_path_buf());
+            }
+        }
+        Ok(())
+    }
+
+    // Write data into an opened file and flush it to the disk
+    fn write_file(mut file: File, data: &[u8]) -> Result<()> {
+        file.write_all(data)?;
+        file.sync_all()?;
+        Ok(())
+    }
+}
+
+impl Storage for FileStorage {
+    fn root(&self) -> &Path {
+        &self.root
+    }
+
+    fn exists(&self, path: &Path) -> bool {
+        self.root.join(path).is_file()
+    }
+
+    fn read(&self, path: &Path) -> Result<Vec<u8>> {
+        Ok(fs::read(self.root.join(path))?)
+    }
+
+    // The content is written into a temporary file placed next to the original one, which is then
+    // renamed over the original
+    fn write(&self, path: &Path, data: &[u8]) -> Result<()> {
+        let file_path = self.root.join(path);
+        let temp_path = Self::temp_path(&file_path);
+
+        let temp_file = File::create(&temp_path)?;
+        if let Err(err) = Self::write_file(temp_file, data) {
+            // Do not leave partially written file behind. The original error is more relevant
+            // than a possible failure of file removal
+            let _ = fs::remove_file(temp_path);
+            return Err(err);
+        }
+
+        fs::rename(&temp_path, &file_path)?;
+        Self::sync_parent(&file_path)
+    }
+
+    fn write_new(&self, path: &Path, data: &[u8]) -> Result<()> {
+        let file_path = self.root.join(path);
+
+        // Create directories leading to the file
+        if let Some(dirs) = file_path.parent() {
+            fs::create_dir_all(dirs)?;
+        }
+
+        let file = fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&file_path)?;
+        if let Err(err) = Self::write_file(file, data) {
+            // Do not leave partially written file behind
+            let _ = fs::remove_file(file_path);
+            return Err(err);
+        }
+
+        Self::sync_parent(&file_path)
+    }
+
+    fn append(&self, path: &Path, data: &[u8]) -> Result<()> {
+        let file_path = self.root.join(path);
+        let created = !file_path.exists();
+
+        let mut file = fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&file_path)?;
+        file.write_all(data)?;
+        file.sync_data()?;
+
+        if created {
+            Self::sync_parent(&file_path)?;
+        }
+        Ok(())
+    }
+
+    fn remove(&self, path: &Path) -> Result<()> {
+        let file_path = self.root.join(path);
+        fs::remove_file(&file_path)?;
+        Self::sync_parent(&file_path)
+    }
+
+    // Entries of both directories are flushed, so the file is not lost nor duplicated by a crash
+    fn rename(&self, from: &Path, to: &Path) -> Result<()> {
+        let from_path = self.root.join(from);
+        let to_path = self.root.join(to);
+        fs::rename(&from_path, &to_path)?;
+        Self::sync_parent(&to_path)?;
+        if from_path.parent() != to_path.parent() {
+            Self::sync_parent(&from_path)?;
+        }
+        Ok(())
+    }
+
+    fn list(&self) -> Result<Vec<PathBuf>> {
+        let mut paths = Vec::new();
+        self.list_dir(&self.root, &mut paths)?;
+        paths.sort();
+        Ok(paths)
+    }
+
+    // Acquire an advisory lock without blocking
+    fn lock(&mut self, path: &Path, mode: OpenMode) -> Result<()> {
+        let file = fs::OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(self.root.join(path))?;
+
+        let result = match mode {
+            OpenMode::ReadOnly => file.try_lock_shared(),
+            OpenMode::ReadWrite => file.try_lock(),
+        };
+        match result {
+            Ok(()) => {
+                self.lock = Some(file);
+                Ok(())
+            }
+            Err(TryLockError::WouldBlock) => Err(Error::custom_err(
+                CustomKind::Locked,
+                &format!(
+                    "Database {} is locked by another instance",
+                    self.root.display()
+                ),
+            )),
+            Err(TryLockError::Error(err)) => Err(Error::Io(err)),
+        }
+    }
+}
+
+/// A storage keeping files in memory.
+///
+/// Nothing is ever written to the disk, hence all files are gone once the last instance referring
+/// to them is dropped. Clones of a storage share the same files, which allows a database to be
+/// opened again after it has been closed, as long as a clone of its storage has been kept.
+///
+/// The storage is never locked.
+#[derive(Debug, Clone, Default)]
+pub struct MemoryStorage {
+    files: Arc<Mutex<BTreeMap<PathBuf, Vec<u8>>>>,
+}
+
+impl MemoryStorage {
+    const ROOT: &'static str = ":memory:";
+
+    /// Create an empty storage.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Access files of the storage. Files are never left in an inconsistent state, so a poisoned
+    // mutex is not a problem
+    fn files(&self) -> MutexGuard<'_, BTreeMap<PathBuf, Vec<u8>>> {
+        self.files.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    // Return an IO error related to a file
+    fn file_err(kind: ErrorKind, path: &Path) -> Error {
+        Error::Io(io::Error::new(
+            kind,
+            format!("{}: {}", kind, Path::new(Self::ROOT).join(path).display()),
+        ))
+    }
+}
+
+impl Storage for MemoryStorage {
+    fn root(&self) -> &Path {
+        Path::new(Self::ROOT)
+    }
+
+    fn exists(&self, path: &Path) -> bool {
+        self.files().contains_key(path)
+    }
+
+    fn read(&self, path: &Path) -> Result<Vec<u8>> {
+        self.files()
+            .get(path)
+            .cloned()
+            .ok_or_else(|| Self::file_err(ErrorKind::NotFound, path))
+    }
+
+    fn write(&self, path: &Path, data: &[u8]) -> Result<()> {
+        self.files().insert(path.to_path_buf(), data.to_vec());
+        Ok(())
+    }
+
+    fn write_new(&self, path: &Path, data: &[u8]) -> Result<()> {
+        if path.as_os_str().is_empty() {
+            return Err(Self::file_err(ErrorKind::AlreadyExists, path));
+        }
+        match self.files().entry(path.to_path_buf()) {
+            Entry::Occupied(_) => Err(Self::file_err(ErrorKind::AlreadyExists, path)),
+            Entry::Vacant(entry) => {
+                entry.insert(data.to_vec());
+                Ok(())
+            }
+        }
+    }
+
+    fn append(&self, path: &Path, data: &[u8]) -> Result<()> {
+        self.files()
+            .entry(path.to_path_buf())
+            .or_default()
+            .extend_from_slice(data);
+        Ok(())
+    }
+
+    fn remove(&self, path: &Path) -> Result<()> {
+        self.files()
+            .remove(path)
+            .map(|_| ())
+            .ok_or_else(|| Self::file_err(ErrorKind::NotFound, path))
+    }
+
+    fn rename(&self, from: &Path, to: &Path) -> Result<()> {
+        let mut files = self.files();
+        let content = files
+            .remove(from)
+            .ok_or_else(|| Self::file_err(ErrorKind::NotFound, from))?;
+        files.insert(to.to_path_buf(), content);
+        drop(files);
+        Ok(())
+    }
+
+    fn list(&self) -> Result<Vec<PathBuf>> {
+        Ok(self.files().keys().cloned().collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::*;
+    use tempdir::TempDir;
+
+    /* ------------------ */
+    /* ---- Fixtures ---- */
+    /* ------------------ */
+
+    // Return a file storage placed inside a temporary directory
+    #[fixture]
+    fn file_storage() -> (FileStorage, TempDir) {
+        let temp_dir = TempDir::new("").unwrap();
+        (FileStorage::new(temp_dir.path()), temp_dir)
+    }
+
+    /* -------------------------- */
+    /* ---- Test definitions ---- */
+    /* -------------------------- */
+
+    #[rstest]
+    #[case("file.json", ".file.json.tmp")]
+    #[case("sub/file.json", "sub/.file.json.tmp")]
+    fn temporary_file_is_placed_next_to_original_one(#[case] path: &str, #[case] expected: &str) {
+        let base = Path::new("/database");
+        assert_eq!(
+            base.join(expected),
+            FileStorage::temp_path(&base.join(path))
+        );
+    }
+
+    #[rstest]
+    fn directories_are_created_together_with_new_file(file_storage: (FileStorage, TempDir)) {
+        let (storage, temp_dir) = file_storage;
+        let path = Path::new("sub/sub/file.json");
+
+        storage.write_new(path, b"{}").unwrap();
+        assert!(temp_dir.path().join(path).is_file());
+        assert!(storage.exists(path));
+        assert!(!storage.exists(Path::new("sub")));
+
+        temp_dir.close().unwrap();
+    }
+
+    #[rstest]
+    fn memory_storage_files_are_written_and_read() {
+        let storage = MemoryStorage::new();
+        let path = Path::new("sub/file.json");
+
+        storage.write_new(path, b"old").unwrap();
+        storage.write(path, b"new").unwrap();
+        storage.append(path, b"er").unwrap();
+        assert_eq!(b"newer".to_vec(), storage.read(path).unwrap());
+        // Directories do not exist on their own
+        assert!(!storage.exists(Path::new("sub")));
+    }
+
+    #[rstest]
+    #[case::existing_file("file.json")]
+    #[case::empty_path("")]
+    fn memory_storage_does_not_create_file_twice(#[case] path: &str) {
+        let storage = MemoryStorage::new();
+        storage.write_new(Path::new("file.json"), b"{}").unwrap();
+
+        let err = storage.write_new(Path::new(path), b"{}").unwrap_err();
+        // Expect IO error, the same as produced by the filesystem
+        assert!(!err.is_custom());
+    }
+
+    #[rstest]
+    fn memory_storage_files_are_renamed_and_removed() {
+        let storage = MemoryStorage::new();
+
+        storage.write_new(Path::new("old.json"), b"{}").unwrap();
+        storage
+            .rename(Path::new("old.json"), Path::new("new.json"))
+            .unwrap();
+        assert!(!storage.exists(Path::new("old.json")));
+        storage.remove(Path::new("new.json")).unwrap();
+        assert!(!storage.exists(Path::new("new.json")));
+
+        assert!(!storage
+            .remove(Path::new("new.json"))
+            .unwrap_err()
+            .is_custom());
+        assert!(!storage.read(Path::new("new.json")).unwrap_err().is_custom());
+    }
+
+    #[rstest]
+    fn memory_storage_clones_share_files() {
+        let storage = MemoryStorage::new();
+        let clone = storage.clone();
+
+        clone.write_new(Path::new("file.json"), b"{}").unwrap();
+        assert!(storage.exists(Path::new("file.json")));
+    }
+
+    #[rstest]
+    fn files_are_listed_recursively(file_storage: (FileStorage, TempDir)) {
+        let (storage, temp_dir) = file_storage;
+        let memory_storage = MemoryStorage::new();
+        let paths = ["sub/sub/file.json", "file.json", "sub/.file.json.tmp"];
+
+        for path in paths {
+            storage.write_new(Path::new(path), b"{}").unwrap();
+            memory_storage.write_new(Path::new(path), b"{}").unwrap();
+        }
+        let expected: Vec<PathBuf> = ["file.json", "sub/.file.json.tmp", "sub/sub/file.json"]
+            .iter()
+            .map(PathBuf::from)
+            .collect();
+        assert_eq!(expected, storage.list().unwrap());
+        assert_eq!(expected, memory_storage.list().unwrap());
+
+        temp_dir.close().unwrap();
+    }
+}
@@ -0,0 +1,144 @@
+//! Write-ahead log records.
+//!
+//! Rather than rewriting a whole collection file on every change, each document mutation is
+//! appended to a log file placed inside the database directory. Collections are written back
+//! to their files (checkpointed) periodically, after which the log is cleared. Any mutation which
+//! has not been checkpointed yet is replayed when a database is opened.
+//!
+//! The log consists of records, each one holding a batch of mutations. A record is appended
+//! to the log in a single write, hence mutations of a record are either all replayed or none
+//! of them is, in case the write has been interrupted.
+//!
+//! Records are idempotent, thus replaying a record which has been already checkpointed does not
+//! change the state of a collection.
+
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value as JValue};
+
+/// A single record of the write-ahead log.
+pub type Record = Vec<Mutation>;
+
+/// A single document mutation recorded in the write-ahead log.
+#[non_exhaustive]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(tag = "op", rename_all = "snake_case")]
+pub enum Mutation {
+    /// Document stored under a key, either inserted or replacing an existing one
+    Put {
+        /// Name of a collection
+        collection: String,
+        /// Key of a document
+        key: String,
+        /// New content of a document
+        document: JValue,
+    },
+    /// Document removed
+    Delete {
+        /// Name of a collection
+        collection: String,
+        /// Key of a document
+        key: String,
+    },
+}
+
+impl Mutation {
+    /// Return name of the collection the mutation refers to.
+    #[must_use]
+    pub fn collection(&self) -> &str {
+        match self {
+            Self::Put { collection, .. } | Self::Delete { collection, .. } => collection,
+        }
+    }
+
+    /// Return key of the document the mutation refers to.
+    #[must_use]
+    pub fn key(&self) -> &str {
+        match self {
+            Self::Put { key, .. } | Self::Delete { key, .. } => key,
+        }
+    }
+
+    /// Apply the mutation to documents of a collection.
+    ///
+    /// A document which was stored under the mutated key before is returned.
+    pub fn apply(self, documents: &mut Map<String, JValue>) -> Option<JValue> {
+        match self {
+            Self::Put { key, document, .. } => documents.insert(key, document),
+            Self::Delete { key, .. } => documents.remove(&key),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::*;
+    use serde_json::json;
+
+    /* ------------------ */
+    /* ---- Fixtures ---- */
+    /* ------------------ */
+
+    #[fixture]
+    fn documents() -> Map<String, JValue> {
+        json!({"apple": {"word": "apple"}})
+            .as_object()
+            .unwrap()
+            .clone()
+    }
+
+    #[fixture]
+    fn put() -> Mutation {
+        Mutation::Put {
+            collection: "words".to_string(),
+            key: "tree".to_string(),
+            document: json!({"word": "tree"}),
+        }
+    }
+
+    #[fixture]
+    fn delete() -> Mutation {
+        Mutation::Delete {
+            collection: "words".to_string(),
+            key: "apple".to_string(),
+        }
+    }
+
+    /* -------------------------- */
+    /* ---- Test definitions ---- */
+    /* -------------------------- */
+
+    #[rstest]
+    fn mutation_is_serialized_with_operation_tag(put: Mutation, delete: Mutation) {
+        assert_eq!(
+            json!({"op": "put", "collection": "words", "key": "tree", "document": {"word": "tree"}}),
+            serde_json::to_value(&put).unwrap()
+        );
+        assert_eq!(
+            json!({"op": "delete", "collection": "words", "key": "apple"}),
+            serde_json::to_value(&delete).unwrap()
+        );
+    }
+
+    #[rstest]
+    fn put_stores_document(put: Mutation, mut documents: Map<String, JValue>) {
+        assert!(put.clone().apply(&mut documents).is_none());
+        assert_eq!(json!({"word": "tree"}), documents["tree"]);
+
+        // Replaying the same mutation does not change anything
+        put.apply(&mut documents);
+        assert_eq!(2, documents.len());
+    }
+
+    #[rstest]
+    fn delete_removes_document(delete: Mutation, mut documents: Map<String, JValue>) {
+        assert_eq!(
+            Some(json!({"word": "apple"})),
+            delete.clone().apply(&mut documents)
+        );
+        assert!(documents.is_empty());
+
+        // Replaying the same mutation does not change anything
+        assert!(delete.apply(&mut documents).is_none());
+    }
+}